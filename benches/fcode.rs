@@ -0,0 +1,371 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hint::black_box;
+
+mod protobench {
+	include!("common/monster.rs");
+}
+
+// types from the flatbuffers benchmark
+mod benchfb {
+	use serde::{Deserialize, Serialize};
+
+	#[derive(Serialize, Deserialize)]
+	pub enum Enum {
+		Apples,
+		Pears,
+		Bananas,
+	}
+
+	#[derive(Serialize, Deserialize)]
+	pub struct Foo {
+		pub id: u64,
+		pub count: i16,
+		pub prefix: i8,
+		pub length: u32,
+	}
+
+	#[derive(Serialize, Deserialize)]
+	pub struct Bar {
+		pub parent: Foo,
+		pub time: i32,
+		pub ratio: f32,
+		pub size: u16,
+	}
+
+	#[derive(Serialize, Deserialize)]
+	pub struct FooBar {
+		pub sibling: Bar,
+		pub name: String,
+		pub rating: f64,
+		pub postfix: u8,
+	}
+
+	#[derive(Serialize, Deserialize)]
+	pub struct FooBarContainer {
+		pub list: Vec<FooBar>,
+		pub initialized: bool,
+		pub fruit: Enum,
+		pub location: String,
+	}
+
+	pub fn monster() -> FooBarContainer {
+		FooBarContainer {
+			list: (0i32..3)
+				.map(|i| FooBar {
+					sibling: Bar {
+						parent: Foo {
+							id: 0xABADCAFEABADCAFE + i as u64,
+							count: 10000 + i as i16,
+							prefix: '@' as i8 + i as i8,
+							length: 1000000 + i as u32,
+						},
+						time: 123456 + i,
+						ratio: 3.141519 + i as f32,
+						size: 10000 + i as u16,
+					},
+					name: "Hello, World!".into(),
+					rating: 3.141_543_243_244_554_4 + i as f64,
+					postfix: b'!' + i as u8,
+				})
+				.collect(),
+			initialized: true,
+			fruit: Enum::Bananas,
+			location: "http://google.com/flatbuffers/".into(),
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize)]
+struct StructOfScalars {
+	x: i32,
+	y: f64,
+	z: i64,
+	a1: i32,
+	a2: i32,
+	a3: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StructWithString {
+	x: i32,
+	y: i32,
+	s: String,
+}
+
+// serialize and deserialize a value in separate benchmarks, reporting throughput in bytes of encoded data
+fn bench_value<T: Serialize + DeserializeOwned>(c: &mut Criterion, name: &str, value: &T) {
+	let encoded = fcode::to_bytes(value).unwrap();
+	let mut group = c.benchmark_group(name);
+	group.throughput(Throughput::Bytes(encoded.len() as u64));
+	let mut buf = Vec::with_capacity(encoded.len());
+	group.bench_function("serialize", |b| {
+		b.iter(|| {
+			buf.clear();
+			fcode::to_writer(&mut buf, black_box(value)).unwrap();
+		})
+	});
+	group.bench_function("deserialize", |b| {
+		b.iter(|| fcode::from_bytes::<T>(black_box(&encoded)).unwrap())
+	});
+	group.finish();
+}
+
+fn scalars(c: &mut Criterion) {
+	bench_value(c, "i32", &42i32);
+	bench_value(
+		c,
+		"struct_of_scalars",
+		&StructOfScalars {
+			x: 42,
+			y: 684.0,
+			z: 84,
+			a1: 1,
+			a2: 2,
+			a3: 3,
+		},
+	);
+}
+
+fn strings(c: &mut Criterion) {
+	bench_value(
+		c,
+		"struct_with_string",
+		&StructWithString {
+			x: 42,
+			y: 43,
+			s: "to be or not to be".to_string(),
+		},
+	);
+	bench_value(c, "long_string", &"x".repeat(4096));
+
+	// UTF-8 validation of many large strings, borrowed to leave out the copy
+	let value: Vec<String> = (0..16).map(|i| format!("{} ü ", i).repeat(1000)).collect();
+	let encoded = fcode::to_bytes(&value).unwrap();
+	let mut group = c.benchmark_group("large_strings");
+	group.throughput(Throughput::Bytes(encoded.len() as u64));
+	group.bench_function("checked_utf8", |b| {
+		b.iter(|| {
+			let mut de = fcode::Deserializer::from_bytes(black_box(&encoded));
+			Vec::<&str>::deserialize(&mut de).unwrap()
+		})
+	});
+	group.bench_function("unchecked_utf8", |b| {
+		b.iter(|| {
+			// safety: the input was just serialized from strings
+			let mut de = unsafe { fcode::Deserializer::from_bytes_unchecked_utf8(black_box(&encoded)) };
+			Vec::<&str>::deserialize(&mut de).unwrap()
+		})
+	});
+	group.finish();
+}
+
+fn monster(c: &mut Criterion) {
+	bench_value(c, "monster", &benchfb::monster());
+}
+
+fn large_vec(c: &mut Criterion) {
+	let v: Vec<i32> = (0..10_000).map(|i| i * 37 - 100_000).collect();
+	bench_value(c, "vec_i32_10k", &v);
+}
+
+fn packed_floats(c: &mut Criterion) {
+	let v = vec![1.0f32; 10_000];
+	bench_value(c, "vec_f32_10k", &v);
+	bench_value(c, "vec_f32_10k_packed", &fcode::PackedFloats(v));
+}
+
+// repeated to_writer calls versus a single serializer for all messages
+fn reuse(c: &mut Criterion) {
+	let value = StructOfScalars {
+		x: 42,
+		y: 684.0,
+		z: 84,
+		a1: 1,
+		a2: 2,
+		a3: 3,
+	};
+	let mut group = c.benchmark_group("struct_of_scalars_x100");
+	let mut buf = Vec::with_capacity(4096);
+	group.bench_function("to_writer", |b| {
+		b.iter(|| {
+			buf.clear();
+			for _ in 0..100 {
+				fcode::to_writer(&mut buf, black_box(&value)).unwrap();
+			}
+		})
+	});
+	let mut ser = fcode::Serializer::new(&mut buf);
+	group.bench_function("reused_serializer", |b| {
+		b.iter(|| {
+			ser.get_mut().clear();
+			for _ in 0..100 {
+				ser.serialize_value(black_box(&value)).unwrap();
+			}
+		})
+	});
+	group.finish();
+}
+
+// a Vec<i32> that ignores the deserializer's size hint, to show the cost of growing the vector
+struct NoHint(Vec<i32>);
+
+impl<'de> Deserialize<'de> for NoHint {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		struct Visitor;
+		impl<'de> serde::de::Visitor<'de> for Visitor {
+			type Value = NoHint;
+			fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+				f.write_str("a sequence")
+			}
+			fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<NoHint, A::Error> {
+				let mut v = Vec::new();
+				while let Some(x) = seq.next_element()? {
+					v.push(x);
+				}
+				Ok(NoHint(v))
+			}
+		}
+		deserializer.deserialize_seq(Visitor)
+	}
+}
+
+// same for a map
+struct NoHintMap(HashMap<u32, u64>);
+
+impl<'de> Deserialize<'de> for NoHintMap {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		struct Visitor;
+		impl<'de> serde::de::Visitor<'de> for Visitor {
+			type Value = NoHintMap;
+			fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+				f.write_str("a map")
+			}
+			fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<NoHintMap, A::Error> {
+				let mut m = HashMap::new();
+				while let Some((k, v)) = map.next_entry()? {
+					m.insert(k, v);
+				}
+				Ok(NoHintMap(m))
+			}
+		}
+		deserializer.deserialize_map(Visitor)
+	}
+}
+
+fn size_hint(c: &mut Criterion) {
+	let v: Vec<i32> = (0..10_000).collect();
+	let encoded = fcode::to_bytes(&v).unwrap();
+	let mut group = c.benchmark_group("vec_i32_10k_size_hint");
+	group.throughput(Throughput::Bytes(encoded.len() as u64));
+	group.bench_function("with_hint", |b| {
+		b.iter(|| fcode::from_bytes::<Vec<i32>>(black_box(&encoded)).unwrap())
+	});
+	group.bench_function("without_hint", |b| {
+		b.iter(|| fcode::from_bytes::<NoHint>(black_box(&encoded)).unwrap().0)
+	});
+	group.finish();
+
+	let m: HashMap<u32, u64> = (0..10_000).map(|i| (i, i as u64 * 1_000_003)).collect();
+	let encoded = fcode::to_bytes(&m).unwrap();
+	let mut group = c.benchmark_group("hashmap_10k_size_hint");
+	group.throughput(Throughput::Bytes(encoded.len() as u64));
+	group.bench_function("with_hint", |b| {
+		b.iter(|| fcode::from_bytes::<HashMap<u32, u64>>(black_box(&encoded)).unwrap())
+	});
+	group.bench_function("without_hint", |b| {
+		b.iter(|| fcode::from_bytes::<NoHintMap>(black_box(&encoded)).unwrap().0)
+	});
+	group.finish();
+}
+
+// round trip of the monster object, compared to other formats
+fn compare(c: &mut Criterion) {
+	fn roundtrip<T>(
+		group: &mut criterion::BenchmarkGroup<criterion::measurement::WallTime>,
+		name: &str,
+		value: &T,
+		encode: impl Fn(&mut Vec<u8>, &T),
+		decode: impl Fn(&[u8]) -> T,
+	) {
+		let mut buf = Vec::new();
+		group.bench_function(BenchmarkId::new("roundtrip", name), |b| {
+			b.iter(|| {
+				buf.clear();
+				encode(&mut buf, black_box(value));
+				decode(&buf)
+			})
+		});
+	}
+
+	let mut group = c.benchmark_group("compare_monster");
+	let value = benchfb::monster();
+	roundtrip(
+		&mut group,
+		"fcode",
+		&value,
+		|buf, v| fcode::to_writer(buf, v).unwrap(),
+		|buf| fcode::from_bytes(buf).unwrap(),
+	);
+	roundtrip(
+		&mut group,
+		"bincode",
+		&value,
+		|buf, v| bincode::serialize_into(buf, v).unwrap(),
+		|buf| bincode::deserialize(buf).unwrap(),
+	);
+	roundtrip(
+		&mut group,
+		"json",
+		&value,
+		|buf, v| serde_json::to_writer(buf, v).unwrap(),
+		|buf| serde_json::from_slice(buf).unwrap(),
+	);
+	let value = protobench::FooBarContainer {
+		list: value
+			.list
+			.iter()
+			.map(|v| protobench::FooBar {
+				sibling: Some(protobench::Bar {
+					parent: Some(protobench::Foo {
+						id: v.sibling.parent.id,
+						count: v.sibling.parent.count as i32,
+						prefix: v.sibling.parent.prefix as i32,
+						length: v.sibling.parent.length,
+					}),
+					time: v.sibling.time,
+					ratio: v.sibling.ratio,
+					size: v.sibling.size as u32,
+				}),
+				name: v.name.clone(),
+				rating: v.rating,
+				postfix: v.postfix as u32,
+			})
+			.collect(),
+		initialized: value.initialized,
+		fruit: protobench::Enum::Bananas as i32,
+		location: value.location.clone(),
+	};
+	roundtrip(
+		&mut group,
+		"prost",
+		&value,
+		|buf, v| prost::Message::encode(v, buf).unwrap(),
+		|buf| prost::Message::decode(buf).unwrap(),
+	);
+	group.finish();
+}
+
+criterion_group!(
+	benches,
+	scalars,
+	strings,
+	monster,
+	large_vec,
+	size_hint,
+	packed_floats,
+	reuse,
+	compare
+);
+criterion_main!(benches);
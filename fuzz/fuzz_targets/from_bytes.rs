@@ -0,0 +1,53 @@
+//! Decode arbitrary input into a few representative types; any error is fine, but nothing may panic.
+//!
+//! Run with `cargo fuzz run from_bytes`, after `cargo run --bin seed_corpus` to start from well-formed messages.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct Record {
+	id: u64,
+	delta: i32,
+	ratio: f64,
+	name: String,
+	#[serde(with = "serde_bytes")]
+	payload: Vec<u8>,
+	tags: Vec<String>,
+	#[serde(default)]
+	parent: Option<Box<Record>>,
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+enum Expr {
+	Lit(i64),
+	Add(Box<Expr>, Box<Expr>),
+	Neg(Box<Expr>),
+	Named { name: String, value: u8 },
+	Unit,
+}
+
+// flattened, to exercise type annotations and deserialize_any
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct Flat {
+	id: u32,
+	#[serde(flatten)]
+	rest: HashMap<String, u64>,
+}
+
+fuzz_target!(|data: &[u8]| {
+	let _ = fcode::from_bytes::<Record>(data);
+	let _ = fcode::from_bytes::<Expr>(data);
+	let _ = fcode::from_bytes::<Flat>(data);
+	let _ = fcode::from_bytes::<BTreeMap<i128, (char, bool, f32)>>(data);
+	let _ = fcode::from_bytes::<Vec<Option<&str>>>(data);
+	let _ = fcode::probe(data);
+	let mut de = fcode::Deserializer::from_bytes(data).canonical(true).max_depth(32);
+	while de.remaining_len() > 0 && de.skip_value().is_ok() {}
+});
@@ -0,0 +1,43 @@
+//! Write a starting corpus for the fuzz targets: a few typical messages, and random but well-formed values.
+//!
+//! Run with `cargo run --bin seed_corpus` from this directory, before `cargo fuzz run from_bytes`.
+
+use arbitrary::{Arbitrary, Unstructured};
+use serde_bytes::Bytes;
+use std::{fs, path::Path};
+
+const RANDOM_VALUES: usize = 500;
+
+fn main() -> std::io::Result<()> {
+	let dir = Path::new("corpus/from_bytes");
+	fs::create_dir_all(dir)?;
+
+	// shaped like the types in from_bytes
+	let record = (7u64, -3i32, 0.5f64, "name", Bytes::new(b"\x00\x01"), vec!["a", "b"]);
+	let mut seeds = vec![
+		fcode::to_bytes(&record).unwrap(),
+		fcode::to_bytes(&(7u64, -3i32, 0.5f64, "name", Bytes::new(b""), vec!["x"], Some(record))).unwrap(),
+		fcode::to_bytes(&vec![(1i128, ('x', true, 1.5f32)), (-1, ('y', false, 0.0))]).unwrap(),
+		fcode::to_bytes(&vec![Some("foo"), None]).unwrap(),
+	];
+
+	let mut state = 0x2545_f491_4f6c_dd1du64;
+	let data: Vec<u8> = (0..RANDOM_VALUES * 256)
+		.map(|_| {
+			state ^= state << 13;
+			state ^= state >> 7;
+			state ^= state << 17;
+			state as u8
+		})
+		.collect();
+	let mut u = Unstructured::new(&data);
+	while seeds.len() < RANDOM_VALUES && !u.is_empty() {
+		seeds.push(fcode::ArbitraryValue::arbitrary(&mut u).unwrap().into_bytes());
+	}
+
+	for (i, seed) in seeds.iter().enumerate() {
+		fs::write(dir.join(format!("seed-{:04}", i)), seed)?;
+	}
+	println!("wrote {} files to {}", seeds.len(), dir.display());
+	Ok(())
+}
@@ -0,0 +1,89 @@
+use crate::wire::{self, WireType};
+use alloc::vec::Vec;
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+// nesting beyond this is cut off with a scalar, well within the deserializer's default depth limit
+const MAX_DEPTH: usize = 16;
+
+/// A random but structurally valid encoded value, for property-based testing and fuzzing of decoders.
+///
+/// Any type implementing `Deserialize` can be decoded from arbitrary bytes, but most random inputs fail on the first
+/// tag byte. This generates a well-formed value instead: integers, fixed-width values, byte strings, sequences and
+/// variants, nested up to a limited depth, in the canonical form the serializer produces. Whether it decodes as a
+/// particular type is up to chance, but it always skips cleanly, and its parts can be decoded as far as the types
+/// match. Type annotations are not generated, as the serializer only writes those inside flattened structs.
+///
+/// ```
+/// use arbitrary::{Arbitrary, Unstructured};
+///
+/// let mut u = Unstructured::new(&[3, 1, 4, 1, 5, 9, 2, 6, 5, 3, 5]);
+/// let value = fcode::ArbitraryValue::arbitrary(&mut u).unwrap();
+/// let mut de = fcode::Deserializer::from_bytes(value.as_bytes());
+/// de.skip_value().unwrap();
+/// assert_eq!(de.remaining_len(), 0);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArbitraryValue(Vec<u8>);
+
+impl ArbitraryValue {
+	/// The encoded value.
+	pub fn as_bytes(&self) -> &[u8] {
+		&self.0
+	}
+
+	pub fn into_bytes(self) -> Vec<u8> {
+		self.0
+	}
+}
+
+impl<'a> Arbitrary<'a> for ArbitraryValue {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		let mut buf = Vec::new();
+		write_value(u, &mut buf, MAX_DEPTH)?;
+		Ok(ArbitraryValue(buf))
+	}
+}
+
+fn write_value(u: &mut Unstructured, buf: &mut Vec<u8>, depth: usize) -> Result<()> {
+	let nested = if depth == 0 { 3 } else { 6 };
+	// writing to a vector can't fail
+	match u.choose_index(nested)? {
+		0 => {
+			// mostly small values, like real data
+			let value = if u.arbitrary()? {
+				u.int_in_range(0..=300)?
+			} else {
+				u.arbitrary::<u128>()?
+			};
+			wire::write_varint_128(buf, WireType::Int, value).unwrap();
+		}
+		1 => {
+			buf.push(WireType::Fixed32 as u8);
+			buf.extend_from_slice(&u.arbitrary::<[u8; 4]>()?);
+		}
+		2 => {
+			buf.push(WireType::Fixed64 as u8);
+			buf.extend_from_slice(&u.arbitrary::<[u8; 8]>()?);
+		}
+		3 => {
+			// short, so that a single value doesn't use up the input
+			let len = u.arbitrary_len::<u8>()?.min(64);
+			let bytes = u.bytes(len)?;
+			wire::write_varint(buf, WireType::Bytes, bytes.len() as u64).unwrap();
+			buf.extend_from_slice(bytes);
+		}
+		4 => {
+			let len = u.int_in_range(0..=8)?;
+			wire::write_varint(buf, WireType::Sequence, len).unwrap();
+			for _ in 0..len {
+				write_value(u, buf, depth - 1)?;
+			}
+		}
+		_ => {
+			let discr = u.int_in_range(0..=4)?;
+			wire::write_varint(buf, WireType::Variant, discr).unwrap();
+			write_value(u, buf, depth - 1)?;
+		}
+	}
+	Ok(())
+}
@@ -0,0 +1,99 @@
+//! Compact encoding of `Vec<bool>` as a bitmap.
+//!
+//! Serde doesn't tell the serializer that a sequence contains booleans, so by default every element takes a byte on
+//! the wire. Use this module on a field to pack the elements 8 per byte instead:
+//!
+//! ```
+//! # use serde::{Serialize, Deserialize};
+//! #[derive(Serialize, Deserialize)]
+//! struct Foo {
+//!     #[serde(with = "fcode::bitvec")]
+//!     flags: Vec<bool>,
+//! }
+//! ```
+//!
+//! The value is encoded as a 2-tuple of the number of elements and a bytes value with the bits, least significant
+//! bit first. Note that this is not interchangeable with the default `Vec<bool>` encoding.
+
+use alloc::{vec, vec::Vec};
+use core::convert::TryFrom;
+use core::fmt;
+use serde::de::{self, DeserializeSeed, Deserializer, SeqAccess, Visitor};
+use serde::ser::{SerializeTuple, Serializer};
+
+// bits.div_ceil(8), which would need Rust 1.73
+fn packed_len(bits: usize) -> usize {
+	bits / 8 + (bits & 7 != 0) as usize
+}
+
+pub fn serialize<S: Serializer>(value: &[bool], serializer: S) -> Result<S::Ok, S::Error> {
+	let mut packed = vec![0u8; packed_len(value.len())];
+	for (i, _) in value.iter().enumerate().filter(|(_, &b)| b) {
+		packed[i / 8] |= 1 << (i % 8);
+	}
+	let mut tuple = serializer.serialize_tuple(2)?;
+	tuple.serialize_element(&(value.len() as u64))?;
+	tuple.serialize_element(&Packed(&packed))?;
+	tuple.end()
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<bool>, D::Error> {
+	deserializer.deserialize_tuple(2, BitVecVisitor)
+}
+
+struct Packed<'a>(&'a [u8]);
+
+impl serde::Serialize for Packed<'_> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_bytes(self.0)
+	}
+}
+
+struct BitVecVisitor;
+
+impl<'de> Visitor<'de> for BitVecVisitor {
+	type Value = Vec<bool>;
+
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("a bit count followed by packed bits")
+	}
+
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<bool>, A::Error> {
+		let len: u64 = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+		let len = usize::try_from(len).map_err(|_| de::Error::invalid_value(de::Unexpected::Unsigned(len), &self))?;
+		seq.next_element_seed(Unpack { len })?
+			.ok_or_else(|| de::Error::invalid_length(1, &self))
+	}
+}
+
+// unpacks the bits straight from the (possibly borrowed) bytes
+struct Unpack {
+	len: usize,
+}
+
+impl<'de> DeserializeSeed<'de> for Unpack {
+	type Value = Vec<bool>;
+
+	fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Vec<bool>, D::Error> {
+		deserializer.deserialize_bytes(self)
+	}
+}
+
+impl<'de> Visitor<'de> for Unpack {
+	type Value = Vec<bool>;
+
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} packed bits", self.len)
+	}
+
+	fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<bool>, E> {
+		if v.len() != packed_len(self.len) {
+			return Err(E::invalid_length(v.len(), &self));
+		}
+		// the unused bits of the last byte are zero, so that the bits have a single encoding
+		if self.len & 7 != 0 && v[v.len() - 1] >> (self.len & 7) != 0 {
+			return Err(E::invalid_value(de::Unexpected::Bytes(v), &self));
+		}
+		Ok((0..self.len).map(|i| v[i / 8] & (1 << (i % 8)) != 0).collect())
+	}
+}
@@ -0,0 +1,142 @@
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+/// Borrowed byte slice that is encoded as a single bytes value, rather than a sequence.
+///
+/// Serde encodes `&[u8]` as a sequence of integers. Wrapped, the content is written as is, after the length. Decoding
+/// borrows from the input, so it only works with [`from_bytes`](crate::from_bytes) and other deserializers that read
+/// from a slice; use [`ByteBuf`] or [`cow_bytes`](crate::cow_bytes) otherwise.
+///
+/// ```
+/// # use serde::{Serialize, Deserialize};
+/// #[derive(Serialize, Deserialize)]
+/// struct Blob<'a> {
+///     #[serde(borrow)]
+///     content: fcode::Bytes<'a>,
+/// }
+/// ```
+///
+/// This is the same encoding as that of `serde_bytes`, so the two are interchangeable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Bytes<'a>(pub &'a [u8]);
+
+impl<'a> Deref for Bytes<'a> {
+	type Target = [u8];
+
+	fn deref(&self) -> &[u8] {
+		self.0
+	}
+}
+
+impl<'a> From<&'a [u8]> for Bytes<'a> {
+	fn from(v: &'a [u8]) -> Self {
+		Bytes(v)
+	}
+}
+
+impl<'a> Serialize for Bytes<'a> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_bytes(self.0)
+	}
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for Bytes<'a> {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		deserializer.deserialize_bytes(BytesVisitor)
+	}
+}
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+	type Value = Bytes<'de>;
+
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("borrowed bytes")
+	}
+
+	fn visit_borrowed_bytes<E: de::Error>(self, v: &'de [u8]) -> Result<Self::Value, E> {
+		Ok(Bytes(v))
+	}
+
+	fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+		Ok(Bytes(v.as_bytes()))
+	}
+}
+
+/// Byte vector that is encoded as a single bytes value, rather than a sequence.
+///
+/// The owned counterpart of [`Bytes`]: serde encodes `Vec<u8>` as a sequence of integers, while this writes the
+/// content as is, after the length. This is the same encoding as that of `serde_bytes`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct ByteBuf(pub Vec<u8>);
+
+impl ByteBuf {
+	/// Unwrap the vector.
+	#[inline]
+	pub fn into_vec(self) -> Vec<u8> {
+		self.0
+	}
+}
+
+impl Deref for ByteBuf {
+	type Target = Vec<u8>;
+
+	fn deref(&self) -> &Vec<u8> {
+		&self.0
+	}
+}
+
+impl DerefMut for ByteBuf {
+	fn deref_mut(&mut self) -> &mut Vec<u8> {
+		&mut self.0
+	}
+}
+
+impl From<Vec<u8>> for ByteBuf {
+	fn from(v: Vec<u8>) -> Self {
+		ByteBuf(v)
+	}
+}
+
+impl Serialize for ByteBuf {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_bytes(&self.0)
+	}
+}
+
+impl<'de> Deserialize<'de> for ByteBuf {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		deserializer.deserialize_byte_buf(ByteBufVisitor)
+	}
+}
+
+struct ByteBufVisitor;
+
+impl<'de> Visitor<'de> for ByteBufVisitor {
+	type Value = ByteBuf;
+
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("bytes")
+	}
+
+	fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+		Ok(ByteBuf(v.to_vec()))
+	}
+
+	fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+		Ok(ByteBuf(v))
+	}
+
+	// for other formats
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+		let mut v = Vec::with_capacity(core::cmp::min(seq.size_hint().unwrap_or(0), 4096));
+		while let Some(b) = seq.next_element()? {
+			v.push(b);
+		}
+		Ok(ByteBuf(v))
+	}
+}
@@ -0,0 +1,106 @@
+//! Compact encoding of a chrono [`DateTime<Utc>`] as a single varint of nanoseconds since the Unix epoch.
+//!
+//! Chrono's own serde encoding is an RFC 3339 string of some 30 bytes. Use this module on a field to encode it as a
+//! zigzag varint instead, which takes 10 bytes for current dates:
+//!
+//! ```
+//! # use serde::{Serialize, Deserialize};
+//! # use chrono::{DateTime, Utc};
+//! #[derive(Serialize, Deserialize)]
+//! struct Event {
+//!     #[serde(with = "fcode::chrono_timestamp")]
+//!     at: DateTime<Utc>,
+//!     #[serde(with = "fcode::chrono_timestamp::secs")]
+//!     created: DateTime<Utc>,
+//! }
+//! ```
+//!
+//! The nanoseconds are encoded as an `i128`, so the full range of `DateTime` fits, and times before the epoch are
+//! negative. Decoding into an `i64` works up to the year 2262; that's also the range of
+//! `DateTime::timestamp_nanos_opt`. Where nanosecond precision isn't needed, [`secs`] takes 5 bytes for current dates
+//! and rounds down to whole seconds. Either encoding is incompatible with chrono's default, so adding the attribute
+//! to an existing field is not a compatible change. The encoding is the same as that of
+//! [`time_timestamp`](crate::time_timestamp), so a field can switch between the two crates.
+//!
+//! Other serde formats see an integer.
+
+use chrono::{DateTime, Utc};
+use core::convert::TryFrom;
+use core::fmt;
+use serde::de::{self, Deserializer, Visitor};
+use serde::ser::Serializer;
+
+const NANOS_PER_SEC: i128 = 1_000_000_000;
+
+pub fn serialize<S: Serializer>(value: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+	let nanos = value.timestamp() as i128 * NANOS_PER_SEC + value.timestamp_subsec_nanos() as i128;
+	serializer.serialize_i128(nanos)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+	deserializer.deserialize_i128(NanosVisitor)
+}
+
+struct NanosVisitor;
+
+impl<'de> Visitor<'de> for NanosVisitor {
+	type Value = DateTime<Utc>;
+
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("a timestamp in nanoseconds")
+	}
+
+	fn visit_i128<E: de::Error>(self, v: i128) -> Result<DateTime<Utc>, E> {
+		let secs = i64::try_from(v.div_euclid(NANOS_PER_SEC)).ok();
+		secs.and_then(|secs| DateTime::from_timestamp(secs, v.rem_euclid(NANOS_PER_SEC) as u32))
+			.ok_or_else(|| de::Error::invalid_value(de::Unexpected::Other("out of range timestamp"), &self))
+	}
+
+	// for other formats
+	fn visit_i64<E: de::Error>(self, v: i64) -> Result<DateTime<Utc>, E> {
+		self.visit_i128(v as i128)
+	}
+
+	fn visit_u64<E: de::Error>(self, v: u64) -> Result<DateTime<Utc>, E> {
+		self.visit_i128(v as i128)
+	}
+}
+
+/// Encoding of a [`DateTime<Utc>`] as a single varint of seconds since the Unix epoch.
+///
+/// Sub-second precision is lost: times are rounded down to the second.
+pub mod secs {
+	use chrono::{DateTime, Utc};
+	use core::convert::TryFrom;
+	use core::fmt;
+	use serde::de::{self, Deserializer, Visitor};
+	use serde::ser::Serializer;
+
+	pub fn serialize<S: Serializer>(value: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_i64(value.timestamp())
+	}
+
+	pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+		deserializer.deserialize_i64(SecsVisitor)
+	}
+
+	struct SecsVisitor;
+
+	impl<'de> Visitor<'de> for SecsVisitor {
+		type Value = DateTime<Utc>;
+
+		fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+			f.write_str("a timestamp in seconds")
+		}
+
+		fn visit_i64<E: de::Error>(self, v: i64) -> Result<DateTime<Utc>, E> {
+			DateTime::from_timestamp(v, 0).ok_or_else(|| de::Error::invalid_value(de::Unexpected::Signed(v), &self))
+		}
+
+		// for other formats
+		fn visit_u64<E: de::Error>(self, v: u64) -> Result<DateTime<Utc>, E> {
+			let v = i64::try_from(v).map_err(|_| de::Error::invalid_value(de::Unexpected::Unsigned(v), &self))?;
+			self.visit_i64(v)
+		}
+	}
+}
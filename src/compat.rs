@@ -0,0 +1,125 @@
+//! Dry-run checks of schema evolution, using sample values.
+//!
+//! Fcode has no schema, so whether a type change is compatible depends on the data. These helpers encode a sample of
+//! the old type and decode it as the new one (and the other way around), and report where that fails. Use them in
+//! tests, with samples that cover the interesting cases, e.g. `None` as well as `Some` and every enum variant:
+//!
+//! ```
+//! # use serde::{Serialize, Deserialize};
+//! #[derive(Serialize, Deserialize, Default)]
+//! struct V1 {
+//!     id: u32,
+//! }
+//! #[derive(Serialize, Deserialize, Default)]
+//! struct V2 {
+//!     id: u32,
+//!     #[serde(default)]
+//!     name: String,
+//! }
+//! fcode::compat::check_compatible(&V1::default(), &V2::default()).unwrap();
+//! ```
+
+use crate::{to_bytes, Deserializer, Error};
+use alloc::{boxed::Box, vec::Vec};
+use core::fmt;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Which way a sample was decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+	/// Data of the old type read by the new type, i.e. an upgraded receiver.
+	OldToNew,
+	/// Data of the new type read by the old type, i.e. an upgraded sender.
+	NewToOld,
+}
+
+/// Why a sample couldn't be decoded as the other type.
+#[derive(Debug)]
+pub struct Incompatibility {
+	pub direction: Direction,
+	/// The decoding error, which includes the path of the failing field.
+	pub error: Error,
+	/// The offset in `encoded` at which decoding stopped.
+	pub offset: usize,
+	/// The encoded sample.
+	pub encoded: Vec<u8>,
+}
+
+impl fmt::Display for Incompatibility {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let direction = match self.direction {
+			Direction::OldToNew => "old data read as new type",
+			Direction::NewToOld => "new data read as old type",
+		};
+		write!(
+			f,
+			"{}: {} (at byte {} of {})",
+			direction,
+			self.error,
+			self.offset,
+			self.encoded.len()
+		)
+	}
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Incompatibility {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		Some(&self.error)
+	}
+}
+
+/// Encode `sample` and decode it as `T`, e.g. to check that an upgraded receiver can read data of the old type.
+///
+/// Returns the decoded value, so that the test can also check how fields were mapped.
+pub fn check_roundtrip<T, S>(sample: &S) -> Result<T, Box<Incompatibility>>
+where
+	T: DeserializeOwned,
+	S: Serialize + ?Sized,
+{
+	decode(sample, Direction::OldToNew)
+}
+
+/// Check both directions: a sample of the old type decoded as the new type, and a sample of the new type decoded as
+/// the old type.
+pub fn check_compatible<Old, New>(old_sample: &Old, new_sample: &New) -> Result<(), Box<Incompatibility>>
+where
+	Old: Serialize + DeserializeOwned,
+	New: Serialize + DeserializeOwned,
+{
+	decode::<New, _>(old_sample, Direction::OldToNew)?;
+	decode::<Old, _>(new_sample, Direction::NewToOld)?;
+	Ok(())
+}
+
+fn decode<T, S>(sample: &S, direction: Direction) -> Result<T, Box<Incompatibility>>
+where
+	T: DeserializeOwned,
+	S: Serialize + ?Sized,
+{
+	let encoded = match to_bytes(sample) {
+		Ok(encoded) => encoded,
+		Err(error) => {
+			return Err(Box::new(Incompatibility {
+				direction,
+				error,
+				offset: 0,
+				encoded: Vec::new(),
+			}))
+		}
+	};
+	let mut de = Deserializer::from_bytes(&encoded);
+	let result = match T::deserialize(&mut de) {
+		Ok(_) if de.remaining_len() > 0 => Err(Error::DataBeyondEnd),
+		result => result,
+	};
+	let offset = de.position();
+	result.map_err(|error| {
+		Box::new(Incompatibility {
+			direction,
+			error,
+			offset,
+			encoded,
+		})
+	})
+}
@@ -0,0 +1,75 @@
+use crate::{from_bytes, to_bytes, Error, Result};
+use alloc::vec::Vec;
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use serde::{de::DeserializeOwned, Serialize};
+use std::io::{Read, Write};
+
+// the prefix byte, telling how the rest of the data is stored
+const RAW: u8 = 0;
+const DEFLATE: u8 = 1;
+
+/// Serialize a value into a new byte vector, compressed with deflate.
+///
+/// The data starts with a byte telling whether it's compressed; values that don't get smaller, such as short
+/// messages, are stored as is. Use [`from_bytes_compressed`] to decode.
+pub fn to_bytes_compressed<T>(value: &T) -> Result<Vec<u8>>
+where
+	T: Serialize + ?Sized,
+{
+	compress(&to_bytes(value)?)
+}
+
+// deflate serialized data behind its prefix byte, or keep it as is if that doesn't make it smaller
+fn compress(raw: &[u8]) -> Result<Vec<u8>> {
+	let mut encoder = DeflateEncoder::new(alloc::vec![DEFLATE], Compression::default());
+	encoder.write_all(raw)?;
+	let compressed = encoder.finish()?;
+	if compressed.len() <= raw.len() {
+		return Ok(compressed);
+	}
+	let mut v = Vec::with_capacity(raw.len() + 1);
+	v.push(RAW);
+	v.extend_from_slice(raw);
+	Ok(v)
+}
+
+/// Deserialize a value written by [`to_bytes_compressed`].
+///
+/// Fails with [`Error::UnknownCompression`] if the data doesn't start with a known prefix byte. Compressed data is
+/// inflated into a temporary buffer, so the value can't borrow from the input. A few kilobytes of deflated data can
+/// inflate to gigabytes, so the inflated size is limited to `max_len` bytes; beyond that, this fails with
+/// [`Error::InputTooLarge`].
+pub fn from_bytes_compressed<T>(data: &[u8], max_len: usize) -> Result<T>
+where
+	T: DeserializeOwned,
+{
+	let (&prefix, rest) = data.split_first().ok_or(Error::UnexpectedEndOfInput)?;
+	match prefix {
+		RAW => from_bytes(rest),
+		DEFLATE => {
+			let mut buf = Vec::new();
+			// one more byte than allowed, to tell a value of exactly max_len from a larger one
+			DeflateDecoder::new(rest)
+				.take(max_len as u64 + 1)
+				.read_to_end(&mut buf)?;
+			if buf.len() > max_len {
+				return Err(Error::InputTooLarge);
+			}
+			from_bytes(&buf)
+		}
+		other => Err(Error::UnknownCompression(other)),
+	}
+}
+
+/// Estimate how well a value compresses.
+///
+/// Returns a pair of (raw_size, compressed_size), where the compressed size is that of the output of
+/// [`to_bytes_compressed`], prefix byte included. This is meant as a diagnostic, to decide per message type whether
+/// compression is worth the CPU time.
+pub fn compression_estimate<T>(value: &T) -> Result<(usize, usize)>
+where
+	T: Serialize + ?Sized,
+{
+	let raw = to_bytes(value)?;
+	Ok((raw.len(), compress(&raw)?.len()))
+}
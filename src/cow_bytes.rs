@@ -0,0 +1,65 @@
+//! Encoding of a `Cow<[u8]>` as a single bytes value, borrowing from the input when decoding.
+//!
+//! Serde encodes a `Cow<[u8]>` like a `Vec<u8>`, i.e. as a sequence with a tag byte per element. Use this module on a
+//! field to encode it compactly, like a `&[u8]`:
+//!
+//! ```
+//! # use serde::{Serialize, Deserialize};
+//! # use std::borrow::Cow;
+//! #[derive(Serialize, Deserialize)]
+//! struct Blob<'a> {
+//!     #[serde(borrow, with = "fcode::cow_bytes")]
+//!     content: Cow<'a, [u8]>,
+//! }
+//! ```
+//!
+//! Decoding from a slice gives `Cow::Borrowed`. A `Cow<str>` needs no such attribute: it's encoded as a string either
+//! way, and borrows with just `#[serde(borrow)]`.
+//!
+//! This is the same encoding as that of the `serde_bytes` crate, so the two are interchangeable. It differs from the
+//! default, so adding the attribute to an existing field is not a compatible change.
+
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use core::fmt;
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::ser::Serializer;
+
+pub fn serialize<S: Serializer>(value: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+	serializer.serialize_bytes(value)
+}
+
+pub fn deserialize<'de: 'a, 'a, D: Deserializer<'de>>(deserializer: D) -> Result<Cow<'a, [u8]>, D::Error> {
+	deserializer.deserialize_bytes(CowVisitor)
+}
+
+struct CowVisitor;
+
+impl<'de> Visitor<'de> for CowVisitor {
+	type Value = Cow<'de, [u8]>;
+
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("bytes")
+	}
+
+	fn visit_borrowed_bytes<E: de::Error>(self, v: &'de [u8]) -> Result<Self::Value, E> {
+		Ok(Cow::Borrowed(v))
+	}
+
+	fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+		Ok(Cow::Owned(v.to_vec()))
+	}
+
+	fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+		Ok(Cow::Owned(v))
+	}
+
+	// for other formats
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+		let mut v = Vec::with_capacity(core::cmp::min(seq.size_hint().unwrap_or(0), 4096));
+		while let Some(b) = seq.next_element()? {
+			v.push(b);
+		}
+		Ok(Cow::Owned(v))
+	}
+}
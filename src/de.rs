@@ -1,18 +1,266 @@
 use crate::{
+	error::PathSegment,
 	wire::{self, WireType},
 	Error, Result,
 };
+use alloc::collections::BTreeSet;
+use core::convert::{TryFrom, TryInto};
 use serde::de::{self, DeserializeSeed, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor};
-use std::convert::TryInto;
 
+// default maximum nesting of sequences, variants and newtypes; that's deep enough for any sane message, and shallow
+// enough not to overflow the stack
+pub(crate) const DEFAULT_MAX_DEPTH: usize = 128;
+
+// newtype name by which adapters in this crate ask for the value to be dispatched on its wire type, for types that
+// accept multiple encodings; unsigned varints, sequences and bytes are supported
+pub(crate) const UNTAGGED_TOKEN: &str = "$fcode::Untagged";
+
+// newtype name by which Lenient asks for an enum, presented as a sequence of either the value, or nothing followed by
+// the unknown discriminator
+pub(crate) const LENIENT_TOKEN: &str = "$fcode::Lenient";
+
+// newtype name by which f16_bytes asks for bytes, or a float that the field used to be
+pub(crate) const FLOAT_BYTES_TOKEN: &str = "$fcode::FloatBytes";
+
+// newtype name by which FixedBytes asks for a byte array; the content is passed to visit_some, as a deserializer that
+// takes the expected length through deserialize_tuple, and accepts either a bytes value or the default array encoding
+pub(crate) const FIXED_BYTES_TOKEN: &str = "$fcode::FixedBytes";
+
+// the type annotations that integer reads accept; an unsigned read also takes bools and chars, which decode unchanged
+const SIGNED: &[u64] = &[wire::TYPE_INT];
+const UNSIGNED: &[u64] = &[wire::TYPE_UINT, wire::TYPE_BOOL, wire::TYPE_CHAR];
+
+/// Deserializer reading from a byte slice.
+///
+/// Cloning is cheap, as the deserializer only references the input. Keep a clone as checkpoint to be able to go back,
+/// e.g. to try decoding the same data as another type.
+#[derive(Clone)]
 pub struct Deserializer<'de> {
 	input: &'de [u8],
+	total_len: usize,
+	canonical: bool,
+	big_endian: bool,
+	strict_sequence_len: bool,
+	strict_tuple_len: bool,
+	strict_bools: bool,
+	variant_names: bool,
+	strict_maps: bool,
+	depth: usize,
+	budget: usize,
+	max_seq_len: usize,
+	max_string_len: usize,
+	structural: bool,
+	// inside a map of unknown length or an annotated value, the only places where type annotations are written
+	annotated: bool,
+	// skip UTF-8 validation of strings; only set through the unsafe constructor
+	unchecked_utf8: bool,
 }
 
 impl<'de> Deserializer<'de> {
 	#[inline]
 	pub fn from_bytes(input: &'de [u8]) -> Self {
-		Deserializer { input }
+		Deserializer {
+			input,
+			total_len: input.len(),
+			canonical: false,
+			big_endian: false,
+			strict_sequence_len: false,
+			strict_tuple_len: false,
+			strict_bools: false,
+			variant_names: false,
+			strict_maps: false,
+			depth: DEFAULT_MAX_DEPTH,
+			budget: usize::MAX,
+			max_seq_len: usize::MAX,
+			max_string_len: usize::MAX,
+			structural: false,
+			annotated: false,
+			unchecked_utf8: false,
+		}
+	}
+
+	/// Create a deserializer that doesn't validate strings as UTF-8.
+	///
+	/// This saves the time to scan strings, which matters for messages with large strings. Other checks, e.g. of
+	/// `char` values, still apply.
+	///
+	/// # Safety
+	///
+	/// All byte strings in `input` that are decoded as `str` or `String` must be valid UTF-8, e.g. because the input
+	/// was produced by this crate's serializer and hasn't been tampered with since. Invalid UTF-8 in a `str` is
+	/// undefined behavior. Never use this on input from an untrusted source.
+	#[inline]
+	pub unsafe fn from_bytes_unchecked_utf8(input: &'de [u8]) -> Self {
+		let mut de = Self::from_bytes(input);
+		de.unchecked_utf8 = true;
+		de
+	}
+
+	/// Require varints to be in their shortest form, and fail with [`Error::NonCanonicalVarint`] otherwise.
+	///
+	/// The serializer always produces the shortest form, but the deserializer by default accepts padded varints.
+	/// That means that the same value can have multiple encodings, which is undesirable when the encoded bytes are
+	/// hashed or signed.
+	#[inline]
+	pub fn canonical(mut self, canonical: bool) -> Self {
+		self.canonical = canonical;
+		self
+	}
+
+	/// Read fixed-width values (`f32`, `f64`, and fixed-width integers) as big-endian rather than little-endian.
+	/// Varints are not affected.
+	#[inline]
+	pub fn big_endian(mut self, big_endian: bool) -> Self {
+		self.big_endian = big_endian;
+		self
+	}
+
+	/// Report a sequence that runs out of input before its claimed number of elements as
+	/// [`Error::SequenceCountMismatch`], rather than [`Error::UnexpectedEndOfInput`].
+	///
+	/// This applies to real sequences (`Vec`, sets, etc.), not to tuples and structs. It gives a clearer error for
+	/// corrupt data, but shouldn't be used when end of input means that more data should be awaited.
+	#[inline]
+	pub fn strict_sequence_len(mut self, strict: bool) -> Self {
+		self.strict_sequence_len = strict;
+		self
+	}
+
+	/// Fail with [`Error::TupleLengthMismatch`] when a tuple or array is received with a different number of elements
+	/// than expected, rather than dropping extra elements.
+	///
+	/// This is for fixed protocols, where a length mismatch means corrupt data. Structs and tuple structs are not
+	/// affected, so that they can still evolve.
+	#[inline]
+	pub fn strict_tuple_len(mut self, strict: bool) -> Self {
+		self.strict_tuple_len = strict;
+		self
+	}
+
+	/// Only accept integers for `bool`, and fail with [`Error::UnexpectedWireType`] otherwise.
+	///
+	/// By default, a `bool` is decoded like a `u64`, which also accepts fixed-width values, and unit variants are
+	/// accepted as well. That silently turns e.g. a float into a `bool` when a field's type was changed incompatibly.
+	#[inline]
+	pub fn strict_bools(mut self, strict: bool) -> Self {
+		self.strict_bools = strict;
+		self
+	}
+
+	/// Also accept enum variants identified by name, i.e. a string in place of the discriminator.
+	///
+	/// The serializer never produces this, but it makes it possible to hand-author data, e.g. for tests or when
+	/// importing from a human-readable format.
+	#[inline]
+	pub fn variant_names(mut self, variant_names: bool) -> Self {
+		self.variant_names = variant_names;
+		self
+	}
+
+	/// Fail with [`Error::DuplicateKey`] when a map contains the same key twice, rather than letting the map type
+	/// decide (typically the last value wins).
+	///
+	/// Keys are compared by their encoded bytes. Combine with [`canonical`](Self::canonical) so that padded varints
+	/// can't be used to encode the same key differently.
+	#[inline]
+	pub fn strict_maps(mut self, strict: bool) -> Self {
+		self.strict_maps = strict;
+		self
+	}
+
+	/// Set the maximum nesting depth of values, after which deserialization fails with
+	/// [`Error::DepthLimitExceeded`]. Default is 128.
+	///
+	/// This protects against stack overflow on malicious input or recursive types.
+	#[inline]
+	pub fn max_depth(mut self, depth: usize) -> Self {
+		self.depth = depth;
+		self
+	}
+
+	/// Fail with [`Error::InputTooLarge`] once more than `max` bytes have been consumed, regardless of the structure of
+	/// the data. Default is unlimited.
+	///
+	/// This is a cap on the total work done per message, rather than on individual collections.
+	#[inline]
+	pub fn max_bytes(mut self, max: usize) -> Self {
+		self.budget = max;
+		self
+	}
+
+	/// Fail with [`Error::SequenceTooLong`] when a sequence or map claims more than `max` elements. For maps, this is
+	/// the number of entries. Default is unlimited.
+	///
+	/// The length is checked before anything is decoded, so this fails fast on hostile lengths. Tuples and structs are
+	/// not affected.
+	#[inline]
+	pub fn max_seq_len(mut self, max: usize) -> Self {
+		self.max_seq_len = max;
+		self
+	}
+
+	/// Fail with [`Error::StringTooLong`] when a string claims more than `max` bytes. Default is unlimited.
+	///
+	/// As with [`max_seq_len`](Self::max_seq_len), the length is checked before anything is read, so a tighter bound
+	/// can be set for text than for byte strings, which are not affected.
+	#[inline]
+	pub fn max_string_len(mut self, max: usize) -> Self {
+		self.max_string_len = max;
+		self
+	}
+
+	// decode values without annotations in deserialize_any by their wire type, which loses information such as the
+	// signedness of integers; this is only good for inspection
+	#[cfg(feature = "transcode")]
+	pub(crate) fn structural(mut self) -> Self {
+		self.structural = true;
+		self
+	}
+
+	// accept type annotations from the start, to walk the entries of a map of unknown length on their own
+	pub(crate) fn annotated(mut self, annotated: bool) -> Self {
+		self.annotated = annotated;
+		self
+	}
+
+	// check the claimed length of a sequence or map
+	#[inline]
+	fn check_seq_len(&self, len: usize) -> Result<()> {
+		if len > self.max_seq_len {
+			return Err(Error::SequenceTooLong {
+				len,
+				max: self.max_seq_len,
+			});
+		}
+		Ok(())
+	}
+
+	/// Skip over the next value without decoding it.
+	///
+	/// This works for any value, as the wire format is self-describing enough to know where a value ends.
+	#[inline]
+	pub fn skip_value(&mut self) -> Result<()> {
+		self.skip()
+	}
+
+	/// Get the wire type of the next value, without consuming anything.
+	///
+	/// This allows a hand-written `Deserialize` implementation to accept multiple encodings. Note that values inside
+	/// flattened structs have type annotations, which show as [`WireType::Typed`].
+	#[inline]
+	pub fn peek_wire_type(&self) -> Result<WireType> {
+		let &tagbyte = self.input.first().ok_or(Error::UnexpectedEndOfInput)?;
+		Ok(wire::read_wiretype(tagbyte))
+	}
+
+	/// Read the next value without decoding it, and return its encoding, from tag byte to end.
+	///
+	/// This is useful to forward a value opaquely: the returned bytes are a complete fcode value.
+	#[inline]
+	pub fn read_raw_value(&mut self) -> Result<&'de [u8]> {
+		let start = self.input;
+		self.skip()?;
+		Ok(&start[..start.len() - self.input.len()])
 	}
 
 	#[inline]
@@ -20,6 +268,18 @@ impl<'de> Deserializer<'de> {
 		self.input.len()
 	}
 
+	/// The number of bytes consumed so far, i.e. the offset of the next value in the input.
+	#[inline]
+	pub fn position(&self) -> usize {
+		self.total_len - self.input.len()
+	}
+
+	/// The length of the input the deserializer was created with.
+	#[inline]
+	pub fn total_len(&self) -> usize {
+		self.total_len
+	}
+
 	#[inline]
 	fn check(&self, n: usize) -> Result<()> {
 		if n > self.input.len() {
@@ -29,123 +289,480 @@ impl<'de> Deserializer<'de> {
 		}
 	}
 
+	#[inline]
+	fn spend(&mut self, n: usize) -> Result<()> {
+		self.budget = self.budget.checked_sub(n).ok_or(Error::InputTooLarge)?;
+		Ok(())
+	}
+
 	#[inline]
 	fn read(&mut self, n: usize) -> Result<&'de [u8]> {
 		self.check(n)?;
+		self.spend(n)?;
 		let (value, remainder) = self.input.split_at(n);
 		self.input = remainder;
 		Ok(value)
 	}
 
+	// read a fixed-width value, returned in little-endian order
 	#[inline]
 	fn read_32(&mut self) -> Result<[u8; 4]> {
-		Ok(self.read(4)?.try_into().unwrap())
+		let mut b: [u8; 4] = self.read(4)?.try_into().unwrap();
+		if self.big_endian {
+			b.reverse();
+		}
+		Ok(b)
 	}
 
 	#[inline]
 	fn read_64(&mut self) -> Result<[u8; 8]> {
-		Ok(self.read(8)?.try_into().unwrap())
+		let mut b: [u8; 8] = self.read(8)?.try_into().unwrap();
+		if self.big_endian {
+			b.reverse();
+		}
+		Ok(b)
 	}
 
 	#[inline]
 	fn read_byte(&mut self) -> Result<u8> {
-		let &b = self.input.get(0).ok_or_else(|| Error::UnexpectedEndOfInput)?;
+		let &b = self.input.first().ok_or(Error::UnexpectedEndOfInput)?;
+		self.spend(1)?;
 		self.input = &self.input[1..];
 		Ok(b)
 	}
 
+	// consume the type annotation of the next value, if any, returning its type code; annotations are rejected where
+	// the serializer doesn't write them, so that each value has a single encoding
+	#[inline]
+	fn read_annotation(&mut self) -> Result<Option<u64>> {
+		match self.input.first() {
+			Some(&tagbyte) if wire::read_wiretype(tagbyte) == WireType::Typed => {
+				if !self.annotated {
+					return Err(Error::UnexpectedWireType);
+				}
+				self.consume(1)?;
+				let code = wire::check_type_code(self.read_varint(tagbyte)?)?;
+				self.check_annotated_content(code)?;
+				Ok(Some(code))
+			}
+			_ => Ok(None),
+		}
+	}
+
+	// only an enum, identified by name, is followed by another annotated value
 	#[inline]
-	fn consume(&mut self, len: usize) {
+	fn check_annotated_content(&self, code: u64) -> Result<()> {
+		match self.input.first() {
+			Some(&tagbyte) if code != wire::TYPE_ENUM && wire::read_wiretype(tagbyte) == WireType::Typed => {
+				Err(Error::UnexpectedWireType)
+			}
+			_ => Ok(()),
+		}
+	}
+
+	// where annotations are written, they are written for every value
+	#[inline]
+	fn check_annotated(&self) -> Result<()> {
+		if self.annotated && self.peek_wire_type()? != WireType::Typed {
+			return Err(Error::UnexpectedWireType);
+		}
+		Ok(())
+	}
+
+	// consume the type annotation of the next value, if any, which must be one of the given types
+	#[inline]
+	fn expect_annotation(&mut self, codes: &[u64]) -> Result<()> {
+		match self.read_annotation()? {
+			Some(code) if !codes.contains(&code) => Err(Error::UnexpectedWireType),
+			_ => Ok(()),
+		}
+	}
+
+	// read the tag byte of the next value, which may be annotated as one of the given types
+	#[inline]
+	fn read_tag(&mut self, codes: &[u64]) -> Result<u8> {
+		self.expect_annotation(codes)?;
+		self.read_byte()
+	}
+
+	// run f with annotations accepted or not, restoring the current state afterwards
+	#[inline]
+	fn with_annotated<T>(&mut self, annotated: bool, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+		let outer = core::mem::replace(&mut self.annotated, annotated);
+		let result = f(self);
+		self.annotated = outer;
+		result
+	}
+
+	#[inline]
+	fn consume(&mut self, len: usize) -> Result<()> {
+		self.check(len)?;
+		self.spend(len)?;
 		self.input = &self.input[len..];
+		Ok(())
 	}
 
 	#[inline]
 	fn read_varint(&mut self, tagbyte: u8) -> Result<u64> {
 		let (value, len) = wire::read_varint(tagbyte, self.input)?;
-		self.consume(len);
+		let bytes = self.read(len)?;
+		if self.canonical {
+			wire::check_canonical_varint(bytes)?;
+		}
 		Ok(value)
 	}
 
-	serde::serde_if_integer128! {
-		fn read_varint_128(&mut self, tagbyte: u8) -> Result<u128> {
-			let (value, len) = wire::read_varint_128(tagbyte, self.input)?;
-			self.consume(len);
-			Ok(value)
+	// read the length of a sequence or bytes value; a length beyond the address space can't be satisfied by the input
+	#[inline]
+	fn read_len(&mut self, tagbyte: u8) -> Result<usize> {
+		usize::try_from(self.read_varint(tagbyte)?).map_err(|_| Error::UnexpectedEndOfInput)
+	}
+
+	fn read_varint_128(&mut self, tagbyte: u8) -> Result<u128> {
+		let (value, len) = wire::read_varint_128(tagbyte, self.input)?;
+		let bytes = self.read(len)?;
+		if self.canonical {
+			wire::check_canonical_varint(bytes)?;
 		}
+		Ok(value)
+	}
+
+	// read a complete value of wire type bytes, returning its content
+	#[inline]
+	fn read_bytes_value(&mut self) -> Result<&'de [u8]> {
+		let tagbyte = self.read_tag(&[wire::TYPE_BYTES, wire::TYPE_STR])?;
+		if wire::read_wiretype(tagbyte) != WireType::Bytes {
+			return Err(Error::UnexpectedWireType);
+		}
+		let len = self.read_len(tagbyte)?;
+		self.read(len)
+	}
+
+	// read a bytes value as UTF-8, checking the claimed length first
+	fn read_str_value(&mut self) -> Result<&'de str> {
+		let tagbyte = self.read_tag(&[wire::TYPE_STR])?;
+		if wire::read_wiretype(tagbyte) != WireType::Bytes {
+			return Err(Error::UnexpectedWireType);
+		}
+		let len = self.read_len(tagbyte)?;
+		if len > self.max_string_len {
+			return Err(Error::StringTooLong {
+				len,
+				max: self.max_string_len,
+			});
+		}
+		let bytes = self.read(len)?;
+		if self.unchecked_utf8 {
+			// safety: guaranteed by the caller of from_bytes_unchecked_utf8
+			return Ok(unsafe { core::str::from_utf8_unchecked(bytes) });
+		}
+		Ok(core::str::from_utf8(bytes)?)
+	}
+
+	/// Read a bytes value as a [`Bytes`](bytes::Bytes) that shares the allocation of `buffer`.
+	///
+	/// This deserializer must have been created from (a part of) `buffer`; if not, the content is copied.
+	#[cfg(feature = "bytes")]
+	pub fn read_shared_bytes(&mut self, buffer: &bytes::Bytes) -> Result<bytes::Bytes> {
+		let content = self.read_bytes_value()?;
+		let outer = buffer.as_ptr_range();
+		let inner = content.as_ptr_range();
+		if content.is_empty() || inner.start < outer.start || inner.end > outer.end {
+			return Ok(bytes::Bytes::copy_from_slice(content));
+		}
+		Ok(buffer.slice_ref(content))
+	}
+
+	// run f one nesting level deeper
+	#[inline]
+	fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+		if self.depth == 0 {
+			return Err(Error::DepthLimitExceeded);
+		}
+		self.depth -= 1;
+		let result = f(self);
+		self.depth += 1;
+		result
 	}
 
 	#[inline]
 	fn skip(&mut self) -> Result<()> {
 		let tagbyte = self.read_byte()?;
+		if !self.skip_flat(tagbyte)? {
+			self.skip_nested(tagbyte)?;
+		}
+		Ok(())
+	}
+
+	// skip a value that doesn't contain other values, given its tag byte; returns false for other values, which have
+	// not been touched
+	#[inline]
+	fn skip_flat(&mut self, tagbyte: u8) -> Result<bool> {
 		match wire::read_wiretype(tagbyte) {
 			WireType::Int => {
 				let len = wire::skip_varint(tagbyte, self.input)?;
-				self.consume(len);
+				let bytes = self.read(len)?;
+				if self.canonical {
+					wire::check_canonical_varint(bytes)?;
+				}
 			}
 			WireType::Fixed32 => {
-				self.read_32()?;
+				self.read(4)?;
 			}
 			WireType::Fixed64 => {
-				self.read_64()?;
+				self.read(8)?;
 			}
+			WireType::Bytes => {
+				let len = self.read_len(tagbyte)?;
+				self.read(len)?;
+			}
+			_ => return Ok(false),
+		}
+		Ok(true)
+	}
+
+	// skip a value that contains other values, one nesting level deeper
+	fn skip_nested(&mut self, tagbyte: u8) -> Result<()> {
+		match wire::read_wiretype(tagbyte) {
 			WireType::Sequence => {
-				let len = self.read_varint(tagbyte)?;
-				for _ in 0..len {
-					self.skip()?;
+				let len = self.read_len(tagbyte)?;
+				// the entries of a map of unknown length are annotated
+				let annotated = self.annotated || (len > 0 && self.peek_wire_type()? == WireType::Typed);
+				self.nested(|d| {
+					d.with_annotated(annotated, |d| {
+						// only recurse for elements that need it; sequences of scalars are common and can be large
+						for _ in 0..len {
+							let tagbyte = d.read_byte()?;
+							if annotated && wire::read_wiretype(tagbyte) != WireType::Typed {
+								return Err(Error::UnexpectedWireType);
+							}
+							if !d.skip_flat(tagbyte)? {
+								d.skip_nested(tagbyte)?;
+							}
+						}
+						Ok(())
+					})
+				})
+			}
+			WireType::Variant => {
+				self.read_varint(tagbyte)?;
+				self.check_annotated()?;
+				self.nested(|d| d.skip())
+			}
+			WireType::Typed => {
+				if !self.annotated {
+					return Err(Error::UnexpectedWireType);
+				}
+				let code = wire::check_type_code(self.read_varint(tagbyte)?)?;
+				self.check_annotated_content(code)?;
+				if code == wire::TYPE_ENUM {
+					self.read_bytes_value()?;
+					self.check_annotated()?;
 				}
+				self.nested(|d| d.skip())
 			}
+			_ => Err(Error::UnexpectedWireType),
+		}
+	}
+
+	// read a sequence of up to len items; the field names are only used in error paths
+	fn read_tuple<V: Visitor<'de>>(
+		&mut self,
+		len: usize,
+		fields: &'static [&'static str],
+		exact: bool,
+		visitor: V,
+	) -> Result<V::Value> {
+		let tagbyte = self.read_tag(&[wire::TYPE_SEQ])?;
+		if wire::read_wiretype(tagbyte) != WireType::Sequence {
+			return Err(Error::UnexpectedWireType);
+		}
+		let n = self.read_len(tagbyte)?;
+		if exact && n != len {
+			return Err(Error::TupleLengthMismatch {
+				expected: len,
+				found: n,
+			});
+		}
+		self.nested(|d| {
+			visitor.visit_seq(SeqRead {
+				d,
+				nread: n,
+				nreturn: core::cmp::min(n, len),
+				claimed: None,
+				keys: None,
+				fields,
+				index: 0,
+			})
+		})
+	}
+
+	// decode a value without annotation by its wire type only
+	fn deserialize_structural<V: Visitor<'de>>(&mut self, visitor: V) -> Result<V::Value> {
+		let tagbyte = self.read_byte()?;
+		match wire::read_wiretype(tagbyte) {
+			WireType::Int => {
+				let v = self.read_varint_128(tagbyte)?;
+				match v.try_into() {
+					Ok(v) => visitor.visit_u64(v),
+					Err(_) => visitor.visit_u128(v),
+				}
+			}
+			WireType::Fixed32 => visitor.visit_f32(f32::from_le_bytes(self.read_32()?)),
+			WireType::Fixed64 => visitor.visit_f64(f64::from_le_bytes(self.read_64()?)),
 			WireType::Bytes => {
-				let len = self.read_varint(tagbyte)?;
-				self.read(len as usize)?;
+				let len = self.read_len(tagbyte)?;
+				let bytes = self.read(len)?;
+				match core::str::from_utf8(bytes) {
+					Ok(s) => visitor.visit_borrowed_str(s),
+					Err(_) => visitor.visit_borrowed_bytes(bytes),
+				}
+			}
+			WireType::Sequence => {
+				let n = self.read_len(tagbyte)?;
+				self.check_seq_len(n)?;
+				self.nested(|d| {
+					visitor.visit_seq(SeqRead {
+						d,
+						nread: n,
+						nreturn: n,
+						claimed: None,
+						keys: None,
+						fields: &[],
+						index: 0,
+					})
+				})
 			}
 			WireType::Variant => {
-				self.read_varint(tagbyte)?;
-				self.skip()?;
+				let index = self.read_varint(tagbyte)?;
+				self.nested(|d| {
+					visitor.visit_map(VariantMap {
+						d,
+						key: Some(VariantKey::Index(index)),
+					})
+				})
 			}
-			_ => {
-				return Err(Error::UnexpectedWireType);
+			WireType::Typed | WireType::Reserved => Err(Error::UnexpectedWireType),
+		}
+	}
+
+	// decode a type-annotated value, as written inside maps of unknown length
+	fn deserialize_annotated<V: Visitor<'de>>(&mut self, visitor: V) -> Result<V::Value> {
+		use de::Deserializer;
+		let code = self.read_annotation()?.ok_or(Error::UnexpectedWireType)?;
+		let &tagbyte = self.input.first().ok_or(Error::UnexpectedEndOfInput)?;
+		match code {
+			wire::TYPE_BOOL => self.deserialize_bool(visitor),
+			wire::TYPE_INT => match wire::read_wiretype(tagbyte) {
+				WireType::Fixed32 => self.deserialize_i32(visitor),
+				WireType::Fixed64 => self.deserialize_i64(visitor),
+				_ => {
+					let tagbyte = self.read_byte()?;
+					if wire::read_wiretype(tagbyte) != WireType::Int {
+						return Err(Error::UnexpectedWireType);
+					}
+					let v = wire::zigzag_decode_128(self.read_varint_128(tagbyte)?);
+					match v.try_into() {
+						Ok(v) => visitor.visit_i64(v),
+						Err(_) => visitor.visit_i128(v),
+					}
+				}
+			},
+			wire::TYPE_UINT => match wire::read_wiretype(tagbyte) {
+				WireType::Fixed32 => self.deserialize_u32(visitor),
+				WireType::Fixed64 => self.deserialize_u64(visitor),
+				_ => {
+					let tagbyte = self.read_byte()?;
+					if wire::read_wiretype(tagbyte) != WireType::Int {
+						return Err(Error::UnexpectedWireType);
+					}
+					let v = self.read_varint_128(tagbyte)?;
+					match v.try_into() {
+						Ok(v) => visitor.visit_u64(v),
+						Err(_) => visitor.visit_u128(v),
+					}
+				}
+			},
+			wire::TYPE_FLOAT => match wire::read_wiretype(tagbyte) {
+				WireType::Fixed32 => self.deserialize_f32(visitor),
+				_ => self.deserialize_f64(visitor),
+			},
+			wire::TYPE_CHAR => self.deserialize_char(visitor),
+			wire::TYPE_STR => self.deserialize_str(visitor),
+			wire::TYPE_BYTES => self.deserialize_bytes(visitor),
+			wire::TYPE_UNIT => self.deserialize_unit(visitor),
+			wire::TYPE_OPTION => self.deserialize_option(visitor),
+			wire::TYPE_SEQ => self.deserialize_seq(visitor),
+			wire::TYPE_MAP => self.deserialize_map(visitor),
+			wire::TYPE_ENUM => {
+				// self-describing formats present an enum as a single-entry map of name to content
+				let name = self.read_str_value()?;
+				self.nested(|d| {
+					visitor.visit_map(VariantMap {
+						d,
+						key: Some(VariantKey::Name(name)),
+					})
+				})
 			}
+			_ => Err(Error::UnexpectedWireType),
 		}
-		Ok(())
 	}
 }
 
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
 	type Error = Error;
 
-	fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
-		unimplemented!()
+	fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+		match self.peek_wire_type()? {
+			// annotated values describe themselves, wherever they are found; everything inside is annotated too
+			WireType::Typed => self.with_annotated(true, |d| d.deserialize_annotated(visitor)),
+			WireType::Reserved => Err(Error::UnexpectedWireType),
+			_ if self.structural => self.deserialize_structural(visitor),
+			// sequences and byte strings can be traversed without knowing their type; scalars are ambiguous, e.g. an
+			// Int may be signed or unsigned
+			WireType::Sequence | WireType::Bytes => self.deserialize_structural(visitor),
+			_ => Err(Error::NotSelfDescribing),
+		}
 	}
 
 	#[inline]
 	fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-		let tagbyte = self.read_byte()?;
+		let tagbyte = self.read_tag(SIGNED)?;
 		if wire::read_wiretype(tagbyte) != WireType::Int {
 			return Err(Error::UnexpectedWireType);
 		}
-		let v: i8 = wire::zigzag_decode(self.read_varint(tagbyte)?).try_into()?;
+		let v = wire::zigzag_decode(self.read_varint(tagbyte)?);
+		let v: i8 = v.try_into().map_err(|_| Error::overflow::<i8>(v))?;
 		visitor.visit_i8(v)
 	}
 
 	#[inline]
 	fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-		let tagbyte = self.read_byte()?;
+		let tagbyte = self.read_tag(SIGNED)?;
 		if wire::read_wiretype(tagbyte) != WireType::Int {
 			return Err(Error::UnexpectedWireType);
 		}
-		let v: i16 = wire::zigzag_decode(self.read_varint(tagbyte)?).try_into()?;
+		let v = wire::zigzag_decode(self.read_varint(tagbyte)?);
+		let v: i16 = v.try_into().map_err(|_| Error::overflow::<i16>(v))?;
 		visitor.visit_i16(v)
 	}
 
 	#[inline]
 	fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-		// for 32-bit and 64-bit ints, we allow the Fixed32/Fixed64 wire type, for the
-		// case where perhaps someday we can tell serde that a value is not suitable
-		// as a varint (e.g. a hash value or other semi-random ID).
-		let tagbyte = self.read_byte()?;
+		// for 32-bit and wider ints, we allow the Fixed32/Fixed64 wire types, as written by the Fixed wrapper for
+		// values that are not suitable as a varint (e.g. a hash value or other semi-random ID); either width is
+		// accepted, so that such fields can change size like varint fields
+		let tagbyte = self.read_tag(SIGNED)?;
 		let v: i32 = match wire::read_wiretype(tagbyte) {
-			WireType::Int => wire::zigzag_decode(self.read_varint(tagbyte)?).try_into()?,
+			WireType::Int => {
+				let v = wire::zigzag_decode(self.read_varint(tagbyte)?);
+				v.try_into().map_err(|_| Error::overflow::<i32>(v))?
+			}
 			WireType::Fixed32 => i32::from_le_bytes(self.read_32()?),
+			WireType::Fixed64 => {
+				let v = i64::from_le_bytes(self.read_64()?);
+				v.try_into().map_err(|_| Error::overflow::<i32>(v))?
+			}
 			_ => return Err(Error::UnexpectedWireType),
 		};
 		visitor.visit_i32(v)
@@ -153,9 +770,10 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 
 	#[inline]
 	fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-		let tagbyte = self.read_byte()?;
+		let tagbyte = self.read_tag(SIGNED)?;
 		let v: i64 = match wire::read_wiretype(tagbyte) {
 			WireType::Int => wire::zigzag_decode(self.read_varint(tagbyte)?),
+			WireType::Fixed32 => i32::from_le_bytes(self.read_32()?) as i64,
 			WireType::Fixed64 => i64::from_le_bytes(self.read_64()?),
 			_ => return Err(Error::UnexpectedWireType),
 		};
@@ -164,30 +782,39 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 
 	#[inline]
 	fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-		let tagbyte = self.read_byte()?;
+		let tagbyte = self.read_tag(UNSIGNED)?;
 		if wire::read_wiretype(tagbyte) != WireType::Int {
 			return Err(Error::UnexpectedWireType);
 		}
-		let v: u8 = self.read_varint(tagbyte)?.try_into()?;
+		let v = self.read_varint(tagbyte)?;
+		let v: u8 = v.try_into().map_err(|_| Error::overflow::<u8>(v))?;
 		visitor.visit_u8(v)
 	}
 
 	#[inline]
 	fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-		let tagbyte = self.read_byte()?;
+		let tagbyte = self.read_tag(UNSIGNED)?;
 		if wire::read_wiretype(tagbyte) != WireType::Int {
 			return Err(Error::UnexpectedWireType);
 		}
-		let v: u16 = self.read_varint(tagbyte)?.try_into()?;
+		let v = self.read_varint(tagbyte)?;
+		let v: u16 = v.try_into().map_err(|_| Error::overflow::<u16>(v))?;
 		visitor.visit_u16(v)
 	}
 
 	#[inline]
 	fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-		let tagbyte = self.read_byte()?;
+		let tagbyte = self.read_tag(UNSIGNED)?;
 		let v: u32 = match wire::read_wiretype(tagbyte) {
-			WireType::Int => self.read_varint(tagbyte)?.try_into()?,
+			WireType::Int => {
+				let v = self.read_varint(tagbyte)?;
+				v.try_into().map_err(|_| Error::overflow::<u32>(v))?
+			}
 			WireType::Fixed32 => u32::from_le_bytes(self.read_32()?),
+			WireType::Fixed64 => {
+				let v = u64::from_le_bytes(self.read_64()?);
+				v.try_into().map_err(|_| Error::overflow::<u32>(v))?
+			}
 			_ => return Err(Error::UnexpectedWireType),
 		};
 		visitor.visit_u32(v)
@@ -195,9 +822,10 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 
 	#[inline]
 	fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-		let tagbyte = self.read_byte()?;
+		let tagbyte = self.read_tag(UNSIGNED)?;
 		let v: u64 = match wire::read_wiretype(tagbyte) {
 			WireType::Int => self.read_varint(tagbyte)?,
+			WireType::Fixed32 => u32::from_le_bytes(self.read_32()?) as u64,
 			WireType::Fixed64 => u64::from_le_bytes(self.read_64()?),
 			_ => return Err(Error::UnexpectedWireType),
 		};
@@ -206,7 +834,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 
 	#[inline]
 	fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-		let tagbyte = self.read_byte()?;
+		let tagbyte = self.read_tag(&[wire::TYPE_FLOAT])?;
 		let v = match wire::read_wiretype(tagbyte) {
 			WireType::Fixed32 => f32::from_le_bytes(self.read_32()?),
 			WireType::Fixed64 => f64::from_le_bytes(self.read_64()?) as f32, // truncate silently
@@ -217,7 +845,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 
 	#[inline]
 	fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-		let tagbyte = self.read_byte()?;
+		let tagbyte = self.read_tag(&[wire::TYPE_FLOAT])?;
 		let v = match wire::read_wiretype(tagbyte) {
 			WireType::Fixed32 => f32::from_le_bytes(self.read_32()?) as f64,
 			WireType::Fixed64 => f64::from_le_bytes(self.read_64()?),
@@ -228,45 +856,64 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 
 	#[inline]
 	fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+		self.expect_annotation(&[wire::TYPE_BOOL, wire::TYPE_UINT])?;
+		let wire_type = self.peek_wire_type()?;
+		if self.strict_bools && wire_type != WireType::Int {
+			return Err(Error::UnexpectedWireType);
+		}
+		if wire_type == WireType::Variant {
+			// a unit variant, e.g. of an `Option<()>` or a fieldless enum that has become a bool: false for the first
+			// variant, true otherwise
+			let tagbyte = self.read_byte()?;
+			let discriminator = self.read_varint(tagbyte)?;
+			if self.read_byte()? != 0 {
+				return Err(de::Error::invalid_value(
+					de::Unexpected::Other("variant with content"),
+					&"a unit variant",
+				));
+			}
+			return visitor.visit_bool(discriminator != 0);
+		}
 		let v: u64 = de::Deserialize::deserialize(self)?;
 		visitor.visit_bool(v != 0)
 	}
 
-	serde::serde_if_integer128! {
-		#[inline]
-		fn deserialize_i128<V:Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-			let tagbyte = self.read_byte()?;
-			if wire::read_wiretype(tagbyte) != WireType::Int {
-				return Err(Error::UnexpectedWireType);
-			}
-			let v  = wire::zigzag_decode_128(self.read_varint_128(tagbyte)?);
-			visitor.visit_i128(v)
-		}
+	#[inline]
+	fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+		let tagbyte = self.read_tag(SIGNED)?;
+		let v = match wire::read_wiretype(tagbyte) {
+			WireType::Int => wire::zigzag_decode_128(self.read_varint_128(tagbyte)?),
+			WireType::Fixed32 => i32::from_le_bytes(self.read_32()?) as i128,
+			WireType::Fixed64 => i64::from_le_bytes(self.read_64()?) as i128,
+			_ => return Err(Error::UnexpectedWireType),
+		};
+		visitor.visit_i128(v)
+	}
 
-		#[inline]
-		fn deserialize_u128<V:Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-			let tagbyte = self.read_byte()?;
-			if wire::read_wiretype(tagbyte) != WireType::Int {
-				return Err(Error::UnexpectedWireType);
-			}
-			let v  = self.read_varint_128(tagbyte)?;
-			visitor.visit_u128(v)
-		}
+	#[inline]
+	fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+		let tagbyte = self.read_tag(UNSIGNED)?;
+		let v = match wire::read_wiretype(tagbyte) {
+			WireType::Int => self.read_varint_128(tagbyte)?,
+			WireType::Fixed32 => u32::from_le_bytes(self.read_32()?) as u128,
+			WireType::Fixed64 => u64::from_le_bytes(self.read_64()?) as u128,
+			_ => return Err(Error::UnexpectedWireType),
+		};
+		visitor.visit_u128(v)
 	}
 
 	#[inline]
 	fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-		use std::convert::TryFrom;
+		use core::convert::TryFrom;
+		self.expect_annotation(&[wire::TYPE_CHAR, wire::TYPE_UINT])?;
 		let v: u32 = de::Deserialize::deserialize(self)?;
-		let c = char::try_from(v)?;
+		let c = char::try_from(v).map_err(|_| Error::InvalidChar(v))?;
 		visitor.visit_char(c)
 	}
 
 	#[inline]
 	fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-		let bytes: &'de [u8] = de::Deserialize::deserialize(self)?;
-		let s = std::str::from_utf8(bytes)?;
-		visitor.visit_borrowed_str(s)
+		visitor.visit_borrowed_str(self.read_str_value()?)
 	}
 
 	#[inline]
@@ -276,12 +923,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 
 	#[inline]
 	fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-		let tagbyte = self.read_byte()?;
-		if wire::read_wiretype(tagbyte) != WireType::Bytes {
-			return Err(Error::UnexpectedWireType);
-		}
-		let len = self.read_varint(tagbyte)?;
-		let bytes = self.read(len as usize)?;
+		let bytes = self.read_bytes_value()?;
 		visitor.visit_borrowed_bytes(bytes)
 	}
 
@@ -292,16 +934,20 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 
 	#[inline]
 	fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-		let tagbyte = self.read_byte()?;
+		let tagbyte = self.read_tag(&[wire::TYPE_OPTION])?;
 		if wire::read_wiretype(tagbyte) != WireType::Variant {
 			return Err(Error::UnexpectedWireType);
 		}
-		let b = self.read_varint(tagbyte)?;
-		if b == 0 {
-			self.skip()?;
-			visitor.visit_none()
-		} else {
-			visitor.visit_some(self)
+		match self.read_varint(tagbyte)? {
+			0 => {
+				self.skip()?;
+				visitor.visit_none()
+			}
+			1 => self.nested(|d| visitor.visit_some(d)),
+			b => Err(de::Error::invalid_value(
+				de::Unexpected::Unsigned(b),
+				&"option discriminator 0 or 1",
+			)),
 		}
 	}
 
@@ -318,36 +964,71 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 	}
 
 	#[inline]
-	fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
-		visitor.visit_newtype_struct(self)
+	fn deserialize_newtype_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V) -> Result<V::Value> {
+		if name == UNTAGGED_TOKEN {
+			self.expect_annotation(&[wire::TYPE_UINT, wire::TYPE_SEQ, wire::TYPE_BYTES])?;
+			let &tagbyte = self.input.first().ok_or(Error::UnexpectedEndOfInput)?;
+			return match wire::read_wiretype(tagbyte) {
+				// values that fit are passed as u64, which is all that most visitors implement
+				WireType::Int => {
+					let tagbyte = self.read_byte()?;
+					match self.read_varint_128(tagbyte)? {
+						v if v <= u64::MAX as u128 => visitor.visit_u64(v as u64),
+						v => visitor.visit_u128(v),
+					}
+				}
+				WireType::Sequence => self.deserialize_seq(visitor),
+				WireType::Bytes => self.deserialize_bytes(visitor),
+				_ => Err(Error::UnexpectedWireType),
+			};
+		}
+		if name == LENIENT_TOKEN {
+			return visitor.visit_seq(LenientAccess { d: self, index: None });
+		}
+		if name == FIXED_BYTES_TOKEN {
+			self.expect_annotation(&[wire::TYPE_BYTES, wire::TYPE_SEQ])?;
+			return visitor.visit_some(FixedBytes(self));
+		}
+		if name == FLOAT_BYTES_TOKEN {
+			self.expect_annotation(&[wire::TYPE_BYTES, wire::TYPE_FLOAT])?;
+			let &tagbyte = self.input.first().ok_or(Error::UnexpectedEndOfInput)?;
+			return match wire::read_wiretype(tagbyte) {
+				WireType::Bytes => self.deserialize_bytes(visitor),
+				WireType::Fixed32 => self.deserialize_f32(visitor),
+				WireType::Fixed64 => self.deserialize_f64(visitor),
+				_ => Err(Error::UnexpectedWireType),
+			};
+		}
+		self.nested(|d| visitor.visit_newtype_struct(d))
 	}
 
 	#[inline]
 	fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-		let tagbyte = self.read_byte()?;
-		if wire::read_wiretype(tagbyte) != WireType::Sequence {
-			return Err(Error::UnexpectedWireType);
+		let tagbyte = self.read_tag(&[wire::TYPE_SEQ])?;
+		match wire::read_wiretype(tagbyte) {
+			WireType::Sequence => {}
+			WireType::Bytes => return Err(Error::ExpectedSequenceFoundBytes),
+			_ => return Err(Error::UnexpectedWireType),
 		}
-		let n = self.read_varint(tagbyte)? as usize;
-		visitor.visit_seq(SeqRead {
-			d: self,
-			nread: n,
-			nreturn: n,
+		let n = self.read_len(tagbyte)?;
+		self.check_seq_len(n)?;
+		let claimed = if self.strict_sequence_len { Some(n) } else { None };
+		self.nested(|d| {
+			visitor.visit_seq(SeqRead {
+				d,
+				nread: n,
+				nreturn: n,
+				claimed,
+				keys: None,
+				fields: &[],
+				index: 0,
+			})
 		})
 	}
 
 	#[inline]
 	fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
-		let tagbyte = self.read_byte()?;
-		if wire::read_wiretype(tagbyte) != WireType::Sequence {
-			return Err(Error::UnexpectedWireType);
-		}
-		let n = self.read_varint(tagbyte)? as usize;
-		visitor.visit_seq(SeqRead {
-			d: self,
-			nread: n,
-			nreturn: std::cmp::min(n, len),
-		})
+		self.read_tuple(len, &[], self.strict_tuple_len, visitor)
 	}
 
 	#[inline]
@@ -357,23 +1038,35 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 		len: usize,
 		visitor: V,
 	) -> Result<V::Value> {
-		self.deserialize_tuple(len, visitor)
+		self.read_tuple(len, &[], false, visitor)
 	}
 
 	#[inline]
 	fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-		let tagbyte = self.read_byte()?;
+		let tagbyte = self.read_tag(&[wire::TYPE_MAP])?;
 		if wire::read_wiretype(tagbyte) != WireType::Sequence {
 			return Err(Error::UnexpectedWireType);
 		}
-		let n = self.read_varint(tagbyte)? as usize;
+		let n = self.read_len(tagbyte)?;
 		if n % 2 != 0 {
 			return Err(Error::InvalidMap);
 		}
-		visitor.visit_map(SeqRead {
-			d: self,
-			nread: n,
-			nreturn: n / 2,
+		self.check_seq_len(n / 2)?;
+		let keys = if self.strict_maps { Some(BTreeSet::new()) } else { None };
+		// the entries of a map of unknown length, e.g. a struct with flattened fields, are annotated
+		let annotated = self.annotated || (n > 0 && self.peek_wire_type()? == WireType::Typed);
+		self.nested(|d| {
+			d.with_annotated(annotated, |d| {
+				visitor.visit_map(SeqRead {
+					d,
+					nread: n,
+					nreturn: n / 2,
+					claimed: None,
+					keys,
+					fields: &[],
+					index: 0,
+				})
+			})
 		})
 	}
 
@@ -384,7 +1077,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 		fields: &'static [&'static str],
 		visitor: V,
 	) -> Result<V::Value> {
-		self.deserialize_tuple(fields.len(), visitor)
+		self.read_tuple(fields.len(), fields, false, visitor)
 	}
 
 	#[inline]
@@ -394,18 +1087,37 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 		_variants: &'static [&'static str],
 		visitor: V,
 	) -> Result<V::Value> {
-		visitor.visit_enum(self)
+		match self.read_annotation()? {
+			Some(wire::TYPE_ENUM) => {
+				// annotated enums are identified by name
+				let variant_names = core::mem::replace(&mut self.variant_names, true);
+				let result = self.nested(|d| visitor.visit_enum(&mut *d));
+				self.variant_names = variant_names;
+				result
+			}
+			Some(_) => Err(Error::UnexpectedWireType),
+			None => self.nested(|d| visitor.visit_enum(d)),
+		}
 	}
 
 	#[inline]
 	fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-		self.deserialize_u32(visitor)
+		// field names are only written as map keys, inside flattened structs
+		self.expect_annotation(&[wire::TYPE_STR, wire::TYPE_UINT])?;
+		match self.input.first().map(|&b| wire::read_wiretype(b)) {
+			Some(WireType::Bytes) => self.deserialize_str(visitor),
+			_ => self.deserialize_u32(visitor),
+		}
 	}
 
 	fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
 		self.skip()?;
 		visitor.visit_unit()
 	}
+
+	fn is_human_readable(&self) -> bool {
+		false
+	}
 }
 
 impl<'de, 'a> EnumAccess<'de> for &'a mut Deserializer<'de> {
@@ -417,19 +1129,36 @@ impl<'de, 'a> EnumAccess<'de> for &'a mut Deserializer<'de> {
 		// we want to read a u32, but with a different wire type, so can't simply use
 		// deserializer -- read the discriminant then force it into a deserializer
 		let tagbyte = self.read_byte()?;
-		if wire::read_wiretype(tagbyte) != WireType::Variant {
-			return Err(Error::UnexpectedWireType)?;
-		}
-		let discr: u32 = self.read_varint(tagbyte)?.try_into()?;
-		use de::IntoDeserializer;
-		let d: de::value::U32Deserializer<Error> = discr.into_deserializer();
-		let val = seed.deserialize(d)?;
+		let val = match wire::read_wiretype(tagbyte) {
+			WireType::Variant => {
+				let discr = self.read_varint(tagbyte)?;
+				let discr: u32 = discr.try_into().map_err(|_| Error::InvalidVariantIndex(discr))?;
+				use de::IntoDeserializer;
+				let d: de::value::U32Deserializer<Error> = discr.into_deserializer();
+				// the only value the variant identifier can reject is the index itself
+				seed.deserialize(d).map_err(|e| match e {
+					Error::InvalidValue { .. } => Error::UnknownVariant(discr),
+					e => e,
+				})?
+			}
+			WireType::Bytes if self.variant_names => {
+				// serde-derive's variant identifiers match by name when visiting a string
+				let len = self.read_len(tagbyte)?;
+				let name = core::str::from_utf8(self.read(len)?)?;
+				seed.deserialize(de::value::BorrowedStrDeserializer::<Error>::new(name))?
+			}
+			_ => return Err(Error::UnexpectedWireType),
+		};
 		Ok((
 			val,
 			SeqRead {
 				d: self,
 				nread: 1,
 				nreturn: 1,
+				claimed: None,
+				keys: None,
+				fields: &[],
+				index: 0,
 			},
 		))
 	}
@@ -439,10 +1168,41 @@ pub struct SeqRead<'de, 'a> {
 	d: &'a mut Deserializer<'de>,
 	nread: usize,
 	nreturn: usize,
+	// claimed length, if running out of input should be reported as a count mismatch
+	claimed: Option<usize>,
+	// encoded keys seen so far, if duplicate map keys should be reported
+	keys: Option<BTreeSet<&'de [u8]>>,
+	// field names of a struct, and the index of the next item, to report where an error occurred
+	fields: &'static [&'static str],
+	index: usize,
 }
 
-// this is for the case when an overly long struct or tuple is received, or not the entire sequence is read for another
-// reason, or the variant is not accessed (in #[serde(other)])
+impl<'de, 'a> SeqRead<'de, 'a> {
+	// the path segment of the next item
+	#[inline]
+	fn segment(&mut self) -> PathSegment {
+		let index = self.index;
+		self.index += 1;
+		match self.fields.get(index) {
+			Some(&name) => PathSegment::Field(name),
+			None => PathSegment::Index(index),
+		}
+	}
+
+	// skip the items that are not going to be returned, e.g. when an overly long struct or tuple is received; this is
+	// done as soon as the last item is returned, so that errors in the tail are not lost
+	#[inline]
+	fn skip_rest(&mut self) -> Result<()> {
+		while self.nread > 0 {
+			self.d.skip()?;
+			self.nread -= 1;
+		}
+		Ok(())
+	}
+}
+
+// this is for the case when not the entire sequence is read for another reason, e.g. the visitor bailed out early;
+// there's no way to report an error here
 impl<'de, 'a> Drop for SeqRead<'de, 'a> {
 	#[inline]
 	fn drop(&mut self) {
@@ -460,12 +1220,26 @@ impl<'de, 'a> SeqAccess<'de> for SeqRead<'de, 'a> {
 	#[inline]
 	fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
 		if self.nreturn == 0 {
+			self.skip_rest()?;
 			return Ok(None);
 		}
 		self.nreturn -= 1;
 		debug_assert!(self.nread > 0);
 		self.nread -= 1;
-		Ok(Some(seed.deserialize(&mut *self.d)?))
+		let segment = self.segment();
+		let value = match (seed.deserialize(&mut *self.d), self.claimed) {
+			(Err(Error::UnexpectedEndOfInput), Some(claimed)) => {
+				return Err(Error::SequenceCountMismatch {
+					claimed,
+					decoded: claimed - self.nread - 1,
+				})
+			}
+			(result, _) => result.map_err(|e| e.at(segment))?,
+		};
+		if self.nreturn == 0 {
+			self.skip_rest()?;
+		}
+		Ok(Some(value))
 	}
 	#[inline]
 	fn size_hint(&self) -> Option<usize> {
@@ -498,23 +1272,140 @@ impl<'de, 'a> VariantAccess<'de> for SeqRead<'de, 'a> {
 	}
 }
 
+struct LenientAccess<'de, 'a> {
+	d: &'a mut Deserializer<'de>,
+	// discriminator of an unknown variant, once skipped
+	index: Option<u32>,
+}
+
+impl<'de, 'a> SeqAccess<'de> for LenientAccess<'de, 'a> {
+	type Error = Error;
+	fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+		if let Some(index) = self.index.take() {
+			return seed.deserialize(de::value::U32Deserializer::new(index)).map(Some);
+		}
+		let checkpoint = self.d.clone();
+		// where the content starts, if this is an enum
+		let content_len = match checkpoint.input.split_first() {
+			Some((&tagbyte, rest)) if wire::read_wiretype(tagbyte) == WireType::Variant => {
+				wire::read_varint(tagbyte, rest).ok().map(|(_, len)| rest.len() - len)
+			}
+			_ => None,
+		};
+		match seed.deserialize(&mut *self.d) {
+			// the error may also come from an enum nested in a known variant, which would have read further
+			Err(Error::UnknownVariant(index)) if Some(self.d.remaining_len()) == content_len => {
+				*self.d = checkpoint;
+				self.d.skip()?;
+				self.index = Some(index);
+				Ok(None)
+			}
+			result => result.map(Some),
+		}
+	}
+}
+
+// the content of FixedBytes, which only supports deserialize_tuple
+struct FixedBytes<'de, 'a>(&'a mut Deserializer<'de>);
+
+impl<'de, 'a> de::Deserializer<'de> for FixedBytes<'de, 'a> {
+	type Error = Error;
+
+	fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+		Err(Error::NotSelfDescribing)
+	}
+
+	fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+		let &tagbyte = self.0.input.first().ok_or(Error::UnexpectedEndOfInput)?;
+		let found = match wire::read_wiretype(tagbyte) {
+			WireType::Bytes => {
+				let bytes = self.0.read_bytes_value()?;
+				if bytes.len() == len {
+					return visitor.visit_borrowed_bytes(bytes);
+				}
+				bytes.len()
+			}
+			// the default encoding of [u8; N], as written before the field was wrapped
+			WireType::Sequence => match self.0.read_tuple(len, &[], true, visitor) {
+				Err(Error::TupleLengthMismatch { found, .. }) => found,
+				result => return result,
+			},
+			_ => return Err(Error::UnexpectedWireType),
+		};
+		Err(Error::WrongLength { expected: len, found })
+	}
+
+	serde::forward_to_deserialize_any! {
+		bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option unit unit_struct
+		newtype_struct seq tuple_struct map struct enum identifier ignored_any
+	}
+}
+
+// an annotated enum, presented as a map with a single entry
+struct VariantMap<'de, 'a> {
+	d: &'a mut Deserializer<'de>,
+	key: Option<VariantKey<'de>>,
+}
+
+enum VariantKey<'de> {
+	Name(&'de str),
+	// only in structural mode, where the enum type isn't known
+	Index(u64),
+}
+
+impl<'de, 'a> MapAccess<'de> for VariantMap<'de, 'a> {
+	type Error = Error;
+	#[inline]
+	fn next_key_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+		match self.key.take() {
+			Some(VariantKey::Name(name)) => seed
+				.deserialize(de::value::BorrowedStrDeserializer::new(name))
+				.map(Some),
+			Some(VariantKey::Index(index)) => seed.deserialize(de::value::U64Deserializer::new(index)).map(Some),
+			None => Ok(None),
+		}
+	}
+	#[inline]
+	fn next_value_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<T::Value> {
+		seed.deserialize(&mut *self.d)
+	}
+	#[inline]
+	fn size_hint(&self) -> Option<usize> {
+		Some(self.key.is_some() as usize)
+	}
+}
+
 impl<'de, 'a> MapAccess<'de> for SeqRead<'de, 'a> {
 	type Error = Error;
 	#[inline]
 	fn next_key_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
 		if self.nreturn == 0 {
+			// normally nothing left, but a visitor may have skipped the value of the last entry
+			self.skip_rest()?;
 			return Ok(None);
 		}
 		self.nreturn -= 1;
 		debug_assert!(self.nread > 0);
 		self.nread -= 1;
-		Ok(Some(seed.deserialize(&mut *self.d)?))
+		self.d.check_annotated()?;
+		let start = self.d.input;
+		let key = seed.deserialize(&mut *self.d)?;
+		if let Some(keys) = &mut self.keys {
+			if !keys.insert(&start[..start.len() - self.d.input.len()]) {
+				return Err(Error::DuplicateKey);
+			}
+		}
+		Ok(Some(key))
 	}
 	#[inline]
 	fn next_value_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<T::Value> {
 		debug_assert!(self.nread > 0);
 		self.nread -= 1;
-		seed.deserialize(&mut *self.d)
+		// entries are counted by their key
+		self.d.check_annotated()?;
+		let segment = PathSegment::Index(self.index);
+		self.index += 1;
+		seed.deserialize(&mut *self.d).map_err(|e| e.at(segment))
 	}
 	#[inline]
 	fn size_hint(&self) -> Option<usize> {
@@ -0,0 +1,86 @@
+//! Compact encoding of [`Duration`] as a single varint of nanoseconds.
+//!
+//! Serde's default encoding of a `Duration` is a struct of seconds and nanoseconds, which takes two varints plus a
+//! sequence header. Use this module on a field to encode it as a single varint instead:
+//!
+//! ```
+//! # use serde::{Serialize, Deserialize};
+//! # use std::time::Duration;
+//! #[derive(Serialize, Deserialize)]
+//! struct Foo {
+//!     #[serde(with = "fcode::duration_nanos")]
+//!     timeout: Duration,
+//! }
+//! ```
+//!
+//! Durations of up to 10^20 nanoseconds (about 3170 years) are written as a single varint of at most ten bytes;
+//! longer ones fall back to the default struct encoding. Decoding accepts both forms, so adding the attribute to an
+//! existing field is a compatible change. Removing it is not.
+//!
+//! With the `std` feature, `system_time_nanos` provides the same encoding for a `SystemTime`.
+//!
+//! Other serde formats see an unsigned integer (a `u128` beyond about 584 years), or a `(seconds, nanoseconds)` tuple
+//! beyond the compact range; decoding there requires a self-describing format.
+
+use crate::de::UNTAGGED_TOKEN;
+use core::convert::TryInto;
+use core::fmt;
+use core::time::Duration;
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::ser::Serializer;
+
+const NANOS_PER_SEC: u32 = 1_000_000_000;
+
+// longest duration in the compact form; a varint of up to 67 bits fits in ten bytes
+const MAX_COMPACT_NANOS: u128 = 100_000_000_000_000_000_000;
+
+pub fn serialize<S: Serializer>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+	let nanos = value.as_nanos();
+	if nanos > MAX_COMPACT_NANOS {
+		return serde::Serialize::serialize(&(value.as_secs(), value.subsec_nanos()), serializer);
+	}
+	// u64 where possible, for formats without 128-bit support
+	match nanos.try_into() {
+		Ok(nanos) => serializer.serialize_u64(nanos),
+		Err(_) => serializer.serialize_u128(nanos),
+	}
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+	deserializer.deserialize_newtype_struct(UNTAGGED_TOKEN, DurationVisitor)
+}
+
+struct DurationVisitor;
+
+impl<'de> Visitor<'de> for DurationVisitor {
+	type Value = Duration;
+
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("a duration in nanoseconds, or seconds and nanoseconds")
+	}
+
+	fn visit_u64<E: de::Error>(self, v: u64) -> Result<Duration, E> {
+		Ok(Duration::from_nanos(v))
+	}
+
+	fn visit_u128<E: de::Error>(self, v: u128) -> Result<Duration, E> {
+		let secs = (v / NANOS_PER_SEC as u128)
+			.try_into()
+			.map_err(|_| E::invalid_value(de::Unexpected::Other("nanoseconds beyond u64 seconds"), &self))?;
+		Ok(Duration::new(secs, (v % NANOS_PER_SEC as u128) as u32))
+	}
+
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Duration, A::Error> {
+		let secs: u64 = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+		let nanos: u32 = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+		if nanos >= NANOS_PER_SEC {
+			return Err(de::Error::invalid_value(de::Unexpected::Unsigned(nanos as u64), &self));
+		}
+		Ok(Duration::new(secs, nanos))
+	}
+
+	// for other formats
+	fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<Duration, D::Error> {
+		deserializer.deserialize_any(self)
+	}
+}
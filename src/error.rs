@@ -1,71 +1,217 @@
+use alloc::{
+	format,
+	string::{String, ToString},
+};
 use thiserror::Error;
 
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
 
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
 	/// The input was incomplete.
 	#[error("unexpected end of input")]
 	UnexpectedEndOfInput,
-	/// The value read was not a valid `char`.
-	#[error("invalid character")]
-	InvalidChar,
+	/// The value read was not a valid `char`: a surrogate, or beyond the Unicode range.
+	#[error("invalid character {0:#x}")]
+	InvalidChar(u32),
 	/// The byte array read did not contain valid UTF-8.
 	#[error("invalid UTF-8 data")]
 	InvalidUtf8,
 	/// The input was longer than expected. If it was expected, please use [`from_bytes_more_data`](fn@crate::from_bytes_more_data).
 	#[error("data beyond end")]
 	DataBeyondEnd,
-	/// The value read doesn't fit into the expected integer type.
-	#[error("data value too large")]
-	ValueOverflow,
+	/// The value read doesn't fit into the expected integer type. The value is known if it could be decoded, i.e. if
+	/// it fits in 128 bits; otherwise, the varint itself is too long for the target type.
+	#[error("data value {}too large for {target}", overflow_value(.value))]
+	ValueOverflow { value: Option<i128>, target: &'static str },
 	/// The wire type of the value doesn't match the expected type
 	#[error("unexpected wire type")]
 	UnexpectedWireType,
+	/// A sequence was expected, but a byte string was found. This usually means that one side uses `serde_bytes`
+	/// (or `&[u8]`), and the other a plain `Vec<u8>`.
+	#[error("expected sequence, found bytes; mismatched use of serde_bytes?")]
+	ExpectedSequenceFoundBytes,
+	/// A value was to be decoded without knowing its type, which fcode doesn't support except for values inside
+	/// flattened structs, and sequences and byte strings, which can be traversed as such.
+	#[error("value is not self-describing")]
+	NotSelfDescribing,
+	/// The checksum of the data doesn't match its content, i.e. the data is corrupt.
+	#[error("checksum mismatch")]
+	ChecksumMismatch,
+	/// Compressed data starts with a prefix byte that isn't known, i.e. it wasn't written by `to_bytes_compressed`
+	/// (with the `compression` feature).
+	#[error("unknown compression method {0}")]
+	UnknownCompression(u8),
+	/// The type fingerprint of the data doesn't match the type it's decoded as.
+	#[error("schema mismatch")]
+	SchemaMismatch,
+	/// A map contains the same key more than once, while checking for strict maps.
+	#[error("duplicate map key")]
+	DuplicateKey,
+	/// A varint was encoded with more bytes than necessary, while checking for canonical encoding.
+	#[error("non-canonical varint")]
+	NonCanonicalVarint,
+	/// More bytes were consumed than the deserializer's [`max_bytes`](crate::Deserializer::max_bytes) limit, or
+	/// compressed data inflated beyond the limit given to `from_bytes_compressed`.
+	#[error("input exceeds byte limit")]
+	InputTooLarge,
+	/// A sequence or map claimed more elements than the deserializer's
+	/// [`max_seq_len`](crate::Deserializer::max_seq_len) limit.
+	#[error("sequence length {len} exceeds limit {max}")]
+	SequenceTooLong { len: usize, max: usize },
+	/// A string claimed more bytes than the deserializer's [`max_string_len`](crate::Deserializer::max_string_len)
+	/// limit.
+	#[error("string length {len} exceeds limit {max}")]
+	StringTooLong { len: usize, max: usize },
+	/// Values were nested deeper than the deserializer's depth limit.
+	#[error("depth limit exceeded")]
+	DepthLimitExceeded,
+	/// A sequence ran out of input before its claimed number of elements (with
+	/// [`strict_sequence_len`](crate::Deserializer::strict_sequence_len)).
+	#[error("sequence claimed {claimed} elements, but only {decoded} could be decoded")]
+	SequenceCountMismatch { claimed: usize, decoded: usize },
+	/// A tuple or array was received with a different number of elements than expected (with
+	/// [`strict_tuple_len`](crate::Deserializer::strict_tuple_len)).
+	#[error("tuple length {found}, expected {expected}")]
+	TupleLengthMismatch { expected: usize, found: usize },
+	/// A [`FixedBytes`](crate::FixedBytes) value was received with a different number of bytes than its size.
+	#[error("byte array length {found}, expected {expected}")]
+	WrongLength { expected: usize, found: usize },
+	/// A tuple or struct was received with fewer elements than required, i.e. a trailing field is missing that
+	/// doesn't have `#[serde(default)]`. The path tells where, as for [`Deserialization`](Error::Deserialization).
+	#[error("invalid length {len}, expected {expected}{}", at_path(.path))]
+	InvalidLength { len: usize, expected: String, path: String },
+	/// A value was decoded that the target type doesn't accept, e.g. zero for a `NonZeroU32`. The path tells where,
+	/// as for [`Deserialization`](Error::Deserialization).
+	#[error("invalid value: {found}, expected {expected}{}", at_path(.path))]
+	InvalidValue {
+		found: String,
+		expected: String,
+		path: String,
+	},
+	/// An enum variant index is not known to the receiver, and the enum has no `#[serde(other)]` variant. This usually
+	/// means that the sender has a newer version of the enum.
+	#[error("unknown variant index {0}")]
+	UnknownVariant(u32),
+	/// An enum discriminator doesn't fit in a `u32`, which means the data is corrupt or not an enum.
+	#[error("invalid variant index {0}")]
+	InvalidVariantIndex(u64),
 	/// A sequence with an odd number of elements was read, which is invalid for a map.
 	#[error("invalid map encoding")]
 	InvalidMap,
+	/// A struct field was conditionally skipped, which is not possible in a positional format.
+	#[error("optionally skipped fields are not supported")]
+	CannotSkipField,
+	/// The value doesn't fit in the output buffer of a [`SliceWriter`](crate::SliceWriter).
+	#[error("output buffer full")]
+	BufferFull,
+	/// A NaN or infinite float was serialized (with [`strict_floats`](crate::Serializer::strict_floats)).
+	#[error("non-finite float")]
+	NonFiniteFloat,
 	/// Serde framework error.
 	#[error("serialization error: {0}")]
 	Serialization(String),
-	/// Serde framework error.
-	#[error("deserialization error: {0}")]
-	Deserialization(String),
+	/// Error reported by the type being decoded, e.g. a missing field. The path tells where the value was found, e.g.
+	/// `outer.inner[3].field`: struct fields by name, and sequence elements and map entries by index. It's empty for
+	/// the top-level value.
+	#[error("deserialization error: {msg}{}", at_path(.path))]
+	Deserialization { msg: String, path: String },
 	/// I/O error in writer.
+	#[cfg(feature = "std")]
 	#[error("I/O error: {0}")]
 	IO(#[source] std::io::Error),
 }
 
 impl serde::ser::Error for Error {
-	fn custom<T: std::fmt::Display>(msg: T) -> Self {
+	fn custom<T: core::fmt::Display>(msg: T) -> Self {
 		Error::Serialization(msg.to_string())
 	}
 }
 
 impl serde::de::Error for Error {
-	fn custom<T: std::fmt::Display>(msg: T) -> Self {
-		Error::Deserialization(msg.to_string())
+	fn custom<T: core::fmt::Display>(msg: T) -> Self {
+		Error::Deserialization {
+			msg: msg.to_string(),
+			path: String::new(),
+		}
+	}
+
+	fn invalid_value(unexp: serde::de::Unexpected, exp: &dyn serde::de::Expected) -> Self {
+		Error::InvalidValue {
+			found: unexp.to_string(),
+			expected: exp.to_string(),
+			path: String::new(),
+		}
+	}
+
+	fn invalid_length(len: usize, exp: &dyn serde::de::Expected) -> Self {
+		Error::InvalidLength {
+			len,
+			expected: exp.to_string(),
+			path: String::new(),
+		}
+	}
+}
+
+impl Error {
+	// a decoded integer that doesn't fit its target type
+	pub(crate) fn overflow<T>(value: impl Into<i128>) -> Self {
+		Error::ValueOverflow {
+			value: Some(value.into()),
+			target: core::any::type_name::<T>(),
+		}
+	}
+}
+
+// a step in the path to a value that failed to decode
+pub(crate) enum PathSegment {
+	Field(&'static str),
+	Index(usize),
+}
+
+impl Error {
+	// add the location of a nested value to the path of errors reported by the type, while unwinding
+	pub(crate) fn at(mut self, segment: PathSegment) -> Self {
+		if let Error::Deserialization { path, .. }
+		| Error::InvalidValue { path, .. }
+		| Error::InvalidLength { path, .. } = &mut self
+		{
+			let rest = core::mem::take(path);
+			*path = match segment {
+				PathSegment::Field(name) => String::from(name),
+				PathSegment::Index(index) => format!("[{}]", index),
+			};
+			if !rest.is_empty() && !rest.starts_with('[') {
+				path.push('.');
+			}
+			path.push_str(&rest);
+		}
+		self
 	}
 }
 
-impl From<std::num::TryFromIntError> for Error {
-	fn from(_e: std::num::TryFromIntError) -> Self {
-		Error::ValueOverflow
+fn at_path(path: &str) -> String {
+	match path {
+		"" => String::new(),
+		path => format!(" at {}", path),
 	}
 }
 
-impl From<std::char::CharTryFromError> for Error {
-	fn from(_e: std::char::CharTryFromError) -> Self {
-		Error::InvalidChar
+fn overflow_value(value: &Option<i128>) -> String {
+	match value {
+		Some(v) => v.to_string() + " ",
+		None => String::new(),
 	}
 }
 
-impl From<std::str::Utf8Error> for Error {
-	fn from(_e: std::str::Utf8Error) -> Self {
+impl From<core::str::Utf8Error> for Error {
+	fn from(_e: core::str::Utf8Error) -> Self {
 		Error::InvalidUtf8
 	}
 }
 
+#[cfg(feature = "std")]
 impl From<std::io::Error> for Error {
 	fn from(e: std::io::Error) -> Self {
 		Error::IO(e)
@@ -0,0 +1,52 @@
+//! Encoding of a newtype struct as a 1-tuple, so that it can later be extended with more fields.
+//!
+//! Newtype structs are transparent on the wire, which means that `Foo(x)` can't become `Foo(x, y)`: the tuple has a
+//! sequence header, the newtype doesn't. Use this module on the field of a newtype struct to encode it as a sequence of
+//! one element instead:
+//!
+//! ```
+//! # use serde::{Serialize, Deserialize};
+//! #[derive(Serialize, Deserialize)]
+//! struct Foo(#[serde(with = "fcode::extendable")] u32);
+//! ```
+//!
+//! The struct is then encoded like a tuple, and may be extended like one: `Foo(u32, #[serde(default)] String)`. This is
+//! a one-time decision at creation of the type; adding the attribute to an existing newtype is not a compatible change.
+//! Once extended, the attribute must be removed, as the struct is no longer a newtype.
+
+use core::fmt;
+use core::marker::PhantomData;
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeTuple, Serializer};
+
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+	T: Serialize,
+	S: Serializer,
+{
+	let mut tup = serializer.serialize_tuple(1)?;
+	tup.serialize_element(value)?;
+	tup.end()
+}
+
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+	T: Deserialize<'de>,
+	D: Deserializer<'de>,
+{
+	deserializer.deserialize_tuple(1, TupleVisitor(PhantomData))
+}
+
+struct TupleVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for TupleVisitor<T> {
+	type Value = T;
+
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("a tuple of at least one element")
+	}
+
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<T, A::Error> {
+		seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))
+	}
+}
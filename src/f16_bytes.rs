@@ -0,0 +1,59 @@
+//! Encoding of a half-precision float as two little-endian bytes.
+//!
+//! Use as `#[serde(with = "fcode::f16_bytes")]` on an [`f16`](struct@half::f16) field; this takes 3 bytes on the
+//! wire: the tag byte (with the length) and the content, against 5 for an `f32`. Fields that used to be `f32` or
+//! `f64` can be changed to `f16`; the old values are rounded to the nearest half-precision value when decoded.
+//!
+//! ```
+//! # use serde::{Deserialize, Serialize};
+//! use half::f16;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Weight {
+//!     #[serde(with = "fcode::f16_bytes")]
+//!     value: f16,
+//! }
+//! ```
+
+use crate::de::FLOAT_BYTES_TOKEN;
+use core::convert::TryInto;
+use core::fmt;
+use half::f16;
+use serde::de::{self, Deserializer, Visitor};
+use serde::ser::Serializer;
+
+pub fn serialize<S: Serializer>(value: &f16, serializer: S) -> Result<S::Ok, S::Error> {
+	serializer.serialize_bytes(&value.to_le_bytes())
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f16, D::Error> {
+	deserializer.deserialize_newtype_struct(FLOAT_BYTES_TOKEN, F16Visitor)
+}
+
+struct F16Visitor;
+
+impl<'de> Visitor<'de> for F16Visitor {
+	type Value = f16;
+
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("2 bytes or a float")
+	}
+
+	fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<f16, E> {
+		let bytes: [u8; 2] = v.try_into().map_err(|_| E::invalid_length(v.len(), &self))?;
+		Ok(f16::from_le_bytes(bytes))
+	}
+
+	fn visit_f32<E: de::Error>(self, v: f32) -> Result<f16, E> {
+		Ok(f16::from_f32(v))
+	}
+
+	fn visit_f64<E: de::Error>(self, v: f64) -> Result<f16, E> {
+		Ok(f16::from_f64(v))
+	}
+
+	// for other formats
+	fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<f16, D::Error> {
+		deserializer.deserialize_bytes(self)
+	}
+}
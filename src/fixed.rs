@@ -0,0 +1,108 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// newtype name by which the serializer recognizes the Fixed wrapper; other formats see a plain newtype struct
+pub(crate) const FIXED_TOKEN: &str = "$fcode::Fixed";
+
+/// Wrapper that encodes 32-bit and 64-bit integers as fixed-width values rather than varints.
+///
+/// Varints are great in the general case, but some integers are just always large (IDs, hashes, nanosecond
+/// timestamps), and then a fixed-width encoding is both smaller and faster. It also gives a constant field width, for
+/// fixed-offset record layouts. Integers nested in the wrapped value (e.g. `Fixed<Vec<u64>>`) are encoded as
+/// fixed-width too. Smaller integers, floats and all other types are unaffected.
+///
+/// The deserializer accepts both encodings, of either width, for integers of 32 bits and wider, so wrapping or
+/// unwrapping a field is a compatible change, as is changing its size. Other serde formats see a transparent newtype
+/// struct.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fixed<T>(pub T);
+
+impl<T: Serialize> Serialize for Fixed<T> {
+	#[inline]
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_newtype_struct(FIXED_TOKEN, &self.0)
+	}
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Fixed<T> {
+	#[inline]
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		T::deserialize(deserializer).map(Fixed)
+	}
+}
+
+/// Encode an `i64` timestamp as exactly 8 bytes (plus tag), regardless of its magnitude.
+///
+/// Use as `#[serde(with = "fcode::fixed_timestamp")]`. This is [`Fixed`] for a single field, so that fields keep their
+/// plain type. A field encoded this way always takes 9 bytes on the wire, which makes it suitable for fixed-layout
+/// records, and is smaller than a varint for e.g. nanosecond timestamps. It's the same as [`fixed64`] on an `i64`.
+pub mod fixed_timestamp {
+	use super::Fixed;
+	use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+	pub fn serialize<S: Serializer>(value: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+		Fixed(*value).serialize(serializer)
+	}
+
+	pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+		i64::deserialize(deserializer)
+	}
+}
+
+/// Encode a 32-bit integer field as exactly 4 bytes (plus tag), regardless of its magnitude.
+///
+/// Use as `#[serde(with = "fcode::fixed32")]` on an `i32` or `u32` field. This is [`Fixed`] for a single field, so that
+/// existing structs keep their plain field types. There's no `fixed128`, as the wire format has no 16-byte fixed type;
+/// 128-bit integers are always varints.
+pub mod fixed32 {
+	use super::{sealed::Sealed, Fixed};
+	use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+	/// Integer types that can be encoded as fixed32.
+	pub trait Integer: Serialize + Sealed {}
+
+	impl Integer for i32 {}
+	impl Integer for u32 {}
+
+	pub fn serialize<T: Integer, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+		Fixed(value).serialize(serializer)
+	}
+
+	pub fn deserialize<'de, T: Integer + Deserialize<'de>, D: Deserializer<'de>>(
+		deserializer: D,
+	) -> Result<T, D::Error> {
+		T::deserialize(deserializer)
+	}
+}
+
+/// Encode a 64-bit integer field as exactly 8 bytes (plus tag), regardless of its magnitude.
+///
+/// Use as `#[serde(with = "fcode::fixed64")]` on an `i64` or `u64` field; see [`fixed32`].
+pub mod fixed64 {
+	use super::{sealed::Sealed, Fixed};
+	use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+	/// Integer types that can be encoded as fixed64.
+	pub trait Integer: Serialize + Sealed {}
+
+	impl Integer for i64 {}
+	impl Integer for u64 {}
+
+	pub fn serialize<T: Integer, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+		Fixed(value).serialize(serializer)
+	}
+
+	pub fn deserialize<'de, T: Integer + Deserialize<'de>, D: Deserializer<'de>>(
+		deserializer: D,
+	) -> Result<T, D::Error> {
+		T::deserialize(deserializer)
+	}
+}
+
+mod sealed {
+	pub trait Sealed {}
+
+	impl Sealed for i32 {}
+	impl Sealed for u32 {}
+	impl Sealed for i64 {}
+	impl Sealed for u64 {}
+}
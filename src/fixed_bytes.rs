@@ -0,0 +1,60 @@
+use crate::de::FIXED_BYTES_TOKEN;
+use core::convert::TryInto;
+use core::fmt;
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+/// Wrapper that encodes a byte array as a single bytes value, rather than a sequence.
+///
+/// Serde treats `[u8; N]` as a tuple of `N` integers, which takes a tag byte per element, besides the varint encoding
+/// of bytes above 15. Wrapped, e.g. a 32-byte hash takes 34 bytes: the tag byte, one more for the length (as 32 doesn't
+/// fit in the tag byte), and the content. Decoding fails with [`Error::WrongLength`](crate::Error::WrongLength) if the
+/// length isn't `N`. It also accepts the default array encoding, so wrapping an existing field is a compatible change;
+/// unwrapping it is not. The encoding is interchangeable with that of `Vec<u8>` using `serde_bytes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FixedBytes<const N: usize>(pub [u8; N]);
+
+impl<const N: usize> Serialize for FixedBytes<N> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_bytes(&self.0)
+	}
+}
+
+impl<'de, const N: usize> Deserialize<'de> for FixedBytes<N> {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		deserializer.deserialize_newtype_struct(FIXED_BYTES_TOKEN, FixedBytesVisitor)
+	}
+}
+
+struct FixedBytesVisitor<const N: usize>;
+
+impl<'de, const N: usize> Visitor<'de> for FixedBytesVisitor<N> {
+	type Value = FixedBytes<N>;
+
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} bytes", N)
+	}
+
+	fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+		let bytes = v.try_into().map_err(|_| E::invalid_length(v.len(), &self))?;
+		Ok(FixedBytes(bytes))
+	}
+
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+		let mut bytes = [0u8; N];
+		for (i, b) in bytes.iter_mut().enumerate() {
+			*b = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(i, &self))?;
+		}
+		Ok(FixedBytes(bytes))
+	}
+
+	// fcode passes the content here, to learn the expected length
+	fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+		deserializer.deserialize_tuple(N, self)
+	}
+
+	// for other formats
+	fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+		deserializer.deserialize_bytes(self)
+	}
+}
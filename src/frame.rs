@@ -0,0 +1,263 @@
+use crate::{
+	de::DEFAULT_MAX_DEPTH,
+	wire::{self, WireType},
+	Error, Result,
+};
+use core::convert::TryFrom;
+use serde::de::DeserializeOwned;
+use std::io::{self, Read};
+use std::marker::PhantomData;
+
+/// Iterator over a stream of length-delimited messages, as written by
+/// [`to_writer_length_delimited`](fn@crate::to_writer_length_delimited).
+///
+/// Every frame is read into an internal buffer that is reused between frames, and then decoded into `T`. Iteration
+/// ends at a clean end of stream (i.e. between frames). An error while reading a frame is returned once, after
+/// which iteration stops. As the buffer is reused, `T` can't borrow from it; e.g. `Cow<'static, str>` fields are
+/// decoded as owned strings.
+pub struct FrameReader<R, T> {
+	reader: R,
+	buffer: Vec<u8>,
+	done: bool,
+	_marker: PhantomData<fn() -> T>,
+}
+
+impl<R: Read, T: DeserializeOwned> FrameReader<R, T> {
+	pub fn new(reader: R) -> Self {
+		FrameReader {
+			reader,
+			buffer: Vec::new(),
+			done: false,
+			_marker: PhantomData,
+		}
+	}
+
+	/// Unwrap the underlying reader.
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+
+	// read the length prefix; returns None on a clean end of stream
+	fn read_len(&mut self) -> Result<Option<usize>> {
+		let mut tagbyte = [0u8; 1];
+		loop {
+			match self.reader.read(&mut tagbyte) {
+				Ok(0) => return Ok(None),
+				Ok(_) => break,
+				Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e.into()),
+			}
+		}
+		let tagbyte = tagbyte[0];
+		if wire::read_wiretype(tagbyte) != WireType::Bytes {
+			return Err(Error::UnexpectedWireType);
+		}
+		self.buffer.clear();
+		let value = read_varint_tail(&mut self.reader, tagbyte, &mut self.buffer)?;
+		usize::try_from(value)
+			.map(Some)
+			.map_err(|_| Error::UnexpectedEndOfInput)
+	}
+
+	fn read_frame(&mut self) -> Result<Option<T>> {
+		let len = match self.read_len()? {
+			Some(len) => len,
+			None => return Ok(None),
+		};
+		self.buffer.clear();
+		// read as the data comes in, rather than trusting the length for an allocation
+		let n = self.reader.by_ref().take(len as u64).read_to_end(&mut self.buffer)?;
+		if n < len {
+			return Err(Error::UnexpectedEndOfInput);
+		}
+		crate::from_bytes(&self.buffer).map(Some)
+	}
+}
+
+impl<R: Read, T: DeserializeOwned> Iterator for FrameReader<R, T> {
+	type Item = Result<T>;
+
+	fn next(&mut self) -> Option<Result<T>> {
+		if self.done {
+			return None;
+		}
+		match self.read_frame() {
+			Ok(Some(value)) => Some(Ok(value)),
+			Ok(None) => {
+				self.done = true;
+				None
+			}
+			Err(e) => {
+				self.done = true;
+				Some(Err(e))
+			}
+		}
+	}
+}
+
+/// Iterator over the elements of a sequence in a stream, e.g. a `Vec` written with [`to_writer`](fn@crate::to_writer),
+/// decoding every element as it arrives.
+///
+/// Only one element is held in memory at a time, so that a sequence larger than memory can be processed. The bytes of
+/// every element are read into an internal buffer that is reused between elements, and then decoded into `T`; as
+/// with [`FrameReader`], `T` can't borrow from it. An error is returned once, after which iteration stops.
+pub struct SeqReader<R, T> {
+	reader: R,
+	buffer: Vec<u8>,
+	remaining: usize,
+	done: bool,
+	_marker: PhantomData<fn() -> T>,
+}
+
+impl<R: Read, T: DeserializeOwned> SeqReader<R, T> {
+	/// Read the sequence header from the stream, i.e. the number of elements.
+	pub fn new(mut reader: R) -> Result<Self> {
+		let mut buffer = Vec::new();
+		let tagbyte = read_byte(&mut reader)?;
+		if wire::read_wiretype(tagbyte) != WireType::Sequence {
+			return Err(Error::UnexpectedWireType);
+		}
+		let len = read_varint_tail(&mut reader, tagbyte, &mut buffer)?;
+		Ok(SeqReader {
+			reader,
+			buffer,
+			remaining: usize::try_from(len).map_err(|_| Error::UnexpectedEndOfInput)?,
+			done: false,
+			_marker: PhantomData,
+		})
+	}
+
+	/// Unwrap the underlying reader. If all elements have been read, it's positioned after the sequence.
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+
+	fn read_element(&mut self) -> Result<T> {
+		self.buffer.clear();
+		copy_value(&mut self.reader, &mut self.buffer, DEFAULT_MAX_DEPTH)?;
+		crate::from_bytes(&self.buffer)
+	}
+}
+
+impl<R: Read, T: DeserializeOwned> Iterator for SeqReader<R, T> {
+	type Item = Result<T>;
+
+	fn next(&mut self) -> Option<Result<T>> {
+		if self.done || self.remaining == 0 {
+			return None;
+		}
+		self.remaining -= 1;
+		let result = self.read_element();
+		self.done = result.is_err();
+		Some(result)
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		// an error ends the iteration early
+		(0, Some(if self.done { 0 } else { self.remaining }))
+	}
+}
+
+// copy one complete value from the reader to the buffer, following the same structure as Deserializer::skip
+fn copy_value(reader: &mut impl Read, buf: &mut Vec<u8>, depth: usize) -> Result<()> {
+	let tagbyte = read_byte(reader)?;
+	buf.push(tagbyte);
+	match wire::read_wiretype(tagbyte) {
+		WireType::Int => copy_varint_tail(reader, tagbyte, buf),
+		WireType::Fixed32 => copy_exact(reader, buf, 4),
+		WireType::Fixed64 => copy_exact(reader, buf, 8),
+		WireType::Bytes => copy_bytes_tail(reader, tagbyte, buf),
+		WireType::Sequence => {
+			let len = read_varint_tail(reader, tagbyte, buf)?;
+			let depth = depth.checked_sub(1).ok_or(Error::DepthLimitExceeded)?;
+			for _ in 0..len {
+				copy_value(reader, buf, depth)?;
+			}
+			Ok(())
+		}
+		WireType::Variant => {
+			read_varint_tail(reader, tagbyte, buf)?;
+			let depth = depth.checked_sub(1).ok_or(Error::DepthLimitExceeded)?;
+			copy_value(reader, buf, depth)
+		}
+		// the annotation is part of the value that follows, after the variant name of an enum
+		WireType::Typed => {
+			if wire::check_type_code(read_varint_tail(reader, tagbyte, buf)?)? == wire::TYPE_ENUM {
+				let tagbyte = read_byte(reader)?;
+				buf.push(tagbyte);
+				if wire::read_wiretype(tagbyte) != WireType::Bytes {
+					return Err(Error::UnexpectedWireType);
+				}
+				copy_bytes_tail(reader, tagbyte, buf)?;
+			}
+			let depth = depth.checked_sub(1).ok_or(Error::DepthLimitExceeded)?;
+			copy_value(reader, buf, depth)
+		}
+		WireType::Reserved => Err(Error::UnexpectedWireType),
+	}
+}
+
+// copy the length and content of a bytes value, given its tag byte
+fn copy_bytes_tail(reader: &mut impl Read, tagbyte: u8, buf: &mut Vec<u8>) -> Result<()> {
+	let len = read_varint_tail(reader, tagbyte, buf)?;
+	// read as the data comes in, rather than trusting the length for an allocation
+	let n = reader.by_ref().take(len).read_to_end(buf)?;
+	if (n as u64) < len {
+		return Err(Error::UnexpectedEndOfInput);
+	}
+	Ok(())
+}
+
+// copy the continuation bytes of a varint of up to 128 bits, which is checked when the value is decoded
+fn copy_varint_tail(reader: &mut impl Read, tagbyte: u8, buf: &mut Vec<u8>) -> Result<()> {
+	let mut more = tagbyte & 0x80 != 0;
+	// 4 bits in the tag byte, and 7 in each of at most 18 more bytes
+	let mut count = 0;
+	while more {
+		if count == 18 {
+			return Err(Error::ValueOverflow {
+				value: None,
+				target: "u128",
+			});
+		}
+		let b = read_byte(reader)?;
+		buf.push(b);
+		more = b & 0x80 != 0;
+		count += 1;
+	}
+	Ok(())
+}
+
+// copy the continuation bytes of a varint that holds a length or discriminator, and return its value
+fn read_varint_tail(reader: &mut impl Read, tagbyte: u8, buf: &mut Vec<u8>) -> Result<u64> {
+	let start = buf.len();
+	let mut more = tagbyte & 0x80 != 0;
+	// 10 bytes is enough for any 64-bit varint; let read_varint do the overflow check
+	while more && buf.len() - start < 10 {
+		let b = read_byte(reader)?;
+		buf.push(b);
+		more = b & 0x80 != 0;
+	}
+	let (value, _) = wire::read_varint(tagbyte, &buf[start..])?;
+	Ok(value)
+}
+
+fn copy_exact(reader: &mut impl Read, buf: &mut Vec<u8>, n: usize) -> Result<()> {
+	let start = buf.len();
+	buf.resize(start + n, 0);
+	read_exact(reader, &mut buf[start..])
+}
+
+fn read_byte(reader: &mut impl Read) -> Result<u8> {
+	let mut b = [0u8; 1];
+	read_exact(reader, &mut b)?;
+	Ok(b[0])
+}
+
+// like Read::read_exact, but a short read is reported as our own end-of-input error
+fn read_exact(reader: &mut impl Read, buf: &mut [u8]) -> Result<()> {
+	reader.read_exact(buf).map_err(|e| match e.kind() {
+		io::ErrorKind::UnexpectedEof => Error::UnexpectedEndOfInput,
+		_ => Error::IO(e),
+	})
+}
@@ -0,0 +1,69 @@
+use crate::{from_bytes, to_writer, Error, Result};
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+// CRC-32 (IEEE 802.3, as used by zlib and ethernet); table-driven, as this is small enough to not need a dependency
+const POLY: u32 = 0xedb8_8320;
+
+const TABLE: [u32; 256] = {
+	let mut table = [0u32; 256];
+	let mut i = 0;
+	while i < 256 {
+		let mut c = i as u32;
+		let mut k = 0;
+		while k < 8 {
+			c = if c & 1 != 0 { POLY ^ (c >> 1) } else { c >> 1 };
+			k += 1;
+		}
+		table[i] = c;
+		i += 1;
+	}
+	table
+};
+
+fn crc32(data: &[u8]) -> u32 {
+	let mut crc = !0u32;
+	for &b in data {
+		crc = TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8);
+	}
+	!crc
+}
+
+/// Serialize a value into a new byte vector, followed by a CRC-32 of the serialized value.
+///
+/// The checksum is 4 bytes little-endian, and detects accidental corruption such as bit-rot of data at rest. It's not
+/// a protection against deliberate tampering. Use [`from_bytes_with_crc`] to decode.
+pub fn to_bytes_with_crc<T>(value: &T) -> Result<Vec<u8>>
+where
+	T: Serialize + ?Sized,
+{
+	let mut v = Vec::new();
+	to_writer(&mut v, value)?;
+	let crc = crc32(&v);
+	v.extend_from_slice(&crc.to_le_bytes());
+	Ok(v)
+}
+
+/// Deserialize a value written by [`to_bytes_with_crc`], after verifying the checksum.
+///
+/// Fails with [`Error::ChecksumMismatch`] if the data is corrupt, before attempting to decode it.
+pub fn from_bytes_with_crc<'de, T>(data: &'de [u8]) -> Result<T>
+where
+	T: Deserialize<'de>,
+{
+	if data.len() < 4 {
+		return Err(Error::UnexpectedEndOfInput);
+	}
+	let (body, crc) = data.split_at(data.len() - 4);
+	if crc32(body).to_le_bytes() != crc {
+		return Err(Error::ChecksumMismatch);
+	}
+	from_bytes(body)
+}
+
+#[test]
+fn test_crc32() {
+	// standard check value
+	assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+	assert_eq!(crc32(b""), 0);
+}
@@ -0,0 +1,80 @@
+use crate::de::LENIENT_TOKEN;
+use core::fmt;
+use core::marker::PhantomData;
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+/// Wrapper for an enum that keeps the discriminator of a variant unknown to the receiver.
+///
+/// `#[serde(other)]` maps unknown variants to a unit variant, losing the discriminator. Wrapped in `Lenient`, an
+/// unknown variant decodes as `Unknown` with its discriminator, e.g. for logging; its content is skipped. Known
+/// variants decode as `Known`, including when they contain unknown variants of other enums, which fail as usual.
+///
+/// Serializing `Unknown` writes a unit variant with the discriminator, i.e. the content isn't preserved. Other serde
+/// formats see `T` only.
+///
+/// ```
+/// # use serde::{Deserialize, Serialize};
+/// use fcode::Lenient;
+///
+/// #[derive(Serialize)]
+/// enum New {
+///     A(u32),
+///     B(String),
+///     C { x: u32 },
+/// }
+///
+/// #[derive(Deserialize, Debug, PartialEq)]
+/// enum Old {
+///     A(u32),
+///     B(String),
+/// }
+///
+/// let buf = fcode::to_bytes(&New::C { x: 42 }).unwrap();
+/// assert_eq!(fcode::from_bytes::<Lenient<Old>>(&buf).unwrap(), Lenient::Unknown(2));
+/// let buf = fcode::to_bytes(&New::A(42)).unwrap();
+/// assert_eq!(fcode::from_bytes::<Lenient<Old>>(&buf).unwrap(), Lenient::Known(Old::A(42)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lenient<T> {
+	Known(T),
+	Unknown(u32),
+}
+
+impl<T: Serialize> Serialize for Lenient<T> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		match self {
+			Lenient::Known(v) => v.serialize(serializer),
+			Lenient::Unknown(index) => serializer.serialize_unit_variant("Lenient", *index, "Unknown"),
+		}
+	}
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Lenient<T> {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		deserializer.deserialize_newtype_struct(LENIENT_TOKEN, LenientVisitor(PhantomData))
+	}
+}
+
+struct LenientVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for LenientVisitor<T> {
+	type Value = Lenient<T>;
+
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("an enum")
+	}
+
+	// other formats
+	fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+		T::deserialize(deserializer).map(Lenient::Known)
+	}
+
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+		if let Some(v) = seq.next_element()? {
+			return Ok(Lenient::Known(v));
+		}
+		let index = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+		Ok(Lenient::Unknown(index))
+	}
+}
@@ -8,7 +8,8 @@
 //! * Add a field to the back of a struct. Deserialization of a longer struct is always possible, but
 //!   in order to allow new code to deserialize an old object, added fields must be marked with
 //!   `#[serde(default)]`.
-//! * Extend a tuple struct, in the same way, *except* extending from a 1-field tuple (newtype) to something longer.
+//! * Extend a tuple struct, in the same way, *except* extending from a 1-field tuple (newtype) to something longer
+//!   (see [`extendable`] for an opt-in that allows it).
 //! * Extend a struct enum variant or tuple enum variant in the same way.
 //! * Change an anonymous tuple into a named tuple with the same field types.
 //! * Change a named or anonymous tuple into a struct, as long as fields with the same type appear in the same order.
@@ -25,32 +26,106 @@
 //!   marked with
 //!   `#[serde(other)]`. It is therefore a good idea to always add such other / fallback variant for enums that
 //!   may be extended in the future. The alternative is to always upgrade both sides before actually using the new variant.
+//!   Without a fallback, an unknown variant fails with [`Error::UnknownVariant`]. To keep the discriminator of an
+//!   unknown variant, e.g. for logging, wrap the enum in [`Lenient`].
 //!
 //! Explicitly not supported:
 //!
-//! * Change a newtype struct (`Foo(x)`) to a tuple (`Foo(x,y)`).
-//! * Change the signedness of an integer (`i32` -> `u32`).
-//! * Conditional skipping of fields (will panic), or skipping fields in serialization only (will cause deserialization badness).
+//! * Change a newtype struct (`Foo(x)`) to a tuple (`Foo(x,y)`), unless it was created with [`extendable`].
+//! * Change the signedness of an integer (`i32` -> `u32`), except in steps using the adapters in [`reinterpret`].
+//! * Conditional skipping of fields (will return [`Error::CannotSkipField`]), or skipping fields in serialization only
+//!   (will cause deserialization badness).
 //! * Serialization of sequences with unknown upfront length (e.g. iterators; will panic).
 //!
+//! Structs with `#[serde(flatten)]` fields are encoded differently: as a map of field names to type-annotated values.
+//! Fields of such structs are matched by name, so they may be reordered, and missing fields need `#[serde(default)]`.
+//! Adding or removing `flatten` is not a compatible change.
+//!
 //! Fields can be deprecated by changing them to unit in the receiver first, and then in the sender once all receivers
 //! have been upgraded. Unit deserialisation blindly skips a field without actually checking the wire type. A unit field
 //! takes a single byte on the wire. Vice versa, a field can be "undeprecated" (re-use of deprecated slot) by changing the
 //! sender before the receiver.
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary_value;
+pub mod bitvec;
+mod byte_buf;
+#[cfg(feature = "chrono")]
+pub mod chrono_timestamp;
+pub mod compat;
+#[cfg(feature = "compression")]
+mod compressed;
+pub mod cow_bytes;
 mod de;
+pub mod duration_nanos;
 mod error;
+pub mod extendable;
+#[cfg(feature = "half")]
+pub mod f16_bytes;
+mod fixed;
+mod fixed_bytes;
+#[cfg(feature = "std")]
+mod frame;
+mod integrity;
+mod lenient;
+mod message_buf;
+mod packed;
+pub mod pair128;
+mod probe;
+pub mod reinterpret;
+mod schema;
 mod ser;
-mod wire;
+pub mod sorted_set;
+#[cfg(feature = "std")]
+pub mod system_time_nanos;
+#[cfg(feature = "time")]
+pub mod time_timestamp;
+#[cfg(feature = "uuid")]
+pub mod uuid_bytes;
+pub mod wire;
+mod writer;
 
 #[cfg(test)]
+// some of the tests predate these lints
+#[allow(
+	deprecated,
+	redundant_semicolons,
+	clippy::bool_assert_comparison,
+	clippy::from_over_into,
+	clippy::redundant_closure,
+	clippy::redundant_iter_cloned
+)]
 mod tests;
 
+#[cfg(feature = "arbitrary")]
+pub use arbitrary_value::ArbitraryValue;
+pub use byte_buf::{ByteBuf, Bytes};
+#[cfg(feature = "compression")]
+pub use compressed::{compression_estimate, from_bytes_compressed, to_bytes_compressed};
 pub use de::Deserializer;
 pub use error::{Error, Result};
+pub use fixed::{fixed32, fixed64, fixed_timestamp, Fixed};
+pub use fixed_bytes::FixedBytes;
+#[cfg(feature = "std")]
+pub use frame::{FrameReader, SeqReader};
+pub use integrity::{from_bytes_with_crc, to_bytes_with_crc};
+pub use lenient::Lenient;
+pub use message_buf::{MessageBuf, MessageIter};
+pub use packed::PackedFloats;
+pub use probe::{probe, ProbeReport};
 pub use ser::Serializer;
+#[cfg(feature = "std")]
+pub use writer::LimitedWriter;
+pub use writer::{ObservedWriter, SliceWriter, Write};
 
-use serde::{Deserialize, Serialize};
+use alloc::vec::Vec;
+use core::convert::{TryFrom, TryInto};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use writer::{CountingWriter, Sink};
 
 /// Serialize a value into a new byte vector.
 #[inline]
@@ -63,31 +138,249 @@ where
 	Ok(v)
 }
 
-/// Serialize a value to a [`io::Write`](std::io::Write) implementation.
+/// Serialize a value to a [`Write`] implementation, e.g. any `std::io::Write`.
 ///
-/// Use this to extend a `Vec<u8>`, or feed into some compressor.
+/// Use this to extend a `Vec<u8>`, or feed into some compressor. The writer is not flushed; with the `std` feature,
+/// see `to_writer_flush` for buffered writers.
 #[inline]
 pub fn to_writer<T, W>(w: &mut W, value: &T) -> Result<()>
 where
 	T: Serialize + ?Sized,
-	W: std::io::Write,
+	W: Write + ?Sized,
 {
 	value.serialize(Serializer::new(w))
 }
 
+/// Serialize a value to a [`Write`] implementation, and return the number of bytes written.
+///
+/// This is convenient when appending multiple messages to a single buffer.
+#[inline]
+pub fn to_writer_len<T, W>(w: &mut W, value: &T) -> Result<usize>
+where
+	T: Serialize + ?Sized,
+	W: Write + ?Sized,
+{
+	let mut counter = CountingWriter { inner: w, count: 0 };
+	to_writer(&mut counter, value)?;
+	Ok(counter.count)
+}
+
+/// Serialize a value to an [`io::Write`](std::io::Write) implementation, flush it, and return the number of bytes
+/// written.
+///
+/// Use this with buffered writers such as a `BufWriter<File>`, where data that isn't flushed may otherwise be lost.
+#[cfg(feature = "std")]
+pub fn to_writer_flush<T, W>(w: &mut W, value: &T) -> Result<usize>
+where
+	T: Serialize + ?Sized,
+	W: std::io::Write + ?Sized,
+{
+	let len = to_writer_len(w, value)?;
+	w.flush()?;
+	Ok(len)
+}
+
+/// Serialize a value into a fixed buffer, and return the number of bytes written.
+///
+/// Fails with [`Error::BufferFull`] if the value doesn't fit, in which case the buffer content is unspecified.
+#[inline]
+pub fn to_slice<T>(buf: &mut [u8], value: &T) -> Result<usize>
+where
+	T: Serialize + ?Sized,
+{
+	let mut w = SliceWriter::new(buf);
+	to_writer(&mut w, value)?;
+	Ok(w.len())
+}
+
+/// Calculate the serialized size of a value, without allocating.
+pub fn serialized_size<T>(value: &T) -> Result<usize>
+where
+	T: Serialize + ?Sized,
+{
+	to_writer_len(&mut Sink, value)
+}
+
+/// Serialize a value to a writer, prefixed with its serialized size.
+///
+/// The prefix is encoded as the header of a bytes value, so that the frame as a whole is a valid fcode value. Use
+/// [`read_length_delimited`] to read back a frame, or with the `std` feature, a `FrameReader` for a stream of them.
+pub fn to_writer_length_delimited<T, W>(w: &mut W, value: &T) -> Result<()>
+where
+	T: Serialize + ?Sized,
+	W: Write + ?Sized,
+{
+	let len = serialized_size(value)?;
+	wire::write_varint(w, wire::WireType::Bytes, len as u64)?;
+	to_writer(w, value)
+}
+
+/// Write a variant discriminator, as used for enums.
+///
+/// This is the building block for custom tagged formats: the discriminator should be followed by exactly one value,
+/// so that the whole remains skippable.
+#[inline]
+pub fn write_discriminator<W: Write + ?Sized>(w: &mut W, discriminator: u32) -> Result<()> {
+	wire::write_varint(w, wire::WireType::Variant, discriminator as u64)
+}
+
+/// Read a variant discriminator, as written by [`write_discriminator`].
+///
+/// Returns a pair of (discriminator, size_read).
+#[inline]
+pub fn read_discriminator(data: &[u8]) -> Result<(u32, usize)> {
+	let (&tagbyte, data) = data.split_first().ok_or(Error::UnexpectedEndOfInput)?;
+	if wire::read_wiretype(tagbyte) != wire::WireType::Variant {
+		return Err(Error::UnexpectedWireType);
+	}
+	let (value, len) = wire::read_varint(tagbyte, data)?;
+	let value = value.try_into().map_err(|_| Error::overflow::<u32>(value))?;
+	Ok((value, len + 1))
+}
+
+/// Write a blob as a bytes value, i.e. prefixed with its length.
+///
+/// This is the same encoding as that of a `&[u8]` with `serde_bytes`, so the receiver may also decode it as a field.
+/// Use this to embed a pre-serialized message in another, so that it can be skipped or extracted without decoding.
+#[inline]
+pub fn write_length_delimited<W: Write + ?Sized>(w: &mut W, data: &[u8]) -> Result<()> {
+	wire::write_varint(w, wire::WireType::Bytes, data.len() as u64)?;
+	w.write_all(data)
+}
+
+/// Read a blob written by [`write_length_delimited`], without copying.
+///
+/// Returns a pair of (content, size_read).
+#[inline]
+pub fn read_length_delimited(data: &[u8]) -> Result<(&[u8], usize)> {
+	let (&tagbyte, rest) = data.split_first().ok_or(Error::UnexpectedEndOfInput)?;
+	if wire::read_wiretype(tagbyte) != wire::WireType::Bytes {
+		return Err(Error::UnexpectedWireType);
+	}
+	let (len, varint_len) = wire::read_varint(tagbyte, rest)?;
+	let content = usize::try_from(len)
+		.ok()
+		.and_then(|len| rest[varint_len..].get(..len))
+		.ok_or(Error::UnexpectedEndOfInput)?;
+	Ok((content, 1 + varint_len + content.len()))
+}
+
+/// Serialize a value into a new byte vector, prefixed with a fingerprint of its type's structure.
+///
+/// The fingerprint is a fixed32 value, derived from kinds of primitives, names of structs and enums, field names and
+/// tuple lengths. Use [`from_bytes_checked`] to decode, which fails with [`Error::SchemaMismatch`] when the type
+/// doesn't match. Note that this is strict: compatible evolutions, such as adding a field, change the fingerprint.
+pub fn to_bytes_with_schema_hash<T>(value: &T) -> Result<Vec<u8>>
+where
+	T: Serialize + ?Sized,
+{
+	let mut v = to_bytes(&Fixed(schema::schema_hash(value)?))?;
+	to_writer(&mut v, value)?;
+	Ok(v)
+}
+
+/// Deserialize a value written by [`to_bytes_with_schema_hash`], and verify that it has the same type structure.
+///
+/// The fingerprint is computed from the decoded value, so data that can't be decoded at all gives a decoding error
+/// rather than [`Error::SchemaMismatch`].
+pub fn from_bytes_checked<'de, T>(data: &'de [u8]) -> Result<T>
+where
+	T: Serialize + Deserialize<'de>,
+{
+	let (Fixed(expected), len) = from_bytes_more_data::<Fixed<u32>>(data)?;
+	let value: T = from_bytes(&data[len..])?;
+	if schema::schema_hash(&value)? != expected {
+		return Err(Error::SchemaMismatch);
+	}
+	Ok(value)
+}
+
 /// Deserialize a value from a byte slice.
+///
+/// Strings and bytes can borrow from `data`: `&str` and `&[u8]` fields, but also `Cow<str>` fields marked with
+/// `#[serde(borrow)]`, which are only copied when decoding from a source that can't be borrowed from.
 pub fn from_bytes<'de, T>(data: &'de [u8]) -> Result<T>
 where
 	T: Deserialize<'de>,
+{
+	from_bytes_seed(data, core::marker::PhantomData::<T>)
+}
+
+/// Deserialize a value from a byte slice, using a [`DeserializeSeed`](serde::de::DeserializeSeed).
+///
+/// This is for stateful deserialization, e.g. interning strings into a shared table. Like
+/// [`from_bytes`], this fails if there is data after the value.
+pub fn from_bytes_seed<'de, S>(data: &'de [u8], seed: S) -> Result<S::Value>
+where
+	S: serde::de::DeserializeSeed<'de>,
 {
 	let mut de = Deserializer::from_bytes(data);
-	let value = T::deserialize(&mut de)?;
+	let value = seed.deserialize(&mut de)?;
 	if de.remaining_len() > 0 {
 		return Err(Error::DataBeyondEnd);
 	}
 	Ok(value)
 }
 
+/// Deserialize a value from a byte slice into an existing value.
+///
+/// This uses serde's in-place deserialization, which allows e.g. `Vec` and `String` allocations to be reused. Derived
+/// structs only implement this when `serde_derive`'s `deserialize_in_place` feature is enabled; otherwise the target
+/// is simply replaced. On error, the target may be partially updated.
+pub fn merge_from_bytes<'de, T>(target: &mut T, data: &'de [u8]) -> Result<()>
+where
+	T: Deserialize<'de>,
+{
+	let mut de = Deserializer::from_bytes(data);
+	T::deserialize_in_place(&mut de, target)?;
+	if de.remaining_len() > 0 {
+		return Err(Error::DataBeyondEnd);
+	}
+	Ok(())
+}
+
+/// Check whether a message is in canonical form, i.e. whether it is exactly what serializing its decoded value
+/// produces.
+///
+/// A message can be non-canonical because of padded varints or repeated map keys, but also because e.g. a map was
+/// encoded in an order that differs from `T`'s. Maps are compared in the order that `T` iterates them, so for a
+/// `HashMap` of more than one entry the answer can change between runs; use an ordered map where this matters.
+/// Decoding errors are returned as such.
+pub fn is_canonical<T>(data: &[u8]) -> Result<bool>
+where
+	T: Serialize + DeserializeOwned,
+{
+	// these are caught while decoding, as the decoded value doesn't show them
+	let mut de = Deserializer::from_bytes(data).canonical(true).strict_maps(true);
+	let value = match T::deserialize(&mut de) {
+		Err(Error::NonCanonicalVarint) | Err(Error::DuplicateKey) => return Ok(false),
+		result => result?,
+	};
+	if de.remaining_len() > 0 {
+		return Err(Error::DataBeyondEnd);
+	}
+	Ok(to_bytes(&value)? == data)
+}
+
+/// Transcode fcode data into another serde format, e.g. JSON for debugging.
+///
+/// As fcode data doesn't describe its types, values are represented by their wire type: integers as unsigned (so
+/// signed integers show in their zigzag encoding, e.g. -1 as 1), fixed-width values as floats, byte strings as
+/// strings if they're valid UTF-8, structs and tuples as sequences, and enums as a map of discriminator to content
+/// (e.g. `Some(x)` as `{1: x}`, and `None` as `{0: 0}`, as units are written as zero). Values inside flattened
+/// structs carry their type, and are transcoded exactly; as maps are sequences of alternating keys and values on the
+/// wire, a flattened struct shows as e.g. `["id", 7, "name", "bar"]`.
+#[cfg(feature = "transcode")]
+pub fn transcode_to<S: serde::Serializer>(data: &[u8], serializer: S) -> core::result::Result<S::Ok, S::Error> {
+	use serde::ser::Error as _;
+	let mut de = Deserializer::from_bytes(data).structural();
+	let result = serde_transcode::transcode(&mut de, serializer)?;
+	if de.remaining_len() > 0 {
+		return Err(S::Error::custom(Error::DataBeyondEnd));
+	}
+	Ok(result)
+}
+
 /// Deserialize a value from a byte slice that may have more data.
 ///
 /// Returns a pair of (value, size_read).
@@ -100,3 +393,14 @@ where
 	let consumed = data.len() - de.remaining_len();
 	Ok((value, consumed))
 }
+
+/// Deserialize a value from the start of a byte slice, and return it with the remaining data.
+///
+/// This is [`from_bytes_more_data`] with a different return shape, for reading consecutive values from a buffer.
+pub fn from_prefix<'de, T>(data: &'de [u8]) -> Result<(T, &'de [u8])>
+where
+	T: Deserialize<'de>,
+{
+	let (value, len) = from_bytes_more_data(data)?;
+	Ok((value, &data[len..]))
+}
@@ -0,0 +1,108 @@
+use crate::{from_bytes, read_length_delimited, to_writer_length_delimited, Result};
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use serde::{Deserialize, Serialize};
+
+/// A batch of messages in a single buffer, each length-delimited as by
+/// [`to_writer_length_delimited`](fn@crate::to_writer_length_delimited).
+///
+/// The buffer can be stored or sent as a whole, and read back with [`MessageIter`], or message by message with
+/// [`read_length_delimited`](crate::read_length_delimited); with the `std` feature, also with a `FrameReader` from a
+/// stream.
+///
+/// ```
+/// let mut batch = fcode::MessageBuf::new();
+/// batch.push(&("foo", 1u32)).unwrap();
+/// batch.push(&("bar", 2u32)).unwrap();
+/// let messages: Vec<(&str, u32)> = batch.iter().collect::<Result<_, _>>().unwrap();
+/// assert_eq!(messages, [("foo", 1), ("bar", 2)]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageBuf {
+	buf: Vec<u8>,
+	len: usize,
+}
+
+impl MessageBuf {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Serialize a message and append it. On error, the buffer is left as it was.
+	pub fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+		let start = self.buf.len();
+		if let Err(e) = to_writer_length_delimited(&mut self.buf, value) {
+			self.buf.truncate(start);
+			return Err(e);
+		}
+		self.len += 1;
+		Ok(())
+	}
+
+	/// The number of messages pushed.
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	pub fn clear(&mut self) {
+		self.buf.clear();
+		self.len = 0;
+	}
+
+	pub fn as_bytes(&self) -> &[u8] {
+		&self.buf
+	}
+
+	pub fn into_bytes(self) -> Vec<u8> {
+		self.buf
+	}
+
+	/// Iterate over the messages in order, decoding each as `T`. Messages can borrow from the buffer.
+	pub fn iter<'a, T: Deserialize<'a>>(&'a self) -> MessageIter<'a, T> {
+		MessageIter::new(&self.buf)
+	}
+}
+
+/// Iterator over the messages in a buffer of length-delimited messages, as built by [`MessageBuf`].
+///
+/// Each message is decoded as `T`, which can borrow from the buffer. An error is returned once, after which iteration
+/// stops.
+pub struct MessageIter<'a, T> {
+	data: &'a [u8],
+	_marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, T: Deserialize<'a>> MessageIter<'a, T> {
+	pub fn new(data: &'a [u8]) -> Self {
+		MessageIter {
+			data,
+			_marker: PhantomData,
+		}
+	}
+
+	fn read_next(&mut self) -> Result<T> {
+		let (content, len) = read_length_delimited(self.data)?;
+		let value = from_bytes(content)?;
+		self.data = &self.data[len..];
+		Ok(value)
+	}
+}
+
+impl<'a, T: Deserialize<'a>> Iterator for MessageIter<'a, T> {
+	type Item = Result<T>;
+
+	fn next(&mut self) -> Option<Result<T>> {
+		if self.data.is_empty() {
+			return None;
+		}
+		let result = self.read_next();
+		if result.is_err() {
+			self.data = &[];
+		}
+		Some(result)
+	}
+}
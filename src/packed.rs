@@ -0,0 +1,80 @@
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::fmt;
+use core::marker::PhantomData;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+/// Wrapper that encodes a vector of `f32` or `f64` as a single bytes value, rather than a sequence.
+///
+/// In a sequence, every float takes a tag byte besides its data, i.e. 25% overhead for `f32`. Packed, the floats are
+/// stored back to back, little-endian, and the count follows from the length of the bytes value. This is not
+/// interchangeable with the default `Vec` encoding. Other serde formats see a byte array.
+#[derive(Debug, Default, Clone, PartialEq, PartialOrd)]
+pub struct PackedFloats<T>(pub Vec<T>);
+
+// implemented for f32 and f64
+pub(crate) trait Float: Copy {
+	const SIZE: usize;
+	fn extend_le(self, buf: &mut Vec<u8>);
+	fn from_le(b: &[u8]) -> Self;
+}
+
+impl Float for f32 {
+	const SIZE: usize = 4;
+	#[inline]
+	fn extend_le(self, buf: &mut Vec<u8>) {
+		buf.extend_from_slice(&self.to_le_bytes());
+	}
+	#[inline]
+	fn from_le(b: &[u8]) -> Self {
+		f32::from_le_bytes(b.try_into().unwrap())
+	}
+}
+
+impl Float for f64 {
+	const SIZE: usize = 8;
+	#[inline]
+	fn extend_le(self, buf: &mut Vec<u8>) {
+		buf.extend_from_slice(&self.to_le_bytes());
+	}
+	#[inline]
+	fn from_le(b: &[u8]) -> Self {
+		f64::from_le_bytes(b.try_into().unwrap())
+	}
+}
+
+impl<T: Float> Serialize for PackedFloats<T> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let mut buf = Vec::with_capacity(self.0.len() * T::SIZE);
+		for &v in &self.0 {
+			v.extend_le(&mut buf);
+		}
+		serializer.serialize_bytes(&buf)
+	}
+}
+
+impl<'de, T: Float> Deserialize<'de> for PackedFloats<T> {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		deserializer.deserialize_bytes(PackedVisitor(PhantomData))
+	}
+}
+
+struct PackedVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Float> Visitor<'de> for PackedVisitor<T> {
+	type Value = PackedFloats<T>;
+
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "packed floats of {} bytes", T::SIZE)
+	}
+
+	// is_multiple_of would need Rust 1.87
+	#[allow(clippy::manual_is_multiple_of)]
+	fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+		if v.len() % T::SIZE != 0 {
+			return Err(E::invalid_length(v.len(), &self));
+		}
+		Ok(PackedFloats(v.chunks_exact(T::SIZE).map(T::from_le).collect()))
+	}
+}
@@ -0,0 +1,65 @@
+//! Encoding of a 128-bit integer as a pair of 64-bit integers.
+//!
+//! Fcode encodes `i128` and `u128` natively, as a single varint, and serde always supports them. Other consumers of the
+//! same data may not: e.g. a decoder in a language without 128-bit integers, or a serde format that rejects them. Use
+//! `#[serde(with = "fcode::pair128")]` on such a field to encode it as a tuple of the high and the low 64 bits
+//! instead:
+//!
+//! ```
+//! # use serde::{Serialize, Deserialize};
+//! #[derive(Serialize, Deserialize)]
+//! struct Account {
+//!     #[serde(with = "fcode::pair128")]
+//!     balance: i128,
+//! }
+//! ```
+//!
+//! For an `i128`, the high half is an `i64` and carries the sign; the low half is always a `u64`. The pair is not
+//! interchangeable with the native encoding, so adding the attribute to an existing field is not a compatible change.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// 128-bit integer types that can be split into a pair.
+pub trait Integer: Sized + sealed::Sealed {}
+
+impl Integer for u128 {}
+impl Integer for i128 {}
+
+pub fn serialize<T: Integer, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+	value.serialize_pair(serializer)
+}
+
+pub fn deserialize<'de, T: Integer, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+	T::deserialize_pair(deserializer)
+}
+
+mod sealed {
+	use super::*;
+
+	pub trait Sealed: Sized {
+		fn serialize_pair<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>;
+		fn deserialize_pair<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>;
+	}
+
+	impl Sealed for u128 {
+		fn serialize_pair<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+			((*self >> 64) as u64, *self as u64).serialize(serializer)
+		}
+
+		fn deserialize_pair<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+			let (high, low) = <(u64, u64)>::deserialize(deserializer)?;
+			Ok((high as u128) << 64 | low as u128)
+		}
+	}
+
+	impl Sealed for i128 {
+		fn serialize_pair<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+			((*self >> 64) as i64, *self as u64).serialize(serializer)
+		}
+
+		fn deserialize_pair<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+			let (high, low) = <(i64, u64)>::deserialize(deserializer)?;
+			Ok((high as i128) << 64 | low as i128)
+		}
+	}
+}
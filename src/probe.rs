@@ -0,0 +1,70 @@
+use crate::{
+	wire::{self, WireType},
+	Deserializer, Error, Result,
+};
+use alloc::vec::Vec;
+
+/// The top-level shape of a message, as found by [`probe`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeReport {
+	/// The wire type of the message; [`WireType::Sequence`] for structs and tuples.
+	pub wire_type: WireType,
+	/// The wire types of the items, if the message is a sequence; empty otherwise.
+	pub fields: Vec<WireType>,
+	/// The encoded length of the message, from tag byte to end.
+	pub len: usize,
+}
+
+/// Walk the wire structure of a message, without decoding it into a type.
+///
+/// This checks that the message is complete and well-formed, and reports its wire type, its length, and for a
+/// sequence the wire types of the items. Items of flattened structs are reported by their own wire type, rather than
+/// [`WireType::Typed`] for the annotation in front of them; enums among them as [`WireType::Variant`], same as
+/// elsewhere. Data beyond the message is ignored.
+///
+/// This helps to route or validate messages whose type isn't available, or to detect that a message was written by
+/// a different version of the sender: e.g. a struct that gained fields shows more items. `T` is the type that the
+/// message is expected to be; nothing of it is constructed, and the walk doesn't depend on it.
+pub fn probe<T: ?Sized>(data: &[u8]) -> Result<ProbeReport> {
+	let mut de = Deserializer::from_bytes(data);
+	let raw = de.read_raw_value()?;
+	let (wire_type, header_len) = header(raw)?;
+	let mut fields = Vec::new();
+	if wire_type == WireType::Sequence {
+		let items = &raw[header_len..];
+		let annotated = items.first().map(|&b| wire::read_wiretype(b)) == Some(WireType::Typed);
+		let mut de = Deserializer::from_bytes(items).annotated(annotated);
+		while de.remaining_len() > 0 {
+			let item = de.read_raw_value()?;
+			fields.push(header(item)?.0);
+		}
+	}
+	Ok(ProbeReport {
+		wire_type,
+		fields,
+		len: raw.len(),
+	})
+}
+
+// the wire type of a complete value, past any type annotation, and the length of the header in front of the content;
+// an annotated enum is a variant, with the name as header
+fn header(raw: &[u8]) -> Result<(WireType, usize)> {
+	let mut pos = 0;
+	loop {
+		let &tagbyte = raw.get(pos).ok_or(Error::UnexpectedEndOfInput)?;
+		let rest = &raw[pos + 1..];
+		match wire::read_wiretype(tagbyte) {
+			WireType::Typed => {
+				let (code, len) = wire::read_varint(tagbyte, rest)?;
+				pos += 1 + len;
+				if code == wire::TYPE_ENUM {
+					// the variant name, followed by the content without a variant tag
+					let &tagbyte = raw.get(pos).ok_or(Error::UnexpectedEndOfInput)?;
+					let (name_len, len) = wire::read_varint(tagbyte, &raw[pos + 1..])?;
+					return Ok((WireType::Variant, pos + 1 + len + name_len as usize));
+				}
+			}
+			wt => return Ok((wt, pos + 1 + wire::skip_varint(tagbyte, rest)?)),
+		}
+	}
+}
@@ -0,0 +1,70 @@
+//! Adapters for changing the signedness of an integer field.
+//!
+//! Signed integers are zigzag-encoded, unsigned integers are not, so a field can't simply change from `u32` to `i32`
+//! or back. These adapters encode an integer with the other signedness, so that the change can be made in steps:
+//!
+//! 1. Mark the field with `#[serde(with = "fcode::reinterpret::as_signed")]` (for an unsigned field that should become
+//!    signed) on the sending side. It's still an unsigned integer in the code, but is encoded as a signed one.
+//! 2. Once all senders are upgraded, change the field type to signed on the receiving side.
+//!
+//! The adapter also decodes the new encoding, so receivers can use it as well, and then change the type along with the
+//! senders. Values that don't fit the target type fail to decode with
+//! [`Error::ValueOverflow`](crate::Error::ValueOverflow) for plain integer fields, e.g. a `u32` above `i32::MAX` read
+//! as `i32`. When decoding through the adapter, such values fail with
+//! [`Error::InvalidValue`](crate::Error::InvalidValue), e.g. a negative value read through `as_signed` into a `u32`.
+//! Encoding a negative value through `as_unsigned` fails.
+
+use core::convert::TryFrom;
+use core::fmt::Display;
+use serde::{de, ser, Deserialize, Deserializer, Serializer};
+
+/// Encode an unsigned (or any) integer as a signed one. Use as `#[serde(with = "fcode::reinterpret::as_signed")]`.
+pub mod as_signed {
+	use super::*;
+
+	pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		T: Copy + Into<i128>,
+		S: Serializer,
+	{
+		serializer.serialize_i128((*value).into())
+	}
+
+	pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+	where
+		T: TryFrom<i128>,
+		D: Deserializer<'de>,
+	{
+		let v = i128::deserialize(deserializer)?;
+		T::try_from(v).map_err(|_| {
+			de::Error::invalid_value(de::Unexpected::Other("out of range integer"), &"an integer in range")
+		})
+	}
+}
+
+/// Encode a signed (or any) integer as an unsigned one. Use as `#[serde(with = "fcode::reinterpret::as_unsigned")]`.
+pub mod as_unsigned {
+	use super::*;
+
+	pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		T: Copy + Display,
+		u128: TryFrom<T>,
+		S: Serializer,
+	{
+		let v = u128::try_from(*value)
+			.map_err(|_| ser::Error::custom(format_args!("negative value {} can't be encoded as unsigned", value)))?;
+		serializer.serialize_u128(v)
+	}
+
+	pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+	where
+		T: TryFrom<u128>,
+		D: Deserializer<'de>,
+	{
+		let v = u128::deserialize(deserializer)?;
+		T::try_from(v).map_err(|_| {
+			de::Error::invalid_value(de::Unexpected::Other("out of range integer"), &"an integer in range")
+		})
+	}
+}
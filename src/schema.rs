@@ -0,0 +1,312 @@
+use crate::{Error, Result};
+use alloc::string::ToString;
+use serde::{ser, Serialize};
+
+// FNV-1a; simple, and unlike std's hashers guaranteed to be stable across releases
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Compute a fingerprint of the structure of a value's type.
+///
+/// The value is walked like a serializer would, but only things that are the same for every value of the type are
+/// recorded: kinds of primitives, names of structs and enums, field names, and tuple lengths. Contents of options,
+/// enums, sequences and maps are not visited, as they depend on the value.
+pub(crate) fn schema_hash<T: ?Sized + Serialize>(value: &T) -> Result<u32> {
+	let mut h = SchemaHasher { hash: FNV_OFFSET };
+	value.serialize(&mut h)?;
+	Ok((h.hash >> 32) as u32 ^ h.hash as u32)
+}
+
+struct SchemaHasher {
+	hash: u64,
+}
+
+impl SchemaHasher {
+	#[inline]
+	fn add(&mut self, s: &str) -> Result<()> {
+		// terminate, so that ("ab", "c") and ("a", "bc") differ
+		for &b in s.as_bytes().iter().chain(&[0]) {
+			self.hash ^= b as u64;
+			self.hash = self.hash.wrapping_mul(FNV_PRIME);
+		}
+		Ok(())
+	}
+
+	#[inline]
+	fn compound(&mut self, recurse: bool) -> Compound<'_> {
+		Compound { h: self, recurse }
+	}
+}
+
+// sequence-like state; elements are only visited for structs and tuples
+struct Compound<'a> {
+	h: &'a mut SchemaHasher,
+	recurse: bool,
+}
+
+impl Compound<'_> {
+	#[inline]
+	fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+		if self.recurse {
+			value.serialize(&mut *self.h)?;
+		}
+		Ok(())
+	}
+
+	#[inline]
+	fn end(self) -> Result<()> {
+		if self.recurse {
+			self.h.add(")")?;
+		}
+		Ok(())
+	}
+}
+
+impl<'a> ser::Serializer for &'a mut SchemaHasher {
+	type Ok = ();
+	type Error = Error;
+	type SerializeSeq = Compound<'a>;
+	type SerializeMap = Compound<'a>;
+	type SerializeTuple = Compound<'a>;
+	type SerializeTupleStruct = Compound<'a>;
+	type SerializeTupleVariant = Compound<'a>;
+	type SerializeStruct = Compound<'a>;
+	type SerializeStructVariant = Compound<'a>;
+
+	fn serialize_bool(self, _v: bool) -> Result<()> {
+		self.add("bool")
+	}
+
+	fn serialize_i8(self, _v: i8) -> Result<()> {
+		self.add("int")
+	}
+
+	fn serialize_i16(self, _v: i16) -> Result<()> {
+		self.add("int")
+	}
+
+	fn serialize_i32(self, _v: i32) -> Result<()> {
+		self.add("int")
+	}
+
+	fn serialize_i64(self, _v: i64) -> Result<()> {
+		self.add("int")
+	}
+
+	fn serialize_i128(self, _v: i128) -> Result<()> {
+		self.add("int")
+	}
+
+	fn serialize_u8(self, _v: u8) -> Result<()> {
+		self.add("uint")
+	}
+
+	fn serialize_u16(self, _v: u16) -> Result<()> {
+		self.add("uint")
+	}
+
+	fn serialize_u32(self, _v: u32) -> Result<()> {
+		self.add("uint")
+	}
+
+	fn serialize_u64(self, _v: u64) -> Result<()> {
+		self.add("uint")
+	}
+
+	fn serialize_u128(self, _v: u128) -> Result<()> {
+		self.add("uint")
+	}
+
+	fn serialize_f32(self, _v: f32) -> Result<()> {
+		self.add("float")
+	}
+
+	fn serialize_f64(self, _v: f64) -> Result<()> {
+		self.add("float")
+	}
+
+	fn serialize_char(self, _v: char) -> Result<()> {
+		self.add("char")
+	}
+
+	fn serialize_str(self, _v: &str) -> Result<()> {
+		self.add("bytes")
+	}
+
+	fn serialize_bytes(self, _v: &[u8]) -> Result<()> {
+		self.add("bytes")
+	}
+
+	fn serialize_none(self) -> Result<()> {
+		self.add("option")
+	}
+
+	fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<()> {
+		self.add("option")
+	}
+
+	fn serialize_unit(self) -> Result<()> {
+		self.add("unit")
+	}
+
+	fn serialize_unit_struct(self, name: &'static str) -> Result<()> {
+		self.add(name)
+	}
+
+	fn serialize_unit_variant(self, name: &'static str, _variant_index: u32, _variant: &'static str) -> Result<()> {
+		self.add(name)
+	}
+
+	fn serialize_newtype_struct<T: ?Sized + Serialize>(self, name: &'static str, value: &T) -> Result<()> {
+		self.add(name)?;
+		value.serialize(self)
+	}
+
+	fn serialize_newtype_variant<T: ?Sized + Serialize>(
+		self,
+		name: &'static str,
+		_variant_index: u32,
+		_variant: &'static str,
+		_value: &T,
+	) -> Result<()> {
+		self.add(name)
+	}
+
+	fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+		self.add("seq")?;
+		Ok(self.compound(false))
+	}
+
+	fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+		self.add("(")?;
+		self.add(&len.to_string())?;
+		Ok(self.compound(true))
+	}
+
+	fn serialize_tuple_struct(self, name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct> {
+		self.add(name)?;
+		self.add("(")?;
+		Ok(self.compound(true))
+	}
+
+	fn serialize_tuple_variant(
+		self,
+		name: &'static str,
+		_variant_index: u32,
+		_variant: &'static str,
+		_len: usize,
+	) -> Result<Self::SerializeTupleVariant> {
+		self.add(name)?;
+		Ok(self.compound(false))
+	}
+
+	fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+		self.add("map")?;
+		Ok(self.compound(false))
+	}
+
+	fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+		self.add(name)?;
+		self.add("(")?;
+		Ok(self.compound(true))
+	}
+
+	fn serialize_struct_variant(
+		self,
+		name: &'static str,
+		_variant_index: u32,
+		_variant: &'static str,
+		_len: usize,
+	) -> Result<Self::SerializeStructVariant> {
+		self.add(name)?;
+		Ok(self.compound(false))
+	}
+
+	fn is_human_readable(&self) -> bool {
+		false
+	}
+}
+
+impl ser::SerializeSeq for Compound<'_> {
+	type Ok = ();
+	type Error = Error;
+	fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+		self.element(value)
+	}
+	fn end(self) -> Result<()> {
+		Compound::end(self)
+	}
+}
+
+impl ser::SerializeTuple for Compound<'_> {
+	type Ok = ();
+	type Error = Error;
+	fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+		self.element(value)
+	}
+	fn end(self) -> Result<()> {
+		Compound::end(self)
+	}
+}
+
+impl ser::SerializeTupleStruct for Compound<'_> {
+	type Ok = ();
+	type Error = Error;
+	fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+		self.element(value)
+	}
+	fn end(self) -> Result<()> {
+		Compound::end(self)
+	}
+}
+
+impl ser::SerializeTupleVariant for Compound<'_> {
+	type Ok = ();
+	type Error = Error;
+	fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+		self.element(value)
+	}
+	fn end(self) -> Result<()> {
+		Compound::end(self)
+	}
+}
+
+impl ser::SerializeMap for Compound<'_> {
+	type Ok = ();
+	type Error = Error;
+	fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<()> {
+		self.element(key)
+	}
+	fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+		self.element(value)
+	}
+	fn end(self) -> Result<()> {
+		Compound::end(self)
+	}
+}
+
+impl ser::SerializeStruct for Compound<'_> {
+	type Ok = ();
+	type Error = Error;
+	fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<()> {
+		self.h.add(key)?;
+		self.element(value)
+	}
+	fn skip_field(&mut self, _key: &'static str) -> Result<()> {
+		Err(Error::CannotSkipField)
+	}
+	fn end(self) -> Result<()> {
+		Compound::end(self)
+	}
+}
+
+impl ser::SerializeStructVariant for Compound<'_> {
+	type Ok = ();
+	type Error = Error;
+	fn serialize_field<T: ?Sized + Serialize>(&mut self, _key: &'static str, value: &T) -> Result<()> {
+		self.element(value)
+	}
+	fn end(self) -> Result<()> {
+		Compound::end(self)
+	}
+}
@@ -1,136 +1,383 @@
 use crate::{
 	wire::{self, WireType},
-	Error, Result,
+	writer::Internal,
+	Error, Result, Write,
 };
+use alloc::vec::Vec;
 use serde::{ser, Serialize};
-use std::io::Write;
 
-pub struct Serializer<'a, W: Write + 'a> {
+pub struct Serializer<'a, W: Write + ?Sized + 'a> {
 	writer: &'a mut W,
+	big_endian: bool,
+	strict_floats: bool,
+	// write 32/64-bit integers as fixed-width; set by the Fixed wrapper
+	fixed: bool,
+	// write type annotations; set inside maps of unknown length
+	annotate: bool,
+	trim_defaults: bool,
 }
 
-impl<'a, W: Write + 'a> Serializer<'a, W> {
+impl<'a, W: Write + ?Sized + 'a> Serializer<'a, W> {
 	pub fn new(writer: &'a mut W) -> Self {
-		Serializer { writer }
+		Serializer {
+			writer,
+			big_endian: false,
+			strict_floats: false,
+			fixed: false,
+			annotate: false,
+			trim_defaults: false,
+		}
+	}
+
+	/// Write fixed-width values (`f32`, `f64`, and [`Fixed`](crate::Fixed) integers) big-endian rather than
+	/// little-endian. Varints are not affected.
+	///
+	/// This is not the standard fcode format; it's meant for interoperating with existing data. The deserializer must
+	/// be configured the same way.
+	#[inline]
+	pub fn big_endian(mut self, big_endian: bool) -> Self {
+		self.big_endian = big_endian;
+		self
+	}
+
+	/// Fail with [`Error::NonFiniteFloat`] when serializing a NaN or infinite float.
+	///
+	/// This is useful when data is passed on to systems that can't represent such values.
+	#[inline]
+	pub fn strict_floats(mut self, strict_floats: bool) -> Self {
+		self.strict_floats = strict_floats;
+		self
+	}
+
+	/// Leave out trailing struct fields whose encoding is that of a typical default: zero, false, an empty string or
+	/// sequence, `None` (or any enum's first unit variant), or a struct of such fields.
+	///
+	/// Fields are matched by their encoding, not compared to their type's actual default, and the receiver fills in
+	/// the missing fields with its own defaults. So this is only correct if every trailing field that may be left out
+	/// has `#[serde(default)]` on the receiving side, with a default that encodes as one of the above. A field with
+	/// e.g. `#[serde(default = "five")]`, or an enum whose `Default` isn't its first variant, silently decodes as a
+	/// different value; a field without `#[serde(default)]` fails to decode. It saves space for structs that have
+	/// grown optional fields over time. Struct fields are buffered to find the trailing defaults, which makes
+	/// serialization slower. Fields of flattened structs are not affected.
+	#[inline]
+	pub fn trim_defaults(mut self, trim_defaults: bool) -> Self {
+		self.trim_defaults = trim_defaults;
+		self
+	}
+
+	/// Serialize a value, keeping the serializer for the next one.
+	///
+	/// The serializer holds nothing but the writer and its settings, so constructing one per message (as
+	/// [`to_writer`](crate::to_writer) does) costs nothing; this is merely convenient when writing many messages with
+	/// the same settings. Use [`get_mut`](Self::get_mut) to reach the writer in between, e.g. to clear a buffer.
+	///
+	/// ```
+	/// # fn main() -> fcode::Result<()> {
+	/// let mut buf = Vec::new();
+	/// let mut ser = fcode::Serializer::new(&mut buf).strict_floats(true);
+	/// for x in [1.5f32, 2.5] {
+	///     ser.get_mut().clear();
+	///     ser.serialize_value(&x)?;
+	///     assert_eq!(ser.get_mut().len(), 5);
+	/// }
+	/// # Ok(())
+	/// # }
+	/// ```
+	#[inline]
+	pub fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+		value.serialize(self.reborrow())
+	}
+
+	/// Access the underlying writer.
+	#[inline]
+	pub fn get_mut(&mut self) -> &mut W {
+		self.writer
+	}
+
+	// a serializer for a nested value, with the same settings
+	#[inline]
+	fn reborrow(&mut self) -> Serializer<'_, W> {
+		Serializer {
+			writer: self.writer,
+			big_endian: self.big_endian,
+			strict_floats: self.strict_floats,
+			fixed: self.fixed,
+			annotate: self.annotate,
+			trim_defaults: self.trim_defaults,
+		}
+	}
+
+	// a serializer with the same settings, writing somewhere else
+	#[inline]
+	fn with_writer<'b, W2: Write + ?Sized>(&self, writer: &'b mut W2) -> Serializer<'b, W2> {
+		Serializer {
+			writer,
+			big_endian: self.big_endian,
+			strict_floats: self.strict_floats,
+			fixed: self.fixed,
+			annotate: self.annotate,
+			trim_defaults: self.trim_defaults,
+		}
+	}
+
+	// serialize a field or element of this value, marking its bounds for an ObservedWriter
+	#[inline]
+	fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+		self.writer.enter_element(Internal);
+		let result = value.serialize(self.reborrow());
+		self.writer.leave_element(Internal);
+		result
+	}
+
+	// write the type annotation for the following item, if needed
+	#[inline]
+	fn annotate(&mut self, code: u64) -> Result<()> {
+		if self.annotate {
+			wire::write_varint(self.writer, WireType::Typed, code)?;
+		}
+		Ok(())
+	}
+
+	#[inline]
+	fn write_varint(self, v: u64) -> Result<()> {
+		wire::write_varint(self.writer, WireType::Int, v)
+	}
+
+	#[inline]
+	fn write_int(mut self, v: i64) -> Result<()> {
+		self.annotate(wire::TYPE_INT)?;
+		self.write_varint(wire::zigzag_encode(v))
+	}
+
+	#[inline]
+	fn write_uint(mut self, v: u64) -> Result<()> {
+		self.annotate(wire::TYPE_UINT)?;
+		self.write_varint(v)
+	}
+
+	#[inline]
+	fn write_bytes(self, v: &[u8]) -> Result<()> {
+		wire::write_varint(self.writer, WireType::Bytes, v.len() as u64)?;
+		self.writer.write_all(v)?;
+		Ok(())
+	}
+
+	#[inline]
+	fn write_sequence_header(self, len: usize) -> Result<Self> {
+		wire::write_varint(self.writer, WireType::Sequence, len as u64)?;
+		Ok(self)
+	}
+
+	// write the header of an enum variant, after which a single item follows
+	#[inline]
+	fn write_variant_header(mut self, variant_index: u32, variant: &'static str) -> Result<Self> {
+		if self.annotate {
+			// buffered values don't know their type, so the variant is identified by name
+			self.annotate(wire::TYPE_ENUM)?;
+			self.reborrow().write_bytes(variant.as_bytes())?;
+		} else {
+			wire::write_varint(self.writer, WireType::Variant, variant_index as u64)?;
+		}
+		Ok(self)
+	}
+
+	// start a struct, after the variant header if any
+	#[inline]
+	fn start_struct(mut self, len: usize) -> Result<StructSerializer<'a, W>> {
+		self.annotate(wire::TYPE_SEQ)?;
+		if !self.trim_defaults {
+			return Ok(StructSerializer::Direct(self.write_sequence_header(len)?));
+		}
+		Ok(StructSerializer::Trimmed {
+			ser: self,
+			buf: Vec::new(),
+			kept: 0,
+			kept_len: 0,
+			fields: 0,
+		})
+	}
+
+	// write a fixed32 value, given in little-endian order
+	#[inline]
+	fn write_fixed32(self, mut v: [u8; 4]) -> Result<()> {
+		if self.big_endian {
+			v.reverse();
+		}
+		let mut b = [0u8; 5];
+		b[0] = WireType::Fixed32 as u8;
+		b[1..].copy_from_slice(&v[..]);
+		self.writer.write_all(&b[..])?;
+		Ok(())
+	}
+
+	// write a fixed64 value, given in little-endian order
+	#[inline]
+	fn write_fixed64(self, mut v: [u8; 8]) -> Result<()> {
+		if self.big_endian {
+			v.reverse();
+		}
+		let mut b = [0u8; 9];
+		b[0] = WireType::Fixed64 as u8;
+		b[1..].copy_from_slice(&v[..]);
+		self.writer.write_all(&b[..])?;
+		Ok(())
 	}
 }
 
-impl<'a, W: Write + 'a> ser::Serializer for Serializer<'a, W> {
+impl<'a, W: Write + ?Sized + 'a> ser::Serializer for Serializer<'a, W> {
 	type Ok = ();
 	type Error = Error;
 	type SerializeSeq = Self;
-	type SerializeMap = Self;
+	type SerializeMap = MapSerializer<'a, W>;
 	type SerializeTuple = Self;
 	type SerializeTupleStruct = Self;
 	type SerializeTupleVariant = Self;
-	type SerializeStruct = Self;
-	type SerializeStructVariant = Self;
+	type SerializeStruct = StructSerializer<'a, W>;
+	type SerializeStructVariant = StructSerializer<'a, W>;
 
 	#[inline]
 	fn serialize_i8(self, v: i8) -> Result<()> {
-		self.serialize_i64(v as i64)
+		self.write_int(v as i64)
 	}
 
 	#[inline]
 	fn serialize_i16(self, v: i16) -> Result<()> {
-		self.serialize_i64(v as i64)
+		self.write_int(v as i64)
 	}
 
 	#[inline]
-	fn serialize_i32(self, v: i32) -> Result<()> {
-		self.serialize_i64(v as i64)
+	fn serialize_i32(mut self, v: i32) -> Result<()> {
+		if self.fixed {
+			self.annotate(wire::TYPE_INT)?;
+			return self.write_fixed32(v.to_le_bytes());
+		}
+		self.write_int(v as i64)
 	}
 
 	#[inline]
-	fn serialize_i64(self, v: i64) -> Result<()> {
-		self.serialize_u64(wire::zigzag_encode(v))
+	fn serialize_i64(mut self, v: i64) -> Result<()> {
+		if self.fixed {
+			self.annotate(wire::TYPE_INT)?;
+			return self.write_fixed64(v.to_le_bytes());
+		}
+		self.write_int(v)
 	}
 
 	#[inline]
 	fn serialize_u8(self, v: u8) -> Result<()> {
-		self.serialize_u64(v as u64)
+		self.write_uint(v as u64)
 	}
 
 	#[inline]
 	fn serialize_u16(self, v: u16) -> Result<()> {
-		self.serialize_u64(v as u64)
+		self.write_uint(v as u64)
 	}
 
 	#[inline]
-	fn serialize_u32(self, v: u32) -> Result<()> {
-		self.serialize_u64(v as u64)
+	fn serialize_u32(mut self, v: u32) -> Result<()> {
+		if self.fixed {
+			self.annotate(wire::TYPE_UINT)?;
+			return self.write_fixed32(v.to_le_bytes());
+		}
+		self.write_uint(v as u64)
 	}
 
 	#[inline]
-	fn serialize_u64(self, v: u64) -> Result<()> {
-		wire::write_varint(self.writer, WireType::Int, v)
+	fn serialize_u64(mut self, v: u64) -> Result<()> {
+		if self.fixed {
+			self.annotate(wire::TYPE_UINT)?;
+			return self.write_fixed64(v.to_le_bytes());
+		}
+		self.write_uint(v)
 	}
 
 	#[inline]
-	fn serialize_bool(self, v: bool) -> Result<()> {
-		self.serialize_u8(if v { 1 } else { 0 })
+	fn serialize_bool(mut self, v: bool) -> Result<()> {
+		self.annotate(wire::TYPE_BOOL)?;
+		self.write_varint(if v { 1 } else { 0 })
 	}
 
-	serde::serde_if_integer128! {
-		#[inline]
-		fn serialize_i128(self, v: i128) -> Result<()> {
-			self.serialize_u128(wire::zigzag_encode_128(v))
-		}
+	#[inline]
+	fn serialize_i128(mut self, v: i128) -> Result<()> {
+		self.annotate(wire::TYPE_INT)?;
+		wire::write_varint_128(self.writer, WireType::Int, wire::zigzag_encode_128(v))
+	}
 
-		#[inline]
-		fn serialize_u128(self, v: u128) -> Result<()> {
-			wire::write_varint_128(self.writer, WireType::Int, v)
-		}
+	#[inline]
+	fn serialize_u128(mut self, v: u128) -> Result<()> {
+		self.annotate(wire::TYPE_UINT)?;
+		wire::write_varint_128(self.writer, WireType::Int, v)
 	}
 
 	#[inline]
-	fn serialize_char(self, v: char) -> Result<()> {
-		self.serialize_u32(v as u32)
+	fn serialize_char(mut self, v: char) -> Result<()> {
+		self.annotate(wire::TYPE_CHAR)?;
+		self.write_varint(v as u64)
 	}
 
 	#[inline]
-	fn serialize_f32(self, v: f32) -> Result<()> {
-		let mut b = [0u8; 5];
-		b[0] = WireType::Fixed32 as u8;
-		(&mut b[1..]).copy_from_slice(&v.to_le_bytes()[..]);
-		self.writer.write_all(&b[..])?;
-		Ok(())
+	fn serialize_f32(mut self, v: f32) -> Result<()> {
+		if self.strict_floats && !v.is_finite() {
+			return Err(Error::NonFiniteFloat);
+		}
+		self.annotate(wire::TYPE_FLOAT)?;
+		self.write_fixed32(v.to_le_bytes())
 	}
 
 	#[inline]
-	fn serialize_f64(self, v: f64) -> Result<()> {
-		let mut b = [0u8; 9];
-		b[0] = WireType::Fixed64 as u8;
-		(&mut b[1..]).copy_from_slice(&v.to_le_bytes()[..]);
-		self.writer.write_all(&b[..])?;
-		Ok(())
+	fn serialize_f64(mut self, v: f64) -> Result<()> {
+		if self.strict_floats && !v.is_finite() {
+			return Err(Error::NonFiniteFloat);
+		}
+		self.annotate(wire::TYPE_FLOAT)?;
+		self.write_fixed64(v.to_le_bytes())
 	}
 
 	#[inline]
-	fn serialize_str(self, v: &str) -> Result<()> {
-		self.serialize_bytes(v.as_bytes())
+	fn serialize_str(mut self, v: &str) -> Result<()> {
+		self.annotate(wire::TYPE_STR)?;
+		self.write_bytes(v.as_bytes())
+	}
+
+	// Display output has to be buffered to know its length; short output stays on the stack
+	fn collect_str<T: core::fmt::Display + ?Sized>(mut self, value: &T) -> Result<()> {
+		use core::fmt::Write as _;
+		let mut buf = StackBuf { buf: [0; 64], len: 0 };
+		if write!(buf, "{}", value).is_ok() {
+			self.annotate(wire::TYPE_STR)?;
+			return self.write_bytes(&buf.buf[..buf.len]);
+		}
+		let mut s = alloc::string::String::new();
+		write!(s, "{}", value)
+			.map_err(|_| <Error as ser::Error>::custom("a Display implementation returned an error"))?;
+		self.annotate(wire::TYPE_STR)?;
+		self.write_bytes(s.as_bytes())
 	}
 
 	#[inline]
-	fn serialize_bytes(self, v: &[u8]) -> Result<()> {
-		wire::write_varint(self.writer, WireType::Bytes, v.len() as u64)?;
-		self.writer.write_all(v)?;
-		Ok(())
+	fn serialize_bytes(mut self, v: &[u8]) -> Result<()> {
+		self.annotate(wire::TYPE_BYTES)?;
+		self.write_bytes(v)
 	}
 
 	#[inline]
-	fn serialize_none(self) -> Result<()> {
-		self.serialize_unit_variant("Option", 0, "None")
+	fn serialize_none(mut self) -> Result<()> {
+		self.annotate(wire::TYPE_OPTION)?;
+		wire::write_varint(self.writer, WireType::Variant, 0)?;
+		self.serialize_unit()
 	}
 
 	#[inline]
-	fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<()> {
-		self.serialize_newtype_variant("Option", 1, "Some", value)
+	fn serialize_some<T: ?Sized + Serialize>(mut self, value: &T) -> Result<()> {
+		self.annotate(wire::TYPE_OPTION)?;
+		wire::write_varint(self.writer, WireType::Variant, 1)?;
+		value.serialize(self)
 	}
 
 	#[inline]
-	fn serialize_unit(self) -> Result<()> {
-		self.serialize_bool(false)
+	fn serialize_unit(mut self) -> Result<()> {
+		self.annotate(wire::TYPE_UNIT)?;
+		self.write_varint(0)
 	}
 
 	#[inline]
@@ -139,13 +386,15 @@ impl<'a, W: Write + 'a> ser::Serializer for Serializer<'a, W> {
 	}
 
 	#[inline]
-	fn serialize_unit_variant(self, _name: &'static str, variant_index: u32, _variant: &'static str) -> Result<()> {
-		wire::write_varint(self.writer, WireType::Variant, variant_index as u64)?;
-		self.serialize_unit()
+	fn serialize_unit_variant(self, _name: &'static str, variant_index: u32, variant: &'static str) -> Result<()> {
+		self.write_variant_header(variant_index, variant)?.serialize_unit()
 	}
 
 	#[inline]
-	fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _name: &'static str, value: &T) -> Result<()> {
+	fn serialize_newtype_struct<T: ?Sized + Serialize>(self, name: &'static str, value: &T) -> Result<()> {
+		if name == crate::fixed::FIXED_TOKEN {
+			return value.serialize(Serializer { fixed: true, ..self });
+		}
 		value.serialize(self)
 	}
 
@@ -154,11 +403,10 @@ impl<'a, W: Write + 'a> ser::Serializer for Serializer<'a, W> {
 		self,
 		_name: &'static str,
 		variant_index: u32,
-		_variant: &'static str,
+		variant: &'static str,
 		value: &T,
 	) -> Result<()> {
-		wire::write_varint(self.writer, WireType::Variant, variant_index as u64)?;
-		value.serialize(self)
+		value.serialize(self.write_variant_header(variant_index, variant)?)
 	}
 
 	#[inline]
@@ -169,15 +417,24 @@ impl<'a, W: Write + 'a> ser::Serializer for Serializer<'a, W> {
 	}
 
 	#[inline]
-	fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
-		wire::write_varint(self.writer, WireType::Sequence, len as u64)?;
-		Ok(self)
+	fn serialize_tuple(mut self, len: usize) -> Result<Self::SerializeTuple> {
+		self.annotate(wire::TYPE_SEQ)?;
+		self.write_sequence_header(len)
 	}
 
 	#[inline]
-	fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
-		let len = len.expect("maps with unknown length not supported");
-		self.serialize_tuple(len * 2)
+	fn serialize_map(mut self, len: Option<usize>) -> Result<Self::SerializeMap> {
+		match len {
+			Some(len) => {
+				self.annotate(wire::TYPE_MAP)?;
+				Ok(MapSerializer::Known(self.write_sequence_header(len * 2)?))
+			}
+			None => Ok(MapSerializer::Buffered {
+				ser: self,
+				buf: Vec::new(),
+				len: 0,
+			}),
+		}
 	}
 
 	#[inline]
@@ -190,27 +447,26 @@ impl<'a, W: Write + 'a> ser::Serializer for Serializer<'a, W> {
 		self,
 		_name: &'static str,
 		variant_index: u32,
-		_variant: &'static str,
+		variant: &'static str,
 		len: usize,
 	) -> Result<Self::SerializeTupleVariant> {
-		wire::write_varint(self.writer, WireType::Variant, variant_index as u64)?;
-		self.serialize_tuple(len)
+		self.write_variant_header(variant_index, variant)?.serialize_tuple(len)
 	}
 
 	#[inline]
 	fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
-		self.serialize_tuple(len)
+		self.start_struct(len)
 	}
 
 	#[inline]
 	fn serialize_struct_variant(
 		self,
-		name: &'static str,
+		_name: &'static str,
 		variant_index: u32,
 		variant: &'static str,
 		len: usize,
 	) -> Result<Self::SerializeStructVariant> {
-		self.serialize_tuple_variant(name, variant_index, variant, len)
+		self.write_variant_header(variant_index, variant)?.start_struct(len)
 	}
 
 	#[inline]
@@ -219,12 +475,27 @@ impl<'a, W: Write + 'a> ser::Serializer for Serializer<'a, W> {
 	}
 }
 
-impl<'a, W: Write + 'a> ser::SerializeSeq for Serializer<'a, W> {
+// fixed-size formatting buffer that fails when full
+struct StackBuf {
+	buf: [u8; 64],
+	len: usize,
+}
+
+impl core::fmt::Write for StackBuf {
+	fn write_str(&mut self, s: &str) -> core::fmt::Result {
+		let dest = self.buf.get_mut(self.len..self.len + s.len()).ok_or(core::fmt::Error)?;
+		dest.copy_from_slice(s.as_bytes());
+		self.len += s.len();
+		Ok(())
+	}
+}
+
+impl<'a, W: Write + ?Sized + 'a> ser::SerializeSeq for Serializer<'a, W> {
 	type Ok = ();
 	type Error = Error;
 	#[inline]
 	fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
-		value.serialize(Serializer { writer: self.writer })
+		self.element(value)
 	}
 	#[inline]
 	fn end(self) -> Result<()> {
@@ -232,61 +503,166 @@ impl<'a, W: Write + 'a> ser::SerializeSeq for Serializer<'a, W> {
 	}
 }
 
-impl<'a, W: Write + 'a> ser::SerializeMap for Serializer<'a, W> {
+/// Map serialization state.
+///
+/// Maps of unknown length (e.g. structs with `#[serde(flatten)]` fields) are buffered, as the length comes first on
+/// the wire. Their entries are written with type annotations, so that they can be decoded by serde's flatten support.
+pub enum MapSerializer<'a, W: Write + ?Sized + 'a> {
+	Known(Serializer<'a, W>),
+	Buffered {
+		ser: Serializer<'a, W>,
+		buf: Vec<u8>,
+		len: usize,
+	},
+}
+
+impl<'a, W: Write + ?Sized + 'a> MapSerializer<'a, W> {
+	#[inline]
+	fn item<T: ?Sized + Serialize>(&mut self, item: &T) -> Result<()> {
+		match self {
+			MapSerializer::Known(ser) => item.serialize(ser.reborrow()),
+			MapSerializer::Buffered { ser, buf, len } => {
+				*len += 1;
+				item.serialize(Serializer {
+					annotate: true,
+					..ser.with_writer(buf)
+				})
+			}
+		}
+	}
+}
+
+impl<'a, W: Write + ?Sized + 'a> ser::SerializeMap for MapSerializer<'a, W> {
 	type Ok = ();
 	type Error = Error;
 	#[inline]
 	fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<()> {
-		key.serialize(Serializer { writer: self.writer })
+		self.item(key)
 	}
 	#[inline]
 	fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
-		value.serialize(Serializer { writer: self.writer })
+		self.item(value)
 	}
 	#[inline]
 	fn end(self) -> Result<()> {
-		Ok(())
+		match self {
+			MapSerializer::Known(_) => Ok(()),
+			MapSerializer::Buffered { mut ser, buf, len } => {
+				ser.annotate(wire::TYPE_MAP)?;
+				ser.write_sequence_header(len)?.writer.write_all(&buf)?;
+				Ok(())
+			}
+		}
+	}
+}
+
+/// Struct serialization state.
+///
+/// With [`trim_defaults`](Serializer::trim_defaults), fields are buffered, as the number of fields that is written
+/// comes first on the wire.
+pub enum StructSerializer<'a, W: Write + ?Sized + 'a> {
+	Direct(Serializer<'a, W>),
+	Trimmed {
+		ser: Serializer<'a, W>,
+		buf: Vec<u8>,
+		// number of fields up to the last one that isn't default, and their length in buf
+		kept: usize,
+		kept_len: usize,
+		fields: usize,
+	},
+}
+
+impl<'a, W: Write + ?Sized + 'a> StructSerializer<'a, W> {
+	#[inline]
+	fn field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+		match self {
+			StructSerializer::Direct(ser) => ser.element(value),
+			StructSerializer::Trimmed {
+				ser,
+				buf,
+				kept,
+				kept_len,
+				fields,
+			} => {
+				let start = buf.len();
+				value.serialize(ser.with_writer(buf))?;
+				ser.writer.buffered_element(&buf[start..], Internal);
+				*fields += 1;
+				if !is_default_encoding(&buf[start..]) {
+					*kept = *fields;
+					*kept_len = buf.len();
+				}
+				Ok(())
+			}
+		}
+	}
+
+	#[inline]
+	fn finish(self) -> Result<()> {
+		match self {
+			StructSerializer::Direct(_) => Ok(()),
+			StructSerializer::Trimmed {
+				ser,
+				buf,
+				kept,
+				kept_len,
+				..
+			} => {
+				ser.write_sequence_header(kept)?.writer.write_all(&buf[..kept_len])?;
+				Ok(())
+			}
+		}
 	}
 }
 
-impl<'a, W: Write + 'a> ser::SerializeStruct for Serializer<'a, W> {
+// whether an encoded value is the default of common types: zero (also unit and false), an empty string or sequence
+// (also a struct with only default fields, once trimmed), or None (or any enum's first unit variant); see the
+// contract on trim_defaults
+fn is_default_encoding(b: &[u8]) -> bool {
+	matches!(
+		b,
+		[0x00] | [0x03] | [0x04] | [0x05, 0x00] | [0x01, 0, 0, 0, 0] | [0x02, 0, 0, 0, 0, 0, 0, 0, 0]
+	)
+}
+
+impl<'a, W: Write + ?Sized + 'a> ser::SerializeStruct for StructSerializer<'a, W> {
 	type Ok = ();
 	type Error = Error;
 	#[inline]
 	fn serialize_field<T: ?Sized + Serialize>(&mut self, _key: &'static str, value: &T) -> Result<()> {
-		value.serialize(Serializer { writer: self.writer })
+		self.field(value)
 	}
 	fn skip_field(&mut self, _key: &'static str) -> Result<()> {
-		panic!("optionally skipped fields are not supported")
+		Err(Error::CannotSkipField)
 	}
 	#[inline]
 	fn end(self) -> Result<()> {
-		Ok(())
+		self.finish()
 	}
 }
 
-impl<'a, W: Write + 'a> ser::SerializeStructVariant for Serializer<'a, W> {
+impl<'a, W: Write + ?Sized + 'a> ser::SerializeStructVariant for StructSerializer<'a, W> {
 	type Ok = ();
 	type Error = Error;
 	#[inline]
 	fn serialize_field<T: ?Sized + Serialize>(&mut self, _key: &'static str, value: &T) -> Result<()> {
-		value.serialize(Serializer { writer: self.writer })
+		self.field(value)
 	}
 	fn skip_field(&mut self, _key: &'static str) -> Result<()> {
-		panic!("optionally skipped fields are not supported")
+		Err(Error::CannotSkipField)
 	}
 	#[inline]
 	fn end(self) -> Result<()> {
-		Ok(())
+		self.finish()
 	}
 }
 
-impl<'a, W: Write + 'a> ser::SerializeTuple for Serializer<'a, W> {
+impl<'a, W: Write + ?Sized + 'a> ser::SerializeTuple for Serializer<'a, W> {
 	type Ok = ();
 	type Error = Error;
 	#[inline]
 	fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
-		value.serialize(Serializer { writer: self.writer })
+		self.element(value)
 	}
 	#[inline]
 	fn end(self) -> Result<()> {
@@ -294,12 +670,12 @@ impl<'a, W: Write + 'a> ser::SerializeTuple for Serializer<'a, W> {
 	}
 }
 
-impl<'a, W: Write + 'a> ser::SerializeTupleVariant for Serializer<'a, W> {
+impl<'a, W: Write + ?Sized + 'a> ser::SerializeTupleVariant for Serializer<'a, W> {
 	type Ok = ();
 	type Error = Error;
 	#[inline]
 	fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
-		value.serialize(Serializer { writer: self.writer })
+		self.element(value)
 	}
 	#[inline]
 	fn end(self) -> Result<()> {
@@ -307,12 +683,12 @@ impl<'a, W: Write + 'a> ser::SerializeTupleVariant for Serializer<'a, W> {
 	}
 }
 
-impl<'a, W: Write + 'a> ser::SerializeTupleStruct for Serializer<'a, W> {
+impl<'a, W: Write + ?Sized + 'a> ser::SerializeTupleStruct for Serializer<'a, W> {
 	type Ok = ();
 	type Error = Error;
 	#[inline]
 	fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
-		value.serialize(Serializer { writer: self.writer })
+		self.element(value)
 	}
 	#[inline]
 	fn end(self) -> Result<()> {
@@ -0,0 +1,40 @@
+//! Deterministic encoding of unordered sets, such as `HashSet`.
+//!
+//! A `HashSet` is encoded in its iteration order, which differs between instances with the same content. Use this
+//! module on a field to sort the elements before encoding, so that the encoding only depends on the content:
+//!
+//! ```
+//! # use serde::{Serialize, Deserialize};
+//! # use std::collections::HashSet;
+//! #[derive(Serialize, Deserialize)]
+//! struct Foo {
+//!     #[serde(with = "fcode::sorted_set")]
+//!     tags: HashSet<String>,
+//! }
+//! ```
+//!
+//! The encoding is the same as that of the plain set, or of a `BTreeSet` with the same content, so adding or removing
+//! the attribute is a compatible change. Sorting takes a temporary vector of references.
+
+use alloc::vec::Vec;
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+pub fn serialize<'a, C, T, S>(set: &'a C, serializer: S) -> Result<S::Ok, S::Error>
+where
+	&'a C: IntoIterator<Item = &'a T>,
+	T: Ord + Serialize + 'a,
+	S: Serializer,
+{
+	let mut items: Vec<&T> = set.into_iter().collect();
+	items.sort_unstable();
+	serializer.collect_seq(items)
+}
+
+pub fn deserialize<'de, C, D>(deserializer: D) -> Result<C, D::Error>
+where
+	C: Deserialize<'de>,
+	D: Deserializer<'de>,
+{
+	C::deserialize(deserializer)
+}
@@ -0,0 +1,27 @@
+//! Compact encoding of [`SystemTime`] as a single varint of nanoseconds since the Unix epoch.
+//!
+//! This is [`duration_nanos`] applied to the time since [`UNIX_EPOCH`]. Serde's default encoding
+//! of a `SystemTime` has the same layout as that of a `Duration`, so as with `duration_nanos`, decoding accepts both
+//! forms, and adding the attribute to an existing field is a compatible change.
+//!
+//! Times up to about the year 5138 fit a single varint; later times fall back to the default encoding. Times before
+//! the epoch can't be encoded, same as with serde's default encoding.
+
+use crate::duration_nanos;
+use serde::de::{self, Deserializer};
+use serde::ser::{self, Serializer};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn serialize<S: Serializer>(value: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+	let d = value
+		.duration_since(UNIX_EPOCH)
+		.map_err(|_| ser::Error::custom("SystemTime must be later than UNIX_EPOCH"))?;
+	duration_nanos::serialize(&d, serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+	let d = duration_nanos::deserialize(deserializer)?;
+	UNIX_EPOCH
+		.checked_add(d)
+		.ok_or_else(|| de::Error::custom("overflow deserializing SystemTime"))
+}
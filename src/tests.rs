@@ -70,125 +70,3177 @@ fn test_minmax() {
 }
 
 #[test]
-fn test_borrowed() {
-	let buf = to_bytes("foobar").unwrap();
-	let s: &str = from_bytes(&buf).unwrap();
-	assert_eq!(s, "foobar");
+fn test_big_endian() {
+	#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+	struct Foo {
+		x: f64,
+		y: f32,
+		z: i32,
+	}
 
-	// serde::ser::Serialize is not specifically implemented for &[u8]
-	let buf = to_bytes(&serde_bytes::Bytes::new("foobar".as_bytes())).unwrap();
-	let v: &[u8] = from_bytes(&buf).unwrap();
-	assert_eq!(std::str::from_utf8(v).unwrap(), "foobar");
+	let mut buf = Vec::new();
+	42.5f64.serialize(Serializer::new(&mut buf).big_endian(true)).unwrap();
+	assert_eq!(buf[0], wire::WireType::Fixed64 as u8);
+	assert_eq!(&buf[1..], &42.5f64.to_be_bytes()[..]);
+	let mut de = Deserializer::from_bytes(&buf).big_endian(true);
+	assert_eq!(f64::deserialize(&mut de).unwrap(), 42.5);
 
-	// other slices can only be serialized
-	let stuff = [1i32, 2i32, 3i32];
-	let buf = to_bytes(&stuff[..]).unwrap();
-	let v: Vec<i32> = from_bytes(&buf).unwrap();
-	assert_eq!(v, vec![1, 2, 3]);
+	// the default is little-endian
+	assert_eq!(&to_bytes(&42.5f64).unwrap()[1..], &42.5f64.to_le_bytes()[..]);
 
-	// embedded in struct
-	#[derive(Debug, Serialize, Deserialize)]
-	struct Foo<'a> {
-		i: i32,
-		s: &'a str,
+	let value = Foo {
+		x: -1.25,
+		y: 3.5,
+		z: -42,
+	};
+	let mut buf = Vec::new();
+	value.serialize(Serializer::new(&mut buf).big_endian(true)).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).big_endian(true);
+	assert_eq!(Foo::deserialize(&mut de).unwrap(), value);
+	// varints are the same in both modes
+	assert_eq!(buf[buf.len() - 1], to_bytes(&value).unwrap()[buf.len() - 1]);
+
+	// fixed-width integers are read big-endian too
+	let mut buf = vec![wire::WireType::Fixed32 as u8];
+	buf.extend_from_slice(&1234567i32.to_be_bytes());
+	let mut de = Deserializer::from_bytes(&buf).big_endian(true);
+	assert_eq!(i32::deserialize(&mut de).unwrap(), 1234567);
+}
+
+#[test]
+fn test_strict_floats() {
+	fn strict<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+		let mut buf = Vec::new();
+		value.serialize(Serializer::new(&mut buf).strict_floats(true))?;
+		Ok(buf)
+	}
+
+	assert!(matches!(strict(&f64::NAN), Err(Error::NonFiniteFloat)));
+	assert!(matches!(strict(&f64::INFINITY), Err(Error::NonFiniteFloat)));
+	assert!(matches!(strict(&f32::NEG_INFINITY), Err(Error::NonFiniteFloat)));
+	assert!(matches!(strict(&vec![1.0, f64::NAN]), Err(Error::NonFiniteFloat)));
+	assert_eq!(
+		strict(&vec![1.0f64, -0.0, f64::MAX]).unwrap(),
+		to_bytes(&vec![1.0f64, -0.0, f64::MAX]).unwrap()
+	);
+
+	// permissive by default
+	assert!(ser_de!(f64::NAN).is_nan());
+	assert_eq!(ser_de!(f32::INFINITY), f32::INFINITY);
+}
+
+#[test]
+fn test_fixed() {
+	assert_eq!(to_bytes(&Fixed(42u32)).unwrap().len(), 5);
+	assert_eq!(to_bytes(&Fixed(-42i32)).unwrap().len(), 5);
+	assert_eq!(to_bytes(&Fixed(42u64)).unwrap().len(), 9);
+	assert_eq!(to_bytes(&Fixed(-42i64)).unwrap().len(), 9);
+	assert_eq!(ser_de!(Fixed(u32::MAX)), Fixed(u32::MAX));
+	assert_eq!(ser_de!(Fixed(i32::MIN)), Fixed(i32::MIN));
+	assert_eq!(ser_de!(Fixed(u64::MAX)), Fixed(u64::MAX));
+	assert_eq!(ser_de!(Fixed(i64::MIN)), Fixed(i64::MIN));
+
+	// applies to nested integers, but not to small ones
+	assert_eq!(to_bytes(&Fixed(vec![1u64, 2, 3])).unwrap().len(), 1 + 3 * 9);
+	assert_eq!(to_bytes(&Fixed((1u8, true))).unwrap(), to_bytes(&(1u8, true)).unwrap());
+
+	// wrapping is a compatible change
+	let buf = to_bytes(&Fixed(0xABADCAFEABADCAFEu64)).unwrap();
+	assert_eq!(from_bytes::<u64>(&buf).unwrap(), 0xABADCAFEABADCAFE);
+	let buf = to_bytes(&42i32).unwrap();
+	assert_eq!(from_bytes::<Fixed<i32>>(&buf).unwrap(), Fixed(42));
+}
+
+#[test]
+#[allow(irrefutable_let_patterns)]
+fn test_integer_evolution() {
+	use std::convert::TryFrom;
+
+	// decode data as D, which should succeed exactly when v fits
+	fn expect<D>(buf: &[u8], v: i128, fixed: bool)
+	where
+		D: DeserializeOwned + TryFrom<i128> + PartialEq + std::fmt::Debug,
+	{
+		let result = from_bytes::<D>(buf);
+		let target = std::any::type_name::<D>();
+		if fixed && std::mem::size_of::<D>() < 4 {
+			assert!(matches!(result, Err(Error::UnexpectedWireType)), "{} as {}", v, target);
+			return;
+		}
+		match D::try_from(v) {
+			Ok(d) => assert_eq!(result.unwrap(), d, "{} as {}", v, target),
+			Err(_) => assert!(
+				matches!(result, Err(Error::ValueOverflow { .. })),
+				"{} as {}",
+				v,
+				target
+			),
+		}
+	}
+	macro_rules! signed {
+		($buf:expr, $v:expr, $fixed:expr) => {
+			expect::<i8>($buf, $v, $fixed);
+			expect::<i16>($buf, $v, $fixed);
+			expect::<i32>($buf, $v, $fixed);
+			expect::<i64>($buf, $v, $fixed);
+			expect::<i128>($buf, $v, $fixed);
+		};
+	}
+	macro_rules! unsigned {
+		($buf:expr, $v:expr, $fixed:expr) => {
+			expect::<u8>($buf, $v, $fixed);
+			expect::<u16>($buf, $v, $fixed);
+			expect::<u32>($buf, $v, $fixed);
+			expect::<u64>($buf, $v, $fixed);
+			expect::<u128>($buf, $v, $fixed);
+		};
+	}
+	// encode v as S if it fits, and decode it as every type of the same signedness
+	macro_rules! source {
+		($v:expr, $dest:ident, $($s:ty),*) => {
+			$(
+				if let Ok(s) = <$s>::try_from($v) {
+					$dest!(&to_bytes(&s).unwrap(), $v, false);
+					if std::mem::size_of::<$s>() == 4 || std::mem::size_of::<$s>() == 8 {
+						$dest!(&to_bytes(&Fixed(s)).unwrap(), $v, true);
+					}
+				}
+			)*
+		};
+	}
+
+	// the boundaries of each type, and just beyond
+	let mut values = vec![0i128, 1, -1];
+	for &(min, max) in &[
+		(i8::MIN as i128, i8::MAX as i128),
+		(i16::MIN as i128, i16::MAX as i128),
+		(i32::MIN as i128, i32::MAX as i128),
+		(i64::MIN as i128, i64::MAX as i128),
+		(0, u8::MAX as i128),
+		(0, u16::MAX as i128),
+		(0, u32::MAX as i128),
+		(0, u64::MAX as i128),
+	] {
+		values.extend(&[min, min - 1, max, max + 1]);
+	}
+	values.extend(&[i128::MIN, i128::MAX]);
+
+	for &v in &values {
+		source!(v, signed, i8, i16, i32, i64, i128);
+		source!(v, unsigned, u8, u16, u32, u64, u128);
+	}
+}
+
+#[test]
+fn test_fixed_timestamp() {
+	#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+	struct Record {
+		#[serde(with = "crate::fixed_timestamp")]
+		ts: i64,
+	}
+
+	for &ts in &[0i64, 1, -1, 1_600_000_000_000_000_000, i64::MAX, i64::MIN] {
+		let value = Record { ts };
+		let buf = to_bytes(&value).unwrap();
+		// sequence header + tag + 8 bytes
+		assert_eq!(buf.len(), 1 + 9);
+		assert_eq!(from_bytes::<Record>(&buf).unwrap(), value);
+	}
+}
+
+#[test]
+fn test_pair128() {
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct Pair {
+		#[serde(with = "crate::pair128")]
+		u: u128,
+		#[serde(with = "crate::pair128")]
+		i: i128,
+	}
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct Native {
+		u: u128,
+		i: i128,
+	}
+
+	for &(u, i) in &[
+		(0, 0),
+		(1, -1),
+		(u64::MAX as u128 + 1, i64::MIN as i128 - 1),
+		(u128::MAX, i128::MIN),
+		(7, i128::MAX),
+	] {
+		let value = Pair { u, i };
+		let buf = to_bytes(&value).unwrap();
+		assert_eq!(from_bytes::<Pair>(&buf).unwrap(), value);
+		// the same value as the native encoding, as two varints each
+		let native = from_bytes::<Native>(&to_bytes(&Native { u, i }).unwrap()).unwrap();
+		assert_eq!((native.u, native.i), (u, i));
+		let halves = (((u >> 64) as u64, u as u64), ((i >> 64) as i64, i as u64));
+		assert_eq!(buf, to_bytes(&halves).unwrap());
+	}
+
+	// readable by a decoder without 128-bit integers
+	let buf = to_bytes(&Pair { u: 5, i: -2 }).unwrap();
+	assert_eq!(
+		from_bytes::<((u64, u64), (i64, u64))>(&buf).unwrap(),
+		((0, 5), (-1, u64::MAX - 1))
+	);
+}
+
+#[test]
+fn test_map_size_hint() {
+	use std::collections::{BTreeMap, HashMap};
+
+	// the entry count reaches the map, so that it's allocated once
+	let m: HashMap<u32, u64> = (0..10_000).map(|i| (i, i as u64)).collect();
+	let decoded: HashMap<u32, u64> = from_bytes(&to_bytes(&m).unwrap()).unwrap();
+	assert_eq!(decoded, m);
+	assert_eq!(
+		decoded.capacity(),
+		HashMap::<u32, u64>::with_capacity(m.len()).capacity()
+	);
+
+	// the hint is the number of entries left
+	struct Hints(Vec<Option<usize>>);
+	impl<'de> Deserialize<'de> for Hints {
+		fn deserialize<D: serde::Deserializer<'de>>(d: D) -> std::result::Result<Self, D::Error> {
+			struct HintsVisitor;
+			impl<'de> serde::de::Visitor<'de> for HintsVisitor {
+				type Value = Hints;
+				fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+					f.write_str("a map")
+				}
+				fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> std::result::Result<Hints, A::Error> {
+					let mut hints = vec![map.size_hint()];
+					while map.next_entry::<u32, u64>()?.is_some() {
+						hints.push(map.size_hint());
+					}
+					Ok(Hints(hints))
+				}
+			}
+			d.deserialize_map(HintsVisitor)
+		}
+	}
+	let m: BTreeMap<u32, u64> = vec![(1, 2), (3, 4)].into_iter().collect();
+	assert_eq!(
+		from_bytes::<Hints>(&to_bytes(&m).unwrap()).unwrap().0,
+		[Some(2), Some(1), Some(0)]
+	);
+}
+
+#[test]
+fn test_compat() {
+	use crate::compat::*;
+
+	let short = ShortStruct { x: 1, y: 2 };
+	let long = LongStruct { x: 1, y: 2, z: 3 };
+	// a field added with a default
+	check_compatible(&short, &long).unwrap();
+	let decoded: LongStruct = check_roundtrip(&short).unwrap();
+	assert_eq!(decoded, LongStruct { x: 1, y: 2, z: 0 });
+
+	// without the default, old data can't be read
+	let err = check_compatible(&ShortTuple(1, 2), &(1i32, 2i32, 3i32)).unwrap_err();
+	assert_eq!(err.direction, Direction::OldToNew);
+	assert!(matches!(err.error, Error::InvalidLength { len: 2, .. }), "{}", err);
+	assert_eq!(err.encoded, to_bytes(&ShortTuple(1, 2)).unwrap());
+
+	// the error tells where
+	#[derive(Serialize, Deserialize, Debug)]
+	struct Old {
+		id: u32,
+		tags: Vec<u32>,
+	}
+	#[derive(Serialize, Deserialize, Debug)]
+	struct New {
+		id: u32,
+		tags: Vec<String>,
+	}
+	let err = check_roundtrip::<New, _>(&Old { id: 1, tags: vec![7] }).unwrap_err();
+	assert!(matches!(err.error, Error::UnexpectedWireType));
+	assert_eq!(err.offset, 4);
+	assert!(err.to_string().starts_with("old data read as new type: "), "{}", err);
+
+	// an empty list passes; samples should cover the interesting cases
+	check_compatible(&Old { id: 1, tags: vec![] }, &New { id: 1, tags: vec![] }).unwrap();
+}
+
+#[test]
+fn test_fixed_fields() {
+	#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+	struct Record {
+		#[serde(with = "crate::fixed32")]
+		a: u32,
+		#[serde(with = "crate::fixed32")]
+		b: i32,
+		#[serde(with = "crate::fixed64")]
+		c: u64,
+		#[serde(with = "crate::fixed64")]
+		d: i64,
+	}
+	#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+	struct Plain {
+		a: u32,
+		b: i32,
+		c: u64,
+		d: i64,
+	}
+
+	let value = Record {
+		a: 1,
+		b: -1,
+		c: 2,
+		d: -2,
+	};
+	let buf = to_bytes(&value).unwrap();
+	assert_eq!(
+		buf,
+		to_bytes(&(Fixed(1u32), Fixed(-1i32), Fixed(2u64), Fixed(-2i64))).unwrap()
+	);
+	assert_eq!(buf.len(), 1 + 5 + 5 + 9 + 9);
+	assert_eq!(from_bytes::<Record>(&buf).unwrap(), value);
+
+	// compatible with varint fields both ways
+	let plain = Plain {
+		a: 1,
+		b: -1,
+		c: 2,
+		d: -2,
+	};
+	assert_eq!(from_bytes::<Plain>(&buf).unwrap(), plain);
+	assert_eq!(from_bytes::<Record>(&to_bytes(&plain).unwrap()).unwrap(), value);
+}
+
+#[test]
+fn test_duration_nanos() {
+	use std::time::Duration;
+
+	#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+	struct Compact {
+		#[serde(with = "crate::duration_nanos")]
+		d: Duration,
+	}
+	#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+	struct Default {
+		d: Duration,
+	}
+
+	// serde's own encoding is seconds and nanoseconds
+	let d = Duration::new(1234, 567_000_000);
+	assert_eq!(ser_de!(d), d);
+	assert_eq!(to_bytes(&d).unwrap(), to_bytes(&(1234u64, 567_000_000u32)).unwrap());
+
+	for &d in &[
+		Duration::from_nanos(0),
+		Duration::from_millis(1500),
+		Duration::new(1234, 567_000_000),
+		Duration::from_nanos(u64::MAX),
+		Duration::new(u64::MAX / 1_000_000_000 + 1, 0),
+		Duration::new(u64::MAX, 999_999_999),
+	] {
+		let compact = Compact { d };
+		assert_eq!(ser_de!(compact.clone()), compact);
+
+		// the default encoding can be read
+		let buf = to_bytes(&Default { d }).unwrap();
+		assert_eq!(from_bytes::<Compact>(&buf).unwrap(), compact);
+	}
+
+	// typical values are a single varint
+	let compact = Compact {
+		d: Duration::from_millis(1500),
+	};
+	let buf = to_bytes(&compact).unwrap();
+	assert_eq!(buf.len(), 1 + serialized_size(&1_500_000_000u64).unwrap());
+	assert!(buf.len() < serialized_size(&Default { d: compact.d }).unwrap());
+
+	// the compact form goes up to 10^20 nanoseconds, beyond u64
+	let d = Duration::new(100_000_000_000, 0);
+	let buf = to_bytes(&Compact { d }).unwrap();
+	assert_eq!(buf, to_bytes(&(d.as_nanos(),)).unwrap());
+	assert_eq!(buf.len(), 11);
+	assert_eq!(from_bytes::<Compact>(&buf).unwrap().d, d);
+	let d = d + Duration::from_nanos(1);
+	let buf = to_bytes(&Compact { d }).unwrap();
+	assert_eq!(buf, to_bytes(&Default { d }).unwrap());
+	assert_eq!(from_bytes::<Compact>(&buf).unwrap().d, d);
+
+	// invalid nanoseconds
+	let buf = to_bytes(&((1u64, 1_000_000_000u32),)).unwrap();
+	assert!(from_bytes::<Compact>(&buf).is_err());
+	let buf = to_bytes(&(u128::MAX,)).unwrap();
+	assert!(from_bytes::<Compact>(&buf).is_err());
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_system_time() {
+	use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+	#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+	struct Compact {
+		#[serde(with = "crate::system_time_nanos")]
+		t: SystemTime,
+	}
+	#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+	struct Default {
+		t: SystemTime,
+	}
+
+	let now = SystemTime::now();
+	assert_eq!(ser_de!(now), now);
+
+	for &t in &[
+		UNIX_EPOCH,
+		now,
+		UNIX_EPOCH + Duration::from_nanos(u64::MAX),
+		UNIX_EPOCH + Duration::new(u64::MAX / 1_000_000_000 + 1, 0),
+		// last time in the compact form
+		UNIX_EPOCH + Duration::new(100_000_000_000, 0),
+		UNIX_EPOCH + Duration::new(100_000_000_000, 1),
+	] {
+		let compact = Compact { t };
+		assert_eq!(ser_de!(compact.clone()), compact);
+		let buf = to_bytes(&Default { t }).unwrap();
+		assert_eq!(from_bytes::<Compact>(&buf).unwrap(), compact);
+	}
+
+	// a current time takes a tag and a 9-byte varint, rather than a sequence of two
+	let buf = to_bytes(&Compact { t: now }).unwrap();
+	assert_eq!(buf.len(), 1 + 1 + 9);
+
+	// before the epoch is not supported either way
+	let early = UNIX_EPOCH - Duration::from_secs(1);
+	assert!(to_bytes(&early).is_err());
+	assert!(to_bytes(&Compact { t: early }).is_err());
+}
+
+#[test]
+fn test_bytes_seq_mismatch() {
+	#[derive(Serialize)]
+	struct Sender {
 		#[serde(with = "serde_bytes")]
-		b: &'a [u8],
-		j: i32,
+		data: Vec<u8>,
 	}
-	let f_in = Foo {
-		i: 42,
-		s: "foobar",
-		b: "barfoo".as_bytes(),
-		j: 43,
+	#[derive(Deserialize, Debug)]
+	struct Receiver {
+		#[allow(dead_code)]
+		data: Vec<u8>,
+	}
+
+	let buf = to_bytes(&Sender { data: vec![1, 2, 3] }).unwrap();
+	assert!(matches!(
+		from_bytes::<Receiver>(&buf),
+		Err(Error::ExpectedSequenceFoundBytes)
+	));
+
+	// other mismatches are still generic
+	let buf = to_bytes(&(5u32,)).unwrap();
+	assert!(matches!(from_bytes::<(Vec<u8>,)>(&buf), Err(Error::UnexpectedWireType)));
+}
+
+#[test]
+fn test_from_bytes_seed() {
+	struct Scaled(i32);
+
+	impl<'de> serde::de::DeserializeSeed<'de> for Scaled {
+		type Value = i32;
+		fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> std::result::Result<i32, D::Error> {
+			Ok(i32::deserialize(deserializer)? * self.0)
+		}
+	}
+
+	let buf = to_bytes(&-7i32).unwrap();
+	assert_eq!(from_bytes_seed(&buf, Scaled(3)).unwrap(), -21);
+	assert_eq!(from_bytes_seed(&buf, Scaled(100)).unwrap(), -700);
+	assert!(matches!(
+		from_bytes_seed(&[buf[0], 0], Scaled(3)),
+		Err(Error::DataBeyondEnd)
+	));
+	assert!(matches!(
+		from_bytes_seed(&[], Scaled(3)),
+		Err(Error::UnexpectedEndOfInput)
+	));
+}
+
+#[test]
+fn test_merge_from_bytes() {
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct State {
+		id: u32,
+		data: Vec<u8>,
+		name: String,
+	}
+
+	let mut state = State {
+		id: 1,
+		data: Vec::with_capacity(1000),
+		name: String::with_capacity(100),
 	};
-	let buf = to_bytes(&f_in).unwrap();
-	let f_out: Foo = from_bytes(&buf).unwrap();
-	assert_eq!(f_out.i, 42);
-	assert_eq!(f_out.j, 43);
-	assert_eq!(f_out.s, "foobar");
-	assert_eq!(std::str::from_utf8(f_out.b).unwrap(), "barfoo");
+	state.data.extend(0..100);
+	let data_ptr = state.data.as_ptr();
+	let name_ptr = state.name.as_ptr();
+
+	let update = State {
+		id: 2,
+		data: vec![7; 50],
+		name: "hello".into(),
+	};
+	let buf = to_bytes(&update).unwrap();
+	merge_from_bytes(&mut state, &buf).unwrap();
+	assert_eq!(state, update);
+	assert_eq!(state.data.capacity(), 1000);
+	assert_eq!(state.data.as_ptr(), data_ptr);
+	assert_eq!(state.name.capacity(), 100);
+	assert_eq!(state.name.as_ptr(), name_ptr);
+
+	let mut buf = buf;
+	buf.push(0);
+	assert!(matches!(merge_from_bytes(&mut state, &buf), Err(Error::DataBeyondEnd)));
+}
+
+#[cfg(feature = "indexmap")]
+#[test]
+fn test_indexmap_order() {
+	use indexmap::IndexMap;
+
+	let mut m = IndexMap::new();
+	for (i, k) in ["zulu", "alpha", "mike", "bravo", "yankee"].iter().enumerate() {
+		m.insert(k.to_string(), i as i32 - 2);
+	}
+	let buf = to_bytes(&m).unwrap();
+	let m2: IndexMap<String, i32> = from_bytes(&buf).unwrap();
+	assert!(m.iter().eq(m2.iter()));
+	assert_eq!(to_bytes(&m2).unwrap(), buf);
+	assert!(is_canonical::<IndexMap<String, i32>>(&buf).unwrap());
+
+	// the order on the wire is kept; a map is a sequence of alternating keys and values
+	let buf = to_bytes(&("b", 1, "a", 2)).unwrap();
+	let m: IndexMap<String, i32> = from_bytes(&buf).unwrap();
+	assert_eq!(m.keys().collect::<Vec<_>>(), ["b", "a"]);
+	assert_eq!(to_bytes(&m).unwrap(), buf);
+}
+
+#[test]
+fn test_btree_deterministic() {
+	use std::collections::{BTreeMap, BTreeSet};
+
+	let keys = ["zulu", "alpha", "mike", "bravo", "yankee", "charlie"];
+	let forward: BTreeMap<String, u32> = keys
+		.iter()
+		.enumerate()
+		.map(|(i, k)| (k.to_string(), i as u32))
+		.collect();
+	let backward: BTreeMap<String, u32> = keys
+		.iter()
+		.enumerate()
+		.rev()
+		.map(|(i, k)| (k.to_string(), i as u32))
+		.collect();
+	let buf = to_bytes(&forward).unwrap();
+	assert_eq!(to_bytes(&forward).unwrap(), buf);
+	assert_eq!(to_bytes(&backward).unwrap(), buf);
+	assert!(is_canonical::<BTreeMap<String, u32>>(&buf).unwrap());
+
+	let forward: BTreeSet<&str> = keys.iter().copied().collect();
+	let backward: BTreeSet<&str> = keys.iter().rev().copied().collect();
+	let buf = to_bytes(&forward).unwrap();
+	assert_eq!(to_bytes(&backward).unwrap(), buf);
+	let mut sorted = keys.to_vec();
+	sorted.sort_unstable();
+	assert_eq!(buf, to_bytes(&sorted).unwrap());
+}
+
+#[test]
+fn test_sets() {
+	use std::collections::{BTreeSet, HashSet};
+
+	let values = [5i32, -3, 100, 0, 42, -1000];
+	let bset: BTreeSet<i32> = values.iter().copied().collect();
+	assert_eq!(ser_de!(bset.clone()), bset);
+	let mut sorted = values.to_vec();
+	sorted.sort_unstable();
+	assert_eq!(to_bytes(&bset).unwrap(), to_bytes(&sorted).unwrap());
+
+	let hset: HashSet<i32> = values.iter().copied().collect();
+	assert_eq!(ser_de!(hset.clone()), hset);
+	let buf = to_bytes(&hset).unwrap();
+	assert_eq!(from_bytes::<BTreeSet<i32>>(&buf).unwrap(), bset);
+
+	// sorted for a deterministic encoding
+	#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+	struct Sorted {
+		#[serde(with = "crate::sorted_set")]
+		set: HashSet<i32>,
+	}
+	let sorted_buf = to_bytes(&Sorted { set: hset.clone() }).unwrap();
+	assert_eq!(sorted_buf, to_bytes(&(bset.clone(),)).unwrap());
+	assert_eq!(from_bytes::<Sorted>(&sorted_buf).unwrap(), Sorted { set: hset });
+	let other: HashSet<i32> = values.iter().rev().copied().collect();
+	assert_eq!(to_bytes(&Sorted { set: other }).unwrap(), sorted_buf);
+}
+
+#[test]
+fn test_empty_collections() {
+	use std::collections::HashMap;
+
+	#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+	struct Empty();
+
+	// a single tag byte with length 0
+	assert_eq!(to_bytes(&Vec::<u32>::new()).unwrap(), [3]);
+	assert_eq!(to_bytes(&String::new()).unwrap(), [4]);
+	assert_eq!(to_bytes(&HashMap::<String, u32>::new()).unwrap(), [3]);
+	assert_eq!(to_bytes(&Empty()).unwrap(), [3]);
+
+	assert_eq!(ser_de!(Vec::<u32>::new()), Vec::<u32>::new());
+	assert_eq!(ser_de!(String::new()), "");
+	assert_eq!(ser_de!(HashMap::<String, u32>::new()), HashMap::new());
+	assert_eq!(ser_de!(Empty()), Empty());
+
+	// nothing is read past an empty collection
+	let v = (
+		Vec::<u32>::new(),
+		String::new(),
+		HashMap::<String, u32>::new(),
+		Empty(),
+		42u32,
+	);
+	assert_eq!(ser_de!(v.clone()), v);
+	let buf = to_bytes(&(HashMap::<String, u32>::new(), 1u32)).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).strict_maps(true);
+	assert_eq!(
+		<(HashMap<String, u32>, u32)>::deserialize(&mut de).unwrap(),
+		(HashMap::new(), 1)
+	);
+
+	// items sent to an empty tuple struct are skipped
+	assert_eq!(from_bytes::<Empty>(&to_bytes(&(1u32, "x")).unwrap()).unwrap(), Empty());
+
+	// asking for more keys or elements after the end keeps returning None
+	struct Exhaust;
+	impl<'de> serde::de::Visitor<'de> for Exhaust {
+		type Value = ();
+		fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+			f.write_str("anything")
+		}
+		fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> std::result::Result<(), A::Error> {
+			for _ in 0..3 {
+				assert_eq!(map.size_hint(), Some(0));
+				assert!(map.next_key::<u32>()?.is_none());
+			}
+			Ok(())
+		}
+		fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<(), A::Error> {
+			for _ in 0..3 {
+				assert_eq!(seq.size_hint(), Some(0));
+				assert!(seq.next_element::<u32>()?.is_none());
+			}
+			Ok(())
+		}
+	}
+	let mut de = Deserializer::from_bytes(&[3, 3, 0x08]);
+	serde::Deserializer::deserialize_map(&mut de, Exhaust).unwrap();
+	serde::Deserializer::deserialize_seq(&mut de, Exhaust).unwrap();
+	assert_eq!(u32::deserialize(&mut de).unwrap(), 1);
+}
+
+#[test]
+fn test_strict_maps() {
+	use std::collections::HashMap;
+
+	// a map is a sequence of alternating keys and values
+	let buf = to_bytes(&("a", 1, "b", 2, "a", 3)).unwrap();
+	let m: HashMap<String, i32> = from_bytes(&buf).unwrap();
+	assert_eq!(m["a"], 3);
+	let mut de = Deserializer::from_bytes(&buf).strict_maps(true);
+	assert!(matches!(
+		HashMap::<String, i32>::deserialize(&mut de),
+		Err(Error::DuplicateKey)
+	));
+
+	// equal values under different keys are fine, also in nested maps
+	let mut m = HashMap::new();
+	m.insert("x".to_string(), HashMap::from([(1u32, 5), (2, 5)]));
+	m.insert("y".to_string(), HashMap::from([(1u32, 5)]));
+	let buf = to_bytes(&m).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).strict_maps(true);
+	assert_eq!(HashMap::<String, HashMap<u32, i32>>::deserialize(&mut de).unwrap(), m);
+}
+
+#[test]
+fn test_nested_option() {
+	let values = [None, Some(None), Some(Some(5)), Some(Some(0))];
+	let encoded: Vec<_> = values.iter().map(|v| to_bytes(v).unwrap()).collect();
+	for (v, buf) in values.iter().zip(&encoded) {
+		assert_eq!(&from_bytes::<Option<Option<i32>>>(buf).unwrap(), v);
+	}
+	for (i, a) in encoded.iter().enumerate() {
+		for b in &encoded[i + 1..] {
+			assert_ne!(a, b);
+		}
+	}
+
+	let v: Option<Option<Option<String>>> = Some(Some(None));
+	assert_eq!(ser_de!(v.clone()), v);
+
+	// in a struct, next to other fields
+	#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+	struct Patch {
+		a: Option<Option<u32>>,
+		b: Option<Option<u32>>,
+		c: u32,
+	}
+	let p = Patch {
+		a: Some(None),
+		b: None,
+		c: 7,
+	};
+	assert_eq!(ser_de!(p.clone()), p);
+
+	// only 0 and 1 are valid discriminators
+	let mut buf = Vec::new();
+	write_discriminator(&mut buf, 2).unwrap();
+	buf.extend(to_bytes(&()).unwrap());
+	assert!(from_bytes::<Option<()>>(&buf).is_err());
+}
+
+#[test]
+#[cfg(feature = "transcode")]
+fn test_transcode() {
+	use serde_json::json;
+
+	fn to_json(data: &[u8]) -> serde_json::Value {
+		let mut out = Vec::new();
+		transcode_to(data, &mut serde_json::Serializer::new(&mut out)).unwrap();
+		serde_json::from_slice(&out).unwrap()
+	}
+
+	#[derive(Serialize)]
+	struct Foo {
+		id: u32,
+		name: &'static str,
+		ratio: f64,
+		tags: Vec<&'static str>,
+		parent: Option<u32>,
+		expr: Expr,
+		delta: i32,
+	}
+	let buf = to_bytes(&Foo {
+		id: 42,
+		name: "foo",
+		ratio: 0.5,
+		tags: vec!["a", "b"],
+		parent: None,
+		expr: Expr::Neg(Box::new(Expr::Lit(3))),
+		delta: -1,
+	})
+	.unwrap();
+	assert_eq!(
+		to_json(&buf),
+		json!([42, "foo", 0.5, ["a", "b"], {"0": 0}, {"2": {"0": 6}}, 1])
+	);
+
+	// flattened structs know their field names and types, but maps are sequences of keys and values on the wire
+	#[derive(Serialize)]
+	struct Inner {
+		delta: i32,
+		name: String,
+	}
+	#[derive(Serialize)]
+	struct Outer {
+		id: u32,
+		#[serde(flatten)]
+		inner: Inner,
+	}
+	let buf = to_bytes(&Outer {
+		id: 7,
+		inner: Inner {
+			delta: -1,
+			name: "bar".into(),
+		},
+	})
+	.unwrap();
+	assert_eq!(to_json(&buf), json!(["id", 7, "delta", -1, "name", "bar"]));
+
+	let mut buf = to_bytes(&1u32).unwrap();
+	buf.push(0);
+	let mut out = Vec::new();
+	assert!(transcode_to(&buf, &mut serde_json::Serializer::new(&mut out)).is_err());
+}
+
+#[test]
+fn test_crc() {
+	let value = (ShortStruct { x: 1, y: -2 }, "foobar".to_string(), vec![1u64 << 40; 3]);
+	let buf = to_bytes_with_crc(&value).unwrap();
+	assert_eq!(buf.len(), serialized_size(&value).unwrap() + 4);
+	assert_eq!(
+		from_bytes_with_crc::<(ShortStruct, String, Vec<u64>)>(&buf).unwrap(),
+		value
+	);
+
+	// any flipped bit is detected, in body or checksum
+	for i in 0..buf.len() {
+		for bit in 0..8 {
+			let mut corrupt = buf.clone();
+			corrupt[i] ^= 1 << bit;
+			assert!(matches!(
+				from_bytes_with_crc::<(ShortStruct, String, Vec<u64>)>(&corrupt),
+				Err(Error::ChecksumMismatch)
+			));
+		}
+	}
+	assert!(matches!(
+		from_bytes_with_crc::<u32>(&buf[..3]),
+		Err(Error::UnexpectedEndOfInput)
+	));
+}
+
+#[test]
+fn test_schema_hash() {
+	#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+	struct A {
+		x: u32,
+		y: u32,
+		name: String,
+	}
+	#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+	struct B {
+		count: u32,
+		total: u32,
+		label: String,
+	}
+
+	let a = A {
+		x: 1,
+		y: 2,
+		name: "foo".into(),
+	};
+	let buf = to_bytes_with_schema_hash(&a).unwrap();
+	assert_eq!(buf.len(), 5 + serialized_size(&a).unwrap());
+	assert_eq!(from_bytes_checked::<A>(&buf).unwrap(), a);
+
+	// without the check, B decodes just fine
+	let plain = to_bytes(&a).unwrap();
+	assert!(from_bytes::<B>(&plain).is_ok());
+	assert!(matches!(from_bytes_checked::<B>(&buf), Err(Error::SchemaMismatch)));
+
+	// the fingerprint doesn't depend on the value
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct C {
+		a: Option<A>,
+		v: Vec<u8>,
+		e: Expr,
+		m: std::collections::BTreeMap<u32, String>,
+	}
+	let c1 = C {
+		a: None,
+		v: vec![],
+		e: Expr::Lit(1),
+		m: Default::default(),
+	};
+	let c2 = C {
+		a: Some(a),
+		v: vec![1, 2, 3],
+		e: Expr::Neg(Box::new(Expr::Lit(2))),
+		m: vec![(1, "x".to_string())].into_iter().collect(),
+	};
+	let buf1 = to_bytes_with_schema_hash(&c1).unwrap();
+	let buf2 = to_bytes_with_schema_hash(&c2).unwrap();
+	assert_eq!(buf1[..5], buf2[..5]);
+	assert_eq!(from_bytes_checked::<C>(&buf2).unwrap(), c2);
+
+	// field kinds and tuple lengths count
+	let h = |buf: Vec<u8>| buf[..5].to_vec();
+	assert_ne!(
+		h(to_bytes_with_schema_hash(&(1u32, 2u32)).unwrap()),
+		h(to_bytes_with_schema_hash(&(1i32, 2u32)).unwrap())
+	);
+	assert_ne!(
+		h(to_bytes_with_schema_hash(&(1u32, 2u32)).unwrap()),
+		h(to_bytes_with_schema_hash(&(1u32, 2u32, 3u32)).unwrap())
+	);
+}
+
+#[test]
+fn test_flatten() {
+	use std::collections::BTreeMap;
+
+	#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+	enum Color {
+		Red,
+		Rgb(u8, u8, u8),
+		Named { name: String },
+	}
+	#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+	struct Inner {
+		b: i64,
+		c: String,
+		d: Option<Option<f32>>,
+		e: Vec<(u32, bool)>,
+		f: Color,
+		g: Color,
+		h: BTreeMap<String, i8>,
+		i: char,
+		j: (),
+		k: Fixed<i32>,
+		l: ShortStruct,
+	}
+	#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+	struct Outer {
+		a: i32,
+		#[serde(flatten)]
+		inner: Inner,
+		z: Color,
+	}
+
+	let v = Outer {
+		a: -5,
+		inner: Inner {
+			b: -1 << 40,
+			c: "hello".into(),
+			d: Some(None),
+			e: vec![(1, true), (300, false)],
+			f: Color::Red,
+			g: Color::Rgb(1, 2, 3),
+			h: vec![("x".to_string(), -3)].into_iter().collect(),
+			i: 'ß',
+			j: (),
+			k: Fixed(-7),
+			l: ShortStruct { x: 1, y: -2 },
+		},
+		z: Color::Named { name: "teal".into() },
+	};
+	assert_eq!(ser_de!(v.clone()), v);
+
+	// the struct is a map of field names to annotated values, so fields are matched by name
+	#[derive(Deserialize, PartialEq, Debug)]
+	struct PartialInner {
+		c: String,
+		b: i64,
+	}
+	#[derive(Deserialize, PartialEq, Debug)]
+	struct Partial {
+		z: Color,
+		#[serde(flatten)]
+		inner: PartialInner,
+	}
+	let buf = to_bytes(&v).unwrap();
+	assert_eq!(
+		from_bytes::<Partial>(&buf).unwrap(),
+		Partial {
+			z: v.z.clone(),
+			inner: PartialInner {
+				c: "hello".into(),
+				b: -1 << 40
+			},
+		}
+	);
+
+	// values other than the flattened ones can't be decoded without their type
+	let buf = to_bytes(&5u32).unwrap();
+	assert!(matches!(
+		serde_json::Value::deserialize(&mut Deserializer::from_bytes(&buf)),
+		Err(Error::NotSelfDescribing)
+	));
+}
+
+#[test]
+fn test_misplaced_annotation() {
+	// annotations are only accepted where the serializer writes them, so that a value has a single encoding
+	for data in &[&[0x06, 0xd0, 0x02][..], &[0x5e, 0xd0, 0x02]] {
+		assert!(matches!(from_bytes::<u32>(data), Err(Error::UnexpectedWireType)));
+		let mut de = Deserializer::from_bytes(data).canonical(true);
+		assert!(matches!(u32::deserialize(&mut de), Err(Error::UnexpectedWireType)));
+		let mut de = Deserializer::from_bytes(data).canonical(true);
+		assert!(matches!(de.skip_value(), Err(Error::UnexpectedWireType)));
+	}
+	for code in 0..=wire::TYPE_ENUM {
+		let mut buf = Vec::new();
+		wire::write_varint(&mut buf, wire::WireType::Typed, code).unwrap();
+		buf.extend(to_bytes(&42u32).unwrap());
+		assert!(
+			matches!(from_bytes::<u32>(&buf), Err(Error::UnexpectedWireType)),
+			"{}",
+			code
+		);
+	}
+	// a self-describing read takes them anywhere
+	assert_eq!(
+		serde_json::Value::deserialize(&mut Deserializer::from_bytes(&[0x16, 0x28])).unwrap(),
+		serde_json::json!(5)
+	);
+
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct Outer {
+		a: u32,
+		#[serde(flatten)]
+		rest: ShortStruct,
+	}
+	let v = Outer {
+		a: 5,
+		rest: ShortStruct { x: 1, y: -2 },
+	};
+	let buf = to_bytes(&v).unwrap();
+	assert_eq!(from_bytes::<Outer>(&buf).unwrap(), v);
+	Deserializer::from_bytes(&buf).canonical(true).skip_value().unwrap();
+	// the value of `a`, annotated as unsigned
+	let pos = buf.iter().position(|&b| b == 0x16).unwrap();
+	let mut wrong_type = buf.clone();
+	wrong_type[pos] = 0x2e;
+	let mut twice = buf.clone();
+	twice.insert(pos, 0x16);
+	let mut missing = buf.clone();
+	missing.remove(pos);
+	for data in &[&wrong_type, &twice, &missing] {
+		assert!(matches!(from_bytes::<Outer>(data), Err(Error::UnexpectedWireType)));
+	}
+	// the skipping side only knows the structure, which is intact with a wrong type
+	for data in &[twice, missing] {
+		let mut de = Deserializer::from_bytes(data).canonical(true);
+		assert!(matches!(de.skip_value(), Err(Error::UnexpectedWireType)));
+	}
+}
+
+#[test]
+fn test_custom_writer() {
+	// a fixed-size buffer, as could be used without std
+	struct Buf {
+		data: [u8; 16],
+		len: usize,
+	}
+	impl Write for Buf {
+		fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+			let end = self.len + buf.len();
+			self.data
+				.get_mut(self.len..end)
+				.ok_or_else(|| Error::Serialization("buffer full".into()))?
+				.copy_from_slice(buf);
+			self.len = end;
+			Ok(())
+		}
+	}
+
+	let mut w = Buf { data: [0; 16], len: 0 };
+	let v = (1u32, "abc", -5i64);
+	to_writer(&mut w, &v).unwrap();
+	assert_eq!(
+		from_bytes::<(u32, String, i64)>(&w.data[..w.len]).unwrap(),
+		(1, "abc".into(), -5)
+	);
+	assert!(to_writer(&mut w, &[0u8; 16][..]).is_err());
+}
+
+#[test]
+fn test_capacity_from_length() {
+	// sequence length is passed on as size hint, strings and bytes are allocated in one go
+	let v: Vec<u32> = (0..10_000).collect();
+	let v2: Vec<u32> = from_bytes(&to_bytes(&v).unwrap()).unwrap();
+	assert_eq!(v2.capacity(), v.len());
+
+	let s = "x".repeat(1000);
+	let s2: String = from_bytes(&to_bytes(&s).unwrap()).unwrap();
+	assert_eq!(s2.capacity(), s.len());
+
+	let b = serde_bytes::ByteBuf::from(vec![7u8; 1000]);
+	let b2: serde_bytes::ByteBuf = from_bytes(&to_bytes(&b).unwrap()).unwrap();
+	assert_eq!(b2.into_vec().capacity(), 1000);
+}
+
+#[test]
+fn test_nonzero() {
+	use std::num::*;
+
+	macro_rules! check {
+		($t:ty, $v:expr, $zero:expr) => {
+			let v = <$t>::new($v).unwrap();
+			assert_eq!(ser_de!(v), v);
+			let buf = to_bytes(&$zero).unwrap();
+			match from_bytes::<$t>(&buf) {
+				Err(Error::InvalidValue { found, expected, .. }) => {
+					assert_eq!(found, "integer `0`");
+					assert!(expected.contains("nonzero"), "{}", expected);
+				}
+				other => panic!("unexpected {:?}", other),
+			}
+		};
+	}
+	check!(NonZeroU8, 200, 0u8);
+	check!(NonZeroU16, 60000, 0u16);
+	check!(NonZeroU32, 1, 0u32);
+	check!(NonZeroU64, u64::MAX, 0u64);
+	check!(NonZeroU128, u128::MAX, 0u128);
+	check!(NonZeroI8, -100, 0i8);
+	check!(NonZeroI32, i32::MIN, 0i32);
+	check!(NonZeroI64, -1, 0i64);
+	check!(NonZeroI128, i128::MAX, 0i128);
+
+	// in a struct; the error isn't hidden
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct Id {
+		id: NonZeroU32,
+	}
+	let buf = to_bytes(&(0u32,)).unwrap();
+	assert!(matches!(from_bytes::<Id>(&buf), Err(Error::InvalidValue { .. })));
+}
+
+#[test]
+fn test_error_path() {
+	use std::num::NonZeroU32;
+
+	#[derive(Serialize, Deserialize, Debug)]
+	struct Item {
+		name: String,
+		id: NonZeroU32,
+	}
+	#[derive(Serialize, Deserialize, Debug)]
+	struct Inner {
+		items: Vec<Item>,
+	}
+	#[derive(Serialize, Deserialize, Debug)]
+	struct Outer {
+		inner: Inner,
+	}
+
+	// same layout, but a zero id in the second item
+	let buf = to_bytes(&((vec![("a", 1u32), ("b", 0u32)],),)).unwrap();
+	let err = from_bytes::<Outer>(&buf).unwrap_err();
+	match &err {
+		Error::InvalidValue { path, .. } => assert_eq!(path, "inner.items[1].id"),
+		other => panic!("unexpected {:?}", other),
+	}
+	assert!(err.to_string().ends_with(" at inner.items[1].id"), "{}", err);
+
+	// map entries by index, and a short struct in a tuple
+	use std::collections::BTreeMap;
+	let map: BTreeMap<u8, (&str, u32)> = vec![(1, ("x", 5)), (2, ("y", 0))].into_iter().collect();
+	let buf = to_bytes(&map).unwrap();
+	let err = from_bytes::<BTreeMap<u8, Item>>(&buf).unwrap_err();
+	assert!(
+		matches!(err, Error::InvalidValue { ref path, .. } if path == "[1].id"),
+		"{:?}",
+		err
+	);
+	let buf = to_bytes(&(1u8, ("x",))).unwrap();
+	let err = from_bytes::<(u8, Item)>(&buf).unwrap_err();
+	assert!(
+		matches!(err, Error::InvalidLength { len: 1, ref path, .. } if path == "[1]"),
+		"{:?}",
+		err
+	);
+
+	// at the top level, there's no path
+	let buf = to_bytes(&0u32).unwrap();
+	let err = from_bytes::<NonZeroU32>(&buf).unwrap_err();
+	assert!(!err.to_string().contains(" at "), "{}", err);
+}
+
+#[test]
+fn test_collect_str() {
+	use std::fmt;
+
+	// serializes through Display, like many decimal and URL types
+	#[derive(PartialEq, Debug)]
+	struct Decimal(i64, u32);
+	impl fmt::Display for Decimal {
+		fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+			write!(f, "{}.{:0width$}", self.0, 0, width = self.1 as usize)
+		}
+	}
+	impl Serialize for Decimal {
+		fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+			serializer.collect_str(self)
+		}
+	}
+
+	let buf = to_bytes(&Decimal(42, 2)).unwrap();
+	assert_eq!(buf, b"\x2c42.00");
+	assert_eq!(from_bytes::<String>(&buf).unwrap(), "42.00");
+
+	// longer than fits on the stack
+	let buf = to_bytes(&(Decimal(-1, 100), 7u8)).unwrap();
+	let (s, x) = from_bytes::<(String, u8)>(&buf).unwrap();
+	assert_eq!(s, format!("-1.{}", "0".repeat(100)));
+	assert_eq!(x, 7);
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn test_chrono_timestamp() {
+	use chrono::{DateTime, TimeZone, Utc};
+
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct Event {
+		#[serde(with = "crate::chrono_timestamp")]
+		at: DateTime<Utc>,
+		#[serde(with = "crate::chrono_timestamp::secs")]
+		created: DateTime<Utc>,
+	}
+
+	let at = Utc.with_ymd_and_hms(2021, 3, 14, 15, 9, 26).unwrap() + chrono::Duration::nanoseconds(535_897_932);
+	let event = Event { at, created: at };
+	let decoded = ser_de!(event);
+	assert_eq!(decoded.at, at);
+	assert_eq!(decoded.created, Utc.timestamp_opt(at.timestamp(), 0).unwrap());
+	// the same as an i64 of nanoseconds, within its range
+	let nanos = to_bytes(&at.timestamp_nanos_opt().unwrap()).unwrap();
+	assert_eq!(to_bytes(&event).unwrap()[1..11], nanos[..]);
+	assert_eq!(to_bytes(&event).unwrap().len(), 1 + 10 + 5);
+
+	// before the epoch, and far beyond the range of i64 nanoseconds
+	for at in [
+		Utc.with_ymd_and_hms(1900, 1, 1, 0, 0, 0).unwrap(),
+		DateTime::<Utc>::MAX_UTC,
+		DateTime::<Utc>::MIN_UTC,
+	] {
+		let event = Event {
+			at,
+			created: Utc.timestamp_opt(at.timestamp(), 0).unwrap(),
+		};
+		assert_eq!(ser_de!(event), event);
+	}
+	assert!(matches!(
+		from_bytes::<Event>(&to_bytes(&(i128::MAX, 0)).unwrap()),
+		Err(Error::InvalidValue { .. })
+	));
+}
+
+#[cfg(feature = "time")]
+#[test]
+fn test_time_timestamp() {
+	use time::{Duration, OffsetDateTime, UtcOffset};
+
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct Event {
+		#[serde(with = "crate::time_timestamp")]
+		at: OffsetDateTime,
+		#[serde(with = "crate::time_timestamp::secs")]
+		created: OffsetDateTime,
+	}
+
+	let at = OffsetDateTime::from_unix_timestamp(1_615_734_566).unwrap() + Duration::nanoseconds(535_897_932);
+	let event = Event { at, created: at };
+	let decoded = ser_de!(event);
+	assert_eq!(decoded.at, at);
+	assert_eq!(decoded.created.unix_timestamp(), at.unix_timestamp());
+	assert_eq!(decoded.created.nanosecond(), 0);
+	assert_eq!(to_bytes(&event).unwrap().len(), 1 + 10 + 5);
+
+	// the offset is not kept, but the instant is
+	let local = at.to_offset(UtcOffset::from_hms(2, 0, 0).unwrap());
+	let decoded = ser_de!(Event { at: local, created: at });
+	assert_eq!(decoded.at, local);
+	assert_eq!(decoded.at.offset(), UtcOffset::UTC);
+
+	// far future, beyond the range of i64 nanoseconds
+	let far = OffsetDateTime::from_unix_timestamp(200_000_000_000).unwrap();
+	assert_eq!(ser_de!(Event { at: far, created: far }).at, far);
+	assert!(matches!(
+		from_bytes::<Event>(&to_bytes(&(i128::MAX, 0)).unwrap()),
+		Err(Error::InvalidValue { .. })
+	));
+
+	// same encoding as the chrono helpers
+	#[cfg(feature = "chrono")]
+	{
+		#[derive(Deserialize)]
+		struct ChronoEvent {
+			#[serde(with = "crate::chrono_timestamp")]
+			at: chrono::DateTime<chrono::Utc>,
+		}
+		let buf = to_bytes(&Event { at, created: at }).unwrap();
+		let decoded = from_bytes::<ChronoEvent>(&buf).unwrap();
+		assert_eq!(
+			decoded.at.timestamp_nanos_opt().unwrap() as i128,
+			at.unix_timestamp_nanos()
+		);
+	}
+}
+
+#[cfg(feature = "arbitrary")]
+#[test]
+fn test_arbitrary_value() {
+	use arbitrary::{Arbitrary, Unstructured};
+
+	// a simple generator, to get plenty of different inputs
+	let mut state = 0x2545_f491_4f6c_dd1du64;
+	let data: Vec<u8> = (0..1 << 16)
+		.map(|_| {
+			state ^= state << 13;
+			state ^= state >> 7;
+			state ^= state << 17;
+			state as u8
+		})
+		.collect();
+	let mut u = Unstructured::new(&data);
+	let mut nested = 0;
+	while !u.is_empty() {
+		let value = ArbitraryValue::arbitrary(&mut u).unwrap();
+		let buf = value.as_bytes();
+		let mut de = Deserializer::from_bytes(buf).canonical(true);
+		de.skip_value().unwrap();
+		assert_eq!(de.remaining_len(), 0);
+		assert_eq!(probe::<ArbitraryValue>(buf).unwrap().len, buf.len());
+		if wire::read_wiretype(buf[0]) == wire::WireType::Sequence && buf.len() > 10 {
+			nested += 1;
+		}
+	}
+	assert!(nested > 0);
+}
+
+#[test]
+fn test_deserializer_checkpoint() {
+	let mut buf = to_bytes(&1u8).unwrap();
+	to_writer(&mut buf, &("hello", 42i64)).unwrap();
+	let mut de = Deserializer::from_bytes(&buf);
+	assert_eq!(u8::deserialize(&mut de).unwrap(), 1);
+
+	let checkpoint = de.clone();
+	assert!(<(u32, u32)>::deserialize(&mut de).is_err());
+	let mut de = checkpoint.clone();
+	assert_eq!(<(&str, i64)>::deserialize(&mut de).unwrap(), ("hello", 42));
+	assert_eq!(de.remaining_len(), 0);
+
+	// the same region decodes the same way again
+	let mut de = checkpoint;
+	assert_eq!(
+		<(String, i64)>::deserialize(&mut de).unwrap(),
+		("hello".to_string(), 42)
+	);
+	assert_eq!(de.remaining_len(), 0);
+}
+
+#[test]
+fn test_invalid_variant_index() {
+	let mut buf = Vec::new();
+	wire::write_varint(&mut buf, wire::WireType::Variant, 1 << 40).unwrap();
+	buf.extend(to_bytes(&()).unwrap());
+	assert!(matches!(
+		from_bytes::<Expr>(&buf),
+		Err(Error::InvalidVariantIndex(0x100_0000_0000))
+	));
+
+	// an index that fits is left to the enum to reject
+	let mut buf = Vec::new();
+	write_discriminator(&mut buf, u32::MAX).unwrap();
+	buf.extend(to_bytes(&()).unwrap());
+	assert!(matches!(from_bytes::<Expr>(&buf), Err(Error::UnknownVariant(u32::MAX))));
+}
+
+#[test]
+fn test_reinterpret_signedness() {
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct Old {
+		x: u32,
+	}
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct Step {
+		#[serde(with = "crate::reinterpret::as_signed")]
+		x: u32,
+	}
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct New {
+		x: i32,
+	}
+
+	// plain unsigned can't be read as signed
+	let buf = to_bytes(&Old { x: 3 }).unwrap();
+	assert_ne!(from_bytes::<New>(&buf).unwrap(), New { x: 3 });
+
+	for &x in &[0u32, 1, 1000, i32::MAX as u32] {
+		let buf = to_bytes(&Step { x }).unwrap();
+		assert_eq!(from_bytes::<New>(&buf).unwrap(), New { x: x as i32 });
+		assert_eq!(from_bytes::<Step>(&buf).unwrap(), Step { x });
+		assert_eq!(buf, to_bytes(&New { x: x as i32 }).unwrap());
+	}
+	// beyond the overlapping range
+	let buf = to_bytes(&Step { x: u32::MAX }).unwrap();
+	assert!(matches!(from_bytes::<New>(&buf), Err(Error::ValueOverflow { .. })));
+	let buf = to_bytes(&New { x: -1 }).unwrap();
+	assert!(matches!(from_bytes::<Step>(&buf), Err(Error::InvalidValue { .. })));
+
+	// and the other way around
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct Back {
+		#[serde(with = "crate::reinterpret::as_unsigned")]
+		x: i64,
+	}
+	let buf = to_bytes(&Back { x: 1 << 40 }).unwrap();
+	assert_eq!(from_bytes::<u64>(&buf[1..]).unwrap(), 1 << 40);
+	assert_eq!(from_bytes::<Back>(&buf).unwrap(), Back { x: 1 << 40 });
+	assert!(to_bytes(&Back { x: -1 }).is_err());
+}
+
+#[test]
+fn test_extendable_newtype() {
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct Old(#[serde(with = "crate::extendable")] u32);
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct New(u32, #[serde(default)] String);
+
+	let buf = to_bytes(&Old(42)).unwrap();
+	assert_eq!(buf, to_bytes(&(42u32,)).unwrap());
+	assert_eq!(from_bytes::<Old>(&buf).unwrap(), Old(42));
+	assert_eq!(from_bytes::<New>(&buf).unwrap(), New(42, String::new()));
+
+	let buf = to_bytes(&New(42, "foo".into())).unwrap();
+	assert_eq!(from_bytes::<Old>(&buf).unwrap(), Old(42));
+}
+
+#[test]
+#[cfg(feature = "uuid")]
+fn test_uuid() {
+	use uuid::Uuid;
+
+	#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+	struct Record {
+		#[serde(with = "crate::uuid_bytes")]
+		id: Uuid,
+		plain: Uuid,
+	}
+
+	let id = Uuid::from_u128(0x0123_4567_89ab_cdef_0123_4567_89ab_cdef);
+	let buf = to_bytes(&Record { id, plain: id }).unwrap();
+	// sequence header, then twice a header and 16 bytes; the length doesn't fit in the tag byte alone
+	assert_eq!(buf.len(), 1 + 2 * 18);
+	assert_eq!(&buf[3..19], id.as_bytes());
+	assert_eq!(buf[1..19], buf[19..]);
+	assert_eq!(from_bytes::<Record>(&buf).unwrap(), Record { id, plain: id });
+
+	let buf = to_bytes(&(serde_bytes::Bytes::new(&[0; 15]),)).unwrap();
+	assert!(matches!(
+		from_bytes::<Record>(&buf),
+		Err(Error::InvalidLength { len: 15, .. })
+	));
+}
+
+#[test]
+fn test_ip_addr() {
+	use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+	let v4 = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2));
+	assert_eq!(ser_de!(v4), v4);
+	let v6 = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+	assert_eq!(ser_de!(v6), v6);
+	// serde doesn't encode the scope ID (or flow info) of V6 socket addresses
+	for s in &["127.0.0.1:8080", "[::1]:443", "[fe80::1]:22"] {
+		let sock: SocketAddr = s.parse().unwrap();
+		assert_eq!(ser_de!(sock), sock);
+	}
+
+	// variant, then the octets as a tuple
+	let buf = to_bytes(&v4).unwrap();
+	let mut expect = Vec::new();
+	write_discriminator(&mut expect, 0).unwrap();
+	to_writer(&mut expect, &(192u8, 168u8, 1u8, 2u8)).unwrap();
+	assert_eq!(buf, expect);
+	assert_eq!(
+		from_bytes::<Ipv4Addr>(&buf[1..]).unwrap(),
+		Ipv4Addr::new(192, 168, 1, 2)
+	);
+
+	// a socket address is the address, followed by the port
+	let sock: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+	let buf = to_bytes(&sock).unwrap();
+	assert_eq!(
+		from_bytes::<(Ipv4Addr, u16)>(&buf[1..]).unwrap(),
+		(Ipv4Addr::LOCALHOST, 8080)
+	);
+}
+
+#[test]
+fn test_invalid_char() {
+	assert_eq!(ser_de!('\u{10ffff}'), '\u{10ffff}');
+	for &v in &[0xd800u32, 0xdfff, 0x110000, u32::MAX] {
+		let buf = to_bytes(&v).unwrap();
+		assert!(matches!(from_bytes::<char>(&buf), Err(Error::InvalidChar(x)) if x == v));
+	}
+	let buf = to_bytes(&0xd800u32).unwrap();
+	assert_eq!(
+		from_bytes::<char>(&buf).unwrap_err().to_string(),
+		"invalid character 0xd800"
+	);
+}
+
+#[test]
+fn test_from_prefix() {
+	let mut buf = to_bytes(&ShortStruct { x: 1, y: 2 }).unwrap();
+	to_writer(&mut buf, "foo").unwrap();
+
+	let (first, rest) = from_prefix::<ShortStruct>(&buf).unwrap();
+	assert_eq!(first, ShortStruct { x: 1, y: 2 });
+	let (second, rest) = from_prefix::<&str>(rest).unwrap();
+	assert_eq!(second, "foo");
+	assert!(rest.is_empty());
+	assert!(matches!(from_prefix::<u32>(rest), Err(Error::UnexpectedEndOfInput)));
+}
+
+#[test]
+fn test_value_overflow() {
+	#[derive(Serialize, Deserialize, Debug)]
+	struct Narrow {
+		x: i16,
+	}
+	let buf = to_bytes(&(70000i32,)).unwrap();
+	let err = from_bytes::<Narrow>(&buf).unwrap_err();
+	assert!(matches!(
+		err,
+		Error::ValueOverflow {
+			value: Some(70000),
+			target: "i16"
+		}
+	));
+	assert_eq!(err.to_string(), "data value 70000 too large for i16");
+
+	let buf = to_bytes(&u64::MAX).unwrap();
+	assert!(matches!(
+		from_bytes::<u32>(&buf),
+		Err(Error::ValueOverflow { value: Some(v), target: "u32" }) if v == u64::MAX as i128
+	));
+	let buf = to_bytes(&-1i64).unwrap();
+	assert_eq!(from_bytes::<i8>(&buf).unwrap(), -1);
+	let buf = to_bytes(&-200i64).unwrap();
+	assert!(matches!(
+		from_bytes::<i8>(&buf),
+		Err(Error::ValueOverflow {
+			value: Some(-200),
+			target: "i8"
+		})
+	));
+
+	// a varint too long to decode at all
+	let buf = to_bytes(&u128::MAX).unwrap();
+	let err = from_bytes::<u64>(&buf).unwrap_err();
+	assert!(matches!(
+		err,
+		Error::ValueOverflow {
+			value: None,
+			target: "u64"
+		}
+	));
+	assert_eq!(err.to_string(), "data value too large for u64");
+}
+
+#[test]
+fn test_borrowed() {
+	let buf = to_bytes("foobar").unwrap();
+	let s: &str = from_bytes(&buf).unwrap();
+	assert_eq!(s, "foobar");
+
+	// serde::ser::Serialize is not specifically implemented for &[u8]
+	let buf = to_bytes(&serde_bytes::Bytes::new("foobar".as_bytes())).unwrap();
+	let v: &[u8] = from_bytes(&buf).unwrap();
+	assert_eq!(std::str::from_utf8(v).unwrap(), "foobar");
+
+	// other slices can only be serialized
+	let stuff = [1i32, 2i32, 3i32];
+	let buf = to_bytes(&stuff[..]).unwrap();
+	let v: Vec<i32> = from_bytes(&buf).unwrap();
+	assert_eq!(v, vec![1, 2, 3]);
+
+	// embedded in struct
+	#[derive(Debug, Serialize, Deserialize)]
+	struct Foo<'a> {
+		i: i32,
+		s: &'a str,
+		#[serde(with = "serde_bytes")]
+		b: &'a [u8],
+		j: i32,
+	}
+	let f_in = Foo {
+		i: 42,
+		s: "foobar",
+		b: "barfoo".as_bytes(),
+		j: 43,
+	};
+	let buf = to_bytes(&f_in).unwrap();
+	let f_out: Foo = from_bytes(&buf).unwrap();
+	assert_eq!(f_out.i, 42);
+	assert_eq!(f_out.j, 43);
+	assert_eq!(f_out.s, "foobar");
+	assert_eq!(std::str::from_utf8(f_out.b).unwrap(), "barfoo");
+}
+
+#[test]
+fn test_embedded_message() {
+	let sub = to_bytes(&ShortStruct { x: 1, y: 2 }).unwrap();
+
+	// embed as an opaque blob, after another field
+	let mut buf = to_bytes(&42u32).unwrap();
+	write_length_delimited(&mut buf, &sub).unwrap();
+	let (id, rest) = from_prefix::<u32>(&buf).unwrap();
+	assert_eq!(id, 42);
+	let (content, len) = read_length_delimited(rest).unwrap();
+	assert_eq!(content, &sub[..]);
+	assert_eq!(len, rest.len());
+	assert_eq!(from_bytes::<&[u8]>(rest).unwrap(), &sub[..]);
+	assert!(matches!(
+		read_length_delimited(&rest[..len - 1]),
+		Err(Error::UnexpectedEndOfInput)
+	));
+	assert!(matches!(read_length_delimited(&buf), Err(Error::UnexpectedWireType)));
+
+	// or extract the exact bytes of a value embedded as a normal field
+	let buf = to_bytes(&(42u32, ShortStruct { x: 1, y: 2 }, "foo")).unwrap();
+	let mut de = Deserializer::from_bytes(&buf[1..]);
+	de.skip_value().unwrap();
+	assert_eq!(de.read_raw_value().unwrap(), &sub[..]);
+	assert_eq!(<&str>::deserialize(&mut de).unwrap(), "foo");
+	assert!(matches!(de.read_raw_value(), Err(Error::UnexpectedEndOfInput)));
+}
+
+#[test]
+fn test_read_raw_value() {
+	let expr = Expr::Add(Box::new(Expr::Lit(3)), Box::new(Expr::Neg(Box::new(Expr::Lit(-4)))));
+	let values = (LongStruct { x: 1, y: 2, z: 3 }, expr, vec!["a", "bc"]);
+	let buf = to_bytes(&values).unwrap();
+
+	// each raw value decodes on its own to the original, and the raw values make up the whole sequence
+	let mut de = Deserializer::from_bytes(&buf[1..]);
+	let first = de.read_raw_value().unwrap();
+	let second = de.read_raw_value().unwrap();
+	let third = de.read_raw_value().unwrap();
+	assert_eq!(de.remaining_len(), 0);
+	assert_eq!(from_bytes::<LongStruct>(first).unwrap(), values.0);
+	assert_eq!(from_bytes::<Expr>(second).unwrap(), values.1);
+	assert_eq!(from_bytes::<Vec<&str>>(third).unwrap(), values.2);
+	assert_eq!([&buf[..1], first, second, third].concat(), buf);
+}
+
+#[test]
+fn test_packed_floats() {
+	let v = PackedFloats(vec![1.0f32, -2.5, f32::INFINITY, 0.1]);
+	assert_eq!(ser_de!(v.clone()), v);
+	let buf = to_bytes(&v).unwrap();
+	assert_eq!(buf.len(), 2 + 4 * 4);
+	assert_eq!(&buf[2..6], &1.0f32.to_le_bytes());
+	assert!(buf.len() < serialized_size(&v.0).unwrap());
+
+	let v = PackedFloats(vec![1.0f64; 1000]);
+	assert_eq!(ser_de!(v.clone()), v);
+	assert_eq!(serialized_size(&v).unwrap(), 3 + 8000);
+	assert_eq!(ser_de!(PackedFloats::<f64>(vec![])), PackedFloats(vec![]));
+
+	// content must be a multiple of the float size
+	let buf = to_bytes(&serde_bytes::Bytes::new(&[0; 6])).unwrap();
+	assert!(matches!(
+		from_bytes::<PackedFloats<f32>>(&buf),
+		Err(Error::InvalidLength { len: 6, .. })
+	));
+}
+
+#[test]
+fn test_byte_wrappers() {
+	#[derive(Debug, PartialEq, Serialize, Deserialize)]
+	struct Blob<'a> {
+		id: u32,
+		#[serde(borrow)]
+		content: Bytes<'a>,
+		owned: ByteBuf,
+	}
+	let value = Blob {
+		id: 1,
+		content: Bytes(b"foobar"),
+		owned: ByteBuf(vec![1, 2, 3]),
+	};
+	let buf = to_bytes(&value).unwrap();
+	assert_eq!(buf, b"\x1b\x08\x34foobar\x1c\x01\x02\x03");
+	let decoded: Blob = from_bytes(&buf).unwrap();
+	assert_eq!(decoded, value);
+	// borrowed from the input
+	assert_eq!(decoded.content.as_ptr(), buf[3..].as_ptr());
+
+	// the same encoding as serde_bytes
+	let other = to_bytes(&(
+		1u32,
+		serde_bytes::Bytes::new(b"foobar"),
+		serde_bytes::Bytes::new(&[1, 2, 3]),
+	))
+	.unwrap();
+	assert_eq!(buf, other);
+	assert_eq!(
+		from_bytes::<ByteBuf>(&to_bytes(&serde_bytes::Bytes::new(b"foo")).unwrap())
+			.unwrap()
+			.into_vec(),
+		b"foo"
+	);
+}
+
+#[test]
+fn test_cow() {
+	use std::borrow::Cow;
+
+	#[derive(Debug, Serialize, Deserialize)]
+	struct Foo<'a> {
+		#[serde(borrow)]
+		s: Cow<'a, str>,
+		#[serde(borrow, with = "serde_bytes")]
+		b: Cow<'a, [u8]>,
+	}
+	let buf = to_bytes(&Foo {
+		s: "foobar".into(),
+		b: Cow::Borrowed(b"barfoo"),
+	})
+	.unwrap();
+	let f: Foo = from_bytes(&buf).unwrap();
+	assert!(matches!(f.s, Cow::Borrowed("foobar")));
+	assert!(matches!(f.b, Cow::Borrowed(b"barfoo")));
+
+	// owned when the input can't be borrowed
+	#[cfg(feature = "std")]
+	{
+		#[derive(Debug, Serialize, Deserialize)]
+		struct Owned {
+			s: Cow<'static, str>,
+		}
+		let mut stream = Vec::new();
+		to_writer_length_delimited(&mut stream, &Owned { s: "foobar".into() }).unwrap();
+		let f = FrameReader::<_, Owned>::new(&stream[..]).next().unwrap().unwrap();
+		assert!(matches!(f.s, Cow::Owned(ref s) if s == "foobar"));
+	}
+}
+
+#[test]
+fn test_cow_bytes() {
+	use std::borrow::Cow;
+
+	#[derive(Debug, Serialize, Deserialize)]
+	struct Blob<'a> {
+		#[serde(borrow, with = "crate::cow_bytes")]
+		b: Cow<'a, [u8]>,
+		#[serde(borrow)]
+		s: Cow<'a, str>,
+	}
+
+	// borrowed and owned give the same compact encoding
+	let expected = to_bytes(&(serde_bytes::Bytes::new(b"barfoo"), "foobar")).unwrap();
+	let borrowed = Blob {
+		b: Cow::Borrowed(b"barfoo"),
+		s: Cow::Borrowed("foobar"),
+	};
+	let owned = Blob {
+		b: Cow::Owned(b"barfoo".to_vec()),
+		s: Cow::Owned("foobar".into()),
+	};
+	assert_eq!(to_bytes(&borrowed).unwrap(), expected);
+	assert_eq!(to_bytes(&owned).unwrap(), expected);
+	assert_eq!(expected.len(), 1 + 7 + 7);
+
+	let f: Blob = from_bytes(&expected).unwrap();
+	assert!(matches!(f.b, Cow::Borrowed(b"barfoo")));
+	assert!(matches!(f.s, Cow::Borrowed("foobar")));
+}
+
+#[test]
+fn test_smart_pointers() {
+	use std::rc::Rc;
+	use std::sync::Arc;
+
+	let b = Box::new(ShortStruct { x: 1, y: 2 });
+	assert_eq!(ser_de!(b.clone()), b);
+	assert_eq!(to_bytes(&b).unwrap(), to_bytes(&*b).unwrap());
+
+	let a: Arc<str> = "foobar".into();
+	assert_eq!(ser_de!(a.clone()), a);
+	assert_eq!(to_bytes(&a).unwrap(), to_bytes("foobar").unwrap());
+
+	let r: Rc<[i32]> = vec![1, -2, 3].into();
+	assert_eq!(ser_de!(r.clone()), r);
+	assert_eq!(from_bytes::<Vec<i32>>(&to_bytes(&r).unwrap()).unwrap(), vec![1, -2, 3]);
+
+	// nested in a struct, next to borrowed data
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct Foo<'a> {
+		s: &'a str,
+		shared: Arc<ShortStruct>,
+		boxed: Box<[u8]>,
+		inner: Option<Box<Foo<'a>>>,
+	}
+	let foo = Foo {
+		s: "x",
+		shared: Arc::new(ShortStruct { x: 3, y: 4 }),
+		boxed: vec![1, 2].into(),
+		inner: Some(Box::new(Foo {
+			s: "y",
+			shared: Arc::new(ShortStruct { x: 5, y: 6 }),
+			boxed: vec![].into(),
+			inner: None,
+		})),
+	};
+	let buf = to_bytes(&foo).unwrap();
+	assert_eq!(from_bytes::<Foo>(&buf).unwrap(), foo);
+}
+
+#[test]
+fn test_struct() {
+	#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone)]
+	struct Inner {
+		x: i64,
+	}
+	#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone)]
+	struct Foo {
+		x: i32,
+		y: String,
+		z: Vec<i32>,
+		i: Inner,
+	};
+
+	let value = Foo {
+		x: 42,
+		y: "foobar".into(),
+		z: vec![1, 2, 3],
+		i: Inner { x: 43 },
+	};
+	assert_eq!(ser_de!(value.clone()), value);
+
+	#[derive(PartialEq, Eq, Serialize, Deserialize, Debug)]
+	struct Bar(i32);
+	assert_eq!(ser_de!(Bar(42)), Bar(42));
+
+	#[derive(PartialEq, Eq, Serialize, Deserialize, Debug)]
+	struct Bar2(i32, String);
+	assert_eq!(ser_de!(Bar2(42, "foobar".into())), Bar2(42, "foobar".into()));
+
+	#[derive(PartialEq, Eq, Serialize, Deserialize, Debug)]
+	struct FooBar;
+	assert_eq!(ser_de!(FooBar), FooBar);
+}
+
+#[test]
+fn test_struct_vec() {
+	#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone)]
+	struct Inner {
+		x: i64,
+		y: i64,
+	}
+	#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone)]
+	struct Outer {
+		items: Vec<Inner>,
+	}
+
+	let value = Outer {
+		items: vec![Inner { x: 1, y: 2 }, Inner { x: 3, y: 4 }],
+	};
+	assert_eq!(ser_de!(value.clone()), value);
+}
+
+#[test]
+fn test_strict_sequence_len() {
+	// claims 5 elements, but only 3 present
+	let mut buf = to_bytes(&vec![100, 200, 300, 400, 500]).unwrap();
+	buf.truncate(1 + 3 * 2);
+	assert!(matches!(from_bytes::<Vec<i32>>(&buf), Err(Error::UnexpectedEndOfInput)));
+	let mut de = Deserializer::from_bytes(&buf).strict_sequence_len(true);
+	assert!(matches!(
+		Vec::<i32>::deserialize(&mut de),
+		Err(Error::SequenceCountMismatch { claimed: 5, decoded: 3 })
+	));
+
+	// partial element
+	buf.push(0x80);
+	let mut de = Deserializer::from_bytes(&buf).strict_sequence_len(true);
+	assert!(matches!(
+		Vec::<i32>::deserialize(&mut de),
+		Err(Error::SequenceCountMismatch { claimed: 5, decoded: 3 })
+	));
+
+	// the innermost sequence is reported
+	let mut buf = to_bytes(&vec![vec![1, 2], vec![3, 4, 5]]).unwrap();
+	buf.pop();
+	let mut de = Deserializer::from_bytes(&buf).strict_sequence_len(true);
+	assert!(matches!(
+		Vec::<Vec<i32>>::deserialize(&mut de),
+		Err(Error::SequenceCountMismatch { claimed: 3, decoded: 2 })
+	));
+
+	let buf = to_bytes(&vec![1, 2, 3]).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).strict_sequence_len(true);
+	assert_eq!(Vec::<i32>::deserialize(&mut de).unwrap(), vec![1, 2, 3]);
+}
+
+#[test]
+fn test_map() {
+	use std::collections::HashMap;
+
+	let value: HashMap<String, String> = vec![
+		("foo".to_string(), "bar".to_string()),
+		("aap".to_string(), "noot".to_string()),
+	]
+	.into_iter()
+	.collect();
+	assert_eq!(ser_de!(value.clone()), value);
+}
+
+#[test]
+fn test_map_custom_hasher() {
+	use rustc_hash::FxHashMap;
+	use std::collections::HashMap;
+
+	let value: FxHashMap<String, i64> = (0..100).map(|i| (format!("key{}", i), i * 1000)).collect();
+	assert_eq!(ser_de!(value.clone()), value);
+
+	// interchangeable with the default hasher
+	let buf = to_bytes(&value).unwrap();
+	let dest: HashMap<String, i64> = from_bytes(&buf).unwrap();
+	assert_eq!(dest.len(), value.len());
+	assert!(dest.iter().all(|(k, v)| value[k] == *v));
+}
+
+#[test]
+fn test_map_compound_keys() {
+	use std::collections::{BTreeMap, HashMap};
+
+	let value: HashMap<Vec<i32>, String> = vec![
+		(vec![1, 2, 3], "foo".to_string()),
+		(vec![], "bar".to_string()),
+		(vec![-1000], "baz".to_string()),
+	]
+	.into_iter()
+	.collect();
+	assert_eq!(ser_de!(value.clone()), value);
+
+	let value: BTreeMap<(i32, i32), i64> = vec![((1, 2), 3), ((-4, 5), -6), ((70000, 8), 9)].into_iter().collect();
+	assert_eq!(ser_de!(value.clone()), value);
+
+	// keys and values are both compound
+	let value: BTreeMap<(i32, String), Vec<String>> = vec![
+		((1, "a".to_string()), vec!["x".to_string(), "y".to_string()]),
+		((2, "b".to_string()), vec![]),
+	]
+	.into_iter()
+	.collect();
+	assert_eq!(ser_de!(value.clone()), value);
+}
+
+#[test]
+fn test_is_canonical() {
+	use std::collections::{BTreeMap, HashMap};
+
+	let value: BTreeMap<String, i32> = vec![("foo".to_string(), 1), ("bar".to_string(), 2), ("baz".to_string(), 3)]
+		.into_iter()
+		.collect();
+	let buf = to_bytes(&value).unwrap();
+	assert!(is_canonical::<BTreeMap<String, i32>>(&buf).unwrap());
+
+	// same map, but not in sorted order as a HashMap may produce
+	let buf = to_bytes(&("foo", 1, "bar", 2, "baz", 3)).unwrap();
+	assert_eq!(from_bytes::<BTreeMap<String, i32>>(&buf).unwrap(), value);
+	assert!(!is_canonical::<BTreeMap<String, i32>>(&buf).unwrap());
+
+	// padded varint
+	let buf = [(5 << 3) | 0x80, 0x80, 0x00];
+	assert!(!is_canonical::<u32>(&buf).unwrap());
+	assert!(is_canonical::<u32>(&[5 << 3]).unwrap());
+
+	assert!(is_canonical::<u32>(&buf[..2]).is_err());
+
+	// a repeated key doesn't survive decoding, nor does a padded key
+	let buf = to_bytes(&(1u8, 2u8, 1u8, 3u8)).unwrap();
+	assert!(!is_canonical::<HashMap<u8, u8>>(&buf).unwrap());
+	let buf = [0x13, (1 << 3) | 0x80, 0x00, 2 << 3];
+	assert_eq!(from_bytes::<HashMap<u8, u8>>(&buf).unwrap(), HashMap::from([(1, 2)]));
+	assert!(!is_canonical::<HashMap<u8, u8>>(&buf).unwrap());
+	let buf = to_bytes(&HashMap::from([(1u8, 2u8)])).unwrap();
+	assert!(is_canonical::<HashMap<u8, u8>>(&buf).unwrap());
+}
+
+#[test]
+fn test_enum() {
+	#[derive(PartialEq, Eq, Serialize, Deserialize, Debug)]
+	enum E {
+		Unit,
+		Newtype(i32),
+		Tuple(i32, i32),
+		Struct { x: i32, y: i32 },
+	}
+
+	assert_eq!(ser_de!(E::Unit), E::Unit);
+	assert_eq!(ser_de!(E::Newtype(42)), E::Newtype(42));
+	assert_eq!(ser_de!(E::Tuple(42, 43)), E::Tuple(42, 43));
+	assert_eq!(ser_de!(E::Struct { x: 42, y: 43 }), E::Struct { x: 42, y: 43 });
+}
+
+#[test]
+fn test_variant_names() {
+	#[derive(PartialEq, Eq, Serialize, Deserialize, Debug)]
+	enum E {
+		Unit,
+		Tuple(i32, i32),
+		Struct {
+			x: i32,
+			y: i32,
+		},
+		#[serde(other)]
+		Other,
+	}
+
+	// hand-built: variant name followed by the content
+	let mut buf = to_bytes("Struct").unwrap();
+	to_writer(&mut buf, &(42, 43)).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).variant_names(true);
+	assert_eq!(E::deserialize(&mut de).unwrap(), E::Struct { x: 42, y: 43 });
+	assert!(matches!(from_bytes::<E>(&buf), Err(Error::UnexpectedWireType)));
+
+	let mut buf = to_bytes("Tuple").unwrap();
+	to_writer(&mut buf, &(42, 43)).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).variant_names(true);
+	assert_eq!(E::deserialize(&mut de).unwrap(), E::Tuple(42, 43));
+
+	let mut buf = to_bytes("Foo").unwrap();
+	to_writer(&mut buf, &()).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).variant_names(true);
+	assert_eq!(E::deserialize(&mut de).unwrap(), E::Other);
+
+	// discriminators still work, and can be mixed
+	let mut buf = to_bytes(&E::Unit).unwrap();
+	to_writer(&mut buf, "Unit").unwrap();
+	to_writer(&mut buf, &()).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).variant_names(true);
+	assert_eq!(E::deserialize(&mut de).unwrap(), E::Unit);
+	assert_eq!(E::deserialize(&mut de).unwrap(), E::Unit);
+	assert_eq!(de.remaining_len(), 0);
+}
+
+#[derive(PartialEq, Eq, Serialize, Deserialize, Debug)]
+enum Expr {
+	Lit(i32),
+	Add(Box<Expr>, Box<Expr>),
+	Neg(Box<Expr>),
+}
+
+#[test]
+fn test_recursive_enum() {
+	use Expr::*;
+	let value = Add(
+		Box::new(Lit(1)),
+		Box::new(Neg(Box::new(Add(Box::new(Lit(2)), Box::new(Lit(3)))))),
+	);
+	assert_eq!(ser_de!(value), value);
+
+	let mut value = Lit(42);
+	for _ in 0..100 {
+		value = Neg(Box::new(value));
+	}
+	let buf = to_bytes(&value).unwrap();
+	assert_eq!(from_bytes::<Expr>(&buf).unwrap(), value);
+
+	// a lower limit makes the same message fail
+	let mut de = Deserializer::from_bytes(&buf).max_depth(50);
+	assert!(matches!(Expr::deserialize(&mut de), Err(Error::DepthLimitExceeded)));
+}
+
+#[test]
+fn test_depth_limit() {
+	// a million nested Neg variants, built by hand to not need recursion in the test itself
+	let mut buf = vec![(2 << 3) | wire::WireType::Variant as u8; 1_000_000];
+	buf.extend_from_slice(&to_bytes(&Expr::Lit(42)).unwrap());
+	assert!(matches!(from_bytes::<Expr>(&buf), Err(Error::DepthLimitExceeded)));
+
+	// the same when skipping
+	assert!(matches!(from_bytes::<()>(&buf), Err(Error::DepthLimitExceeded)));
+
+	// and nested sequences
+	let buf = vec![(1 << 3) | wire::WireType::Sequence as u8; 1_000_000];
+	assert!(matches!(from_bytes::<()>(&buf), Err(Error::DepthLimitExceeded)));
+}
+
+#[test]
+fn test_unchecked_utf8() {
+	let value = ("grüße", String::from("ok"), 'x');
+	let buf = to_bytes(&value).unwrap();
+	// safety: the input is valid
+	let mut de = unsafe { Deserializer::from_bytes_unchecked_utf8(&buf) };
+	assert_eq!(<(&str, String, char)>::deserialize(&mut de).unwrap(), value);
+
+	// checked by default
+	let buf = to_bytes(&serde_bytes::Bytes::new(&[0xff])).unwrap();
+	assert!(matches!(from_bytes::<&str>(&buf), Err(Error::InvalidUtf8)));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_to_writer_flush() {
+	use std::io::{BufWriter, Cursor};
+
+	let value = (ShortStruct { x: 1, y: 2 }, "foo");
+	let mut w = BufWriter::new(Cursor::new(Vec::new()));
+	assert_eq!(to_writer_len(&mut w, &value).unwrap(), 8);
+	assert!(w.get_ref().get_ref().is_empty());
+	assert_eq!(to_writer_flush(&mut w, &value).unwrap(), 8);
+	let written = w.get_ref().get_ref();
+	assert_eq!(written.len(), 16);
+	assert_eq!(from_bytes::<(ShortStruct, &str)>(&written[8..]).unwrap(), value);
+}
+
+#[test]
+fn test_observer() {
+	#[derive(Serialize)]
+	struct Message {
+		id: u32,
+		name: String,
+		payload: Vec<u8>,
+		score: f64,
+	}
+
+	let value = Message {
+		id: 300,
+		name: "foo".into(),
+		payload: vec![1, 2, 3, 4, 5],
+		score: 0.5,
+	};
+	let mut events = Vec::new();
+	let mut observer = |wt, len| events.push((wt, len));
+	let mut w = ObservedWriter::new(Vec::new(), &mut observer);
+	to_writer(&mut w, &value).unwrap();
+	let buf = w.into_inner();
+	assert_eq!(
+		events,
+		[
+			(wire::WireType::Int, 2),
+			(wire::WireType::Bytes, 4),
+			(wire::WireType::Sequence, 6),
+			(wire::WireType::Fixed64, 9)
+		]
+	);
+	// the struct header plus the fields
+	assert_eq!(buf.len(), 1 + 2 + 4 + 6 + 9);
+	assert_eq!(buf, to_bytes(&value).unwrap());
+
+	// nested values are part of their field; trimmed fields are still reported
+	let mut events = Vec::new();
+	let mut observer = |wt, len| events.push((wt, len));
+	let value = (ShortStruct { x: 1, y: 2 }, LongStruct { x: 1, y: 2, z: 0 });
+	let mut w = ObservedWriter::new(Vec::new(), &mut observer);
+	to_writer(&mut w, &value).unwrap();
+	assert_eq!(events, [(wire::WireType::Sequence, 3), (wire::WireType::Sequence, 4)]);
+	let mut events = Vec::new();
+	let mut observer = |_, len| events.push(len);
+	let mut w = ObservedWriter::new(Vec::new(), &mut observer);
+	let value = LongStruct { x: 1, y: 2, z: 0 };
+	value.serialize(Serializer::new(&mut w).trim_defaults(true)).unwrap();
+	assert_eq!(w.into_inner().len(), 3);
+	assert_eq!(events, [1, 1, 1]);
+
+	// each message is observed in turn
+	let mut events = Vec::new();
+	let mut observer = |_, len| events.push(len);
+	let mut w = ObservedWriter::new(Vec::new(), &mut observer);
+	assert_eq!(to_writer_len(&mut w, &(1u8, "foo")).unwrap(), 6);
+	let mut ser = Serializer::new(&mut w);
+	ser.serialize_value(&[300u32]).unwrap();
+	ser.serialize_value(&7u8).unwrap();
+	assert_eq!(events, [1, 4, 2]);
+}
+
+#[test]
+fn test_ranges() {
+	use std::ops::{Range, RangeFrom, RangeInclusive, RangeTo};
+
+	assert_eq!(ser_de!(0..10), 0..10);
+	assert_eq!(ser_de!(0..=10), 0..=10);
+	assert_eq!(ser_de!(-5i64..), -5i64..);
+	assert_eq!(ser_de!(..7u8), ..7u8);
+	assert_eq!(ser_de!(0.5f64..1.5), 0.5..1.5);
+	assert_eq!(
+		ser_de!(ShortStruct { x: 1, y: 2 }..=ShortStruct { x: 3, y: 4 }).end().x,
+		3
+	);
+
+	// a struct of start and end, for both kinds
+	let buf = to_bytes(&(3u32..10)).unwrap();
+	assert_eq!(buf, [0x13, 0x18, 0x50]);
+	assert_eq!(to_bytes(&(3u32..=10)).unwrap(), buf);
+	assert_eq!(from_bytes::<(u32, u32)>(&buf).unwrap(), (3, 10));
+	assert_eq!(from_bytes::<Range<u32>>(&buf).unwrap(), 3..10);
+	assert_eq!(from_bytes::<RangeInclusive<u32>>(&buf).unwrap(), 3..=10);
+	assert_eq!(from_bytes::<RangeFrom<u32>>(&buf).unwrap(), 3..);
+	assert_eq!(to_bytes(&(..10u32)).unwrap(), [0x0b, 0x50]);
+	assert!(from_bytes::<RangeTo<u32>>(&to_bytes(&(..10u32)).unwrap()).is_ok());
+
+	// an exhausted range decodes as fresh
+	let mut r = 0..=1u8;
+	r.by_ref().for_each(drop);
+	assert!(r.is_empty());
+	assert!(!ser_de!(r).is_empty());
+}
+
+#[test]
+fn test_strict_bools() {
+	let buf = to_bytes(&(true, 1.5f64, Fixed(1u32))).unwrap();
+	let mut de = Deserializer::from_bytes(&buf);
+	assert_eq!(<(bool, bool, bool)>::deserialize(&mut de).unwrap(), (true, true, true));
+	let mut de = Deserializer::from_bytes(&buf).strict_bools(true);
+	assert!(matches!(
+		<(bool, bool)>::deserialize(&mut de),
+		Err(Error::UnexpectedWireType)
+	));
+	let mut de = Deserializer::from_bytes(&buf).strict_bools(true);
+	assert!(<(bool,)>::deserialize(&mut de).unwrap().0);
+
+	// a unit still maps to false
+	let buf = to_bytes(&((), false)).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).strict_bools(true);
+	assert_eq!(<(bool, bool)>::deserialize(&mut de).unwrap(), (false, false));
+
+	// the wire type is checked past the annotation of a field next to a flattened one
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct Outer {
+		on: bool,
+		#[serde(flatten)]
+		rest: ShortStruct,
+	}
+	let v = Outer {
+		on: true,
+		rest: ShortStruct { x: 1, y: -2 },
+	};
+	let buf = to_bytes(&v).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).strict_bools(true);
+	assert_eq!(Outer::deserialize(&mut de).unwrap(), v);
+}
+
+#[test]
+fn test_variant_to_bool() {
+	#[derive(Serialize)]
+	enum Flag {
+		Off,
+		On,
+	}
+
+	// unit variants map to false for the first variant, and true otherwise
+	let buf = to_bytes(&(None::<()>, Some(()), Flag::Off, Flag::On)).unwrap();
+	assert_eq!(
+		from_bytes::<(bool, bool, bool, bool)>(&buf).unwrap(),
+		(false, true, false, true)
+	);
+	// the unit path still works, as do integers
+	assert_eq!(
+		from_bytes::<(bool, bool, bool)>(&to_bytes(&((), 0u8, 7u32)).unwrap()).unwrap(),
+		(false, false, true)
+	);
+
+	// a variant with content isn't a bool
+	assert!(matches!(
+		from_bytes::<bool>(&to_bytes(&Some(5u32)).unwrap()),
+		Err(Error::InvalidValue { .. })
+	));
+	assert!(matches!(
+		from_bytes::<bool>(&to_bytes(&Some("x")).unwrap()),
+		Err(Error::InvalidValue { .. })
+	));
+	// nor with strict bools
+	let buf = to_bytes(&None::<()>).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).strict_bools(true);
+	assert!(matches!(bool::deserialize(&mut de), Err(Error::UnexpectedWireType)));
+}
+
+#[test]
+fn test_zero_sized_fields() {
+	use std::marker::PhantomData;
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Unit;
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Tagged<T> {
+		id: u32,
+		marker: PhantomData<T>,
+		unit: (),
+		unit_struct: Unit,
+	}
+	let v = Tagged::<String> {
+		id: 7,
+		marker: PhantomData,
+		unit: (),
+		unit_struct: Unit,
+	};
+	assert_eq!(ser_de!(v), v);
+	// a byte per marker
+	assert_eq!(to_bytes(&v).unwrap(), [0x23, 0x38, 0x00, 0x00, 0x00]);
+
+	// unless skipped, which changes the layout
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Skipped<T> {
+		id: u32,
+		#[serde(skip)]
+		marker: PhantomData<T>,
+	}
+	let v = Skipped::<String> {
+		id: 7,
+		marker: PhantomData,
+	};
+	assert_eq!(to_bytes(&v).unwrap(), [0x0b, 0x38]);
+	assert_eq!(ser_de!(v), v);
+}
+
+#[test]
+fn test_position() {
+	let buf = to_bytes(&(1u8, "foo", ShortStruct { x: 300, y: -1 }, 2.5f64)).unwrap();
+	let mut de = Deserializer::from_bytes(&buf[1..]);
+	let mut steps = vec![];
+	macro_rules! step {
+		($t:ty) => {
+			<$t>::deserialize(&mut de).unwrap();
+			assert_eq!(de.position() + de.remaining_len(), de.total_len());
+			steps.push(de.position());
+		};
+	}
+	assert_eq!((de.position(), de.total_len()), (0, buf.len() - 1));
+	step!(u8);
+	step!(&str);
+	step!(ShortStruct);
+	step!(f64);
+	assert_eq!(steps, [1, 5, 9, 18]);
+	assert_eq!(de.remaining_len(), 0);
+
+	// a checkpoint keeps its position
+	let mut de = Deserializer::from_bytes(&buf);
+	de.skip_value().unwrap();
+	let checkpoint = de.clone();
+	assert_eq!((checkpoint.position(), checkpoint.total_len()), (buf.len(), buf.len()));
+}
+
+#[test]
+fn test_message_buf() {
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	enum Message<'a> {
+		Hello { name: &'a str },
+		Data(Vec<u32>),
+		Bye,
+	}
+
+	let messages = vec![
+		Message::Hello { name: "foo" },
+		Message::Data(vec![1, 2, 3]),
+		Message::Data(vec![]),
+		Message::Bye,
+	];
+	let mut batch = MessageBuf::new();
+	assert!(batch.is_empty());
+	for m in &messages {
+		batch.push(m).unwrap();
+	}
+	assert_eq!(batch.len(), 4);
+	let decoded: Vec<Message> = batch.iter().collect::<Result<_>>().unwrap();
+	assert_eq!(decoded, messages);
+
+	// the same framing as to_writer_length_delimited
+	let mut stream = Vec::new();
+	for m in &messages {
+		to_writer_length_delimited(&mut stream, m).unwrap();
+	}
+	assert_eq!(batch.as_bytes(), &stream[..]);
+
+	// a damaged buffer gives a single error
+	let bytes = batch.into_bytes();
+	let mut iter = MessageIter::<Message>::new(&bytes[..bytes.len() - 1]);
+	assert_eq!(iter.by_ref().take(3).count(), 3);
+	assert!(matches!(iter.next(), Some(Err(Error::UnexpectedEndOfInput))));
+	assert!(iter.next().is_none());
+}
+
+#[test]
+fn test_strict_tuple_len() {
+	let buf = to_bytes(&(1u8, 2u8)).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).strict_tuple_len(true);
+	assert!(matches!(
+		<(u8, u8, u8)>::deserialize(&mut de),
+		Err(Error::TupleLengthMismatch { expected: 3, found: 2 })
+	));
+	let buf = to_bytes(&(1u8, 2u8, 3u8)).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).strict_tuple_len(true);
+	assert_eq!(<(u8, u8, u8)>::deserialize(&mut de).unwrap(), (1, 2, 3));
+	let mut de = Deserializer::from_bytes(&buf).strict_tuple_len(true);
+	assert!(matches!(
+		<[u8; 2]>::deserialize(&mut de),
+		Err(Error::TupleLengthMismatch { expected: 2, found: 3 })
+	));
+
+	// lenient by default
+	assert_eq!(from_bytes::<[u8; 2]>(&buf).unwrap(), [1, 2]);
+
+	// structs and tuple structs still evolve
+	let buf = to_bytes(&LongStruct { x: 1, y: 2, z: 3 }).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).strict_tuple_len(true);
+	assert_eq!(ShortStruct::deserialize(&mut de).unwrap(), ShortStruct { x: 1, y: 2 });
+	let buf = to_bytes(&LongTuple(1, 2, 3)).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).strict_tuple_len(true);
+	assert_eq!(ShortTuple::deserialize(&mut de).unwrap(), ShortTuple(1, 2));
+}
+
+#[test]
+fn test_max_string_len() {
+	let buf = to_bytes(&("hello", serde_bytes::Bytes::new(b"longer bytes"))).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).max_string_len(5);
+	let (s, b): (String, serde_bytes::ByteBuf) = Deserialize::deserialize(&mut de).unwrap();
+	assert_eq!((s.as_str(), &b[..]), ("hello", &b"longer bytes"[..]));
+	let mut de = Deserializer::from_bytes(&buf).max_string_len(4);
+	assert!(matches!(
+		<(&str, &[u8])>::deserialize(&mut de),
+		Err(Error::StringTooLong { len: 5, max: 4 })
+	));
+
+	// a huge claimed length fails before reading anything, and before the sequence limit applies
+	let mut buf = Vec::new();
+	wire::write_varint(&mut buf, wire::WireType::Bytes, 1 << 30).unwrap();
+	buf.extend_from_slice(b"foo");
+	let mut de = Deserializer::from_bytes(&buf).max_string_len(1000).max_seq_len(10);
+	assert!(matches!(
+		String::deserialize(&mut de),
+		Err(Error::StringTooLong { len, max: 1000 }) if len == 1 << 30
+	));
+	assert_eq!(de.remaining_len(), 3);
+}
+
+#[test]
+fn test_max_seq_len() {
+	use std::collections::HashMap;
+
+	let v: Vec<u32> = (0..100).collect();
+	let buf = to_bytes(&v).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).max_seq_len(100);
+	assert_eq!(Vec::<u32>::deserialize(&mut de).unwrap(), v);
+	let mut de = Deserializer::from_bytes(&buf).max_seq_len(99);
+	assert!(matches!(
+		Vec::<u32>::deserialize(&mut de),
+		Err(Error::SequenceTooLong { len: 100, max: 99 })
+	));
+
+	// maps are limited by their number of entries
+	let m: HashMap<u32, u32> = (0..100).map(|i| (i, i)).collect();
+	let buf = to_bytes(&m).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).max_seq_len(100);
+	assert_eq!(HashMap::<u32, u32>::deserialize(&mut de).unwrap(), m);
+
+	// a huge claimed length fails before reading anything
+	let mut buf = Vec::new();
+	wire::write_varint(&mut buf, wire::WireType::Sequence, u64::MAX - 1).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).max_seq_len(1000);
+	assert!(matches!(
+		HashMap::<u32, u32>::deserialize(&mut de),
+		Err(Error::SequenceTooLong { len, max: 1000 }) if len == (u64::MAX / 2) as usize
+	));
+
+	// an odd length is still an invalid map, also beyond the limit
+	for &n in &[3, u64::MAX] {
+		let mut buf = Vec::new();
+		wire::write_varint(&mut buf, wire::WireType::Sequence, n).unwrap();
+		let mut de = Deserializer::from_bytes(&buf).max_seq_len(1000);
+		assert!(matches!(
+			HashMap::<u32, u32>::deserialize(&mut de),
+			Err(Error::InvalidMap)
+		));
+	}
+
+	// tuples are not affected
+	let buf = to_bytes(&(1, 2, 3)).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).max_seq_len(1);
+	assert_eq!(<(i32, i32, i32)>::deserialize(&mut de).unwrap(), (1, 2, 3));
+}
+
+#[test]
+fn test_deserialize_any_sequence() {
+	use serde::de::{IgnoredAny, SeqAccess, Visitor};
+	use std::fmt;
+
+	// counts the elements of any sequence, without knowing their type
+	struct Count(usize);
+	impl<'de> Deserialize<'de> for Count {
+		fn deserialize<D: serde::Deserializer<'de>>(d: D) -> std::result::Result<Self, D::Error> {
+			struct CountVisitor;
+			impl<'de> Visitor<'de> for CountVisitor {
+				type Value = Count;
+				fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+					f.write_str("a sequence")
+				}
+				fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<Count, A::Error> {
+					let mut n = 0;
+					while seq.next_element::<IgnoredAny>()?.is_some() {
+						n += 1;
+					}
+					Ok(Count(n))
+				}
+			}
+			d.deserialize_any(CountVisitor)
+		}
+	}
+
+	let buf = to_bytes(&vec![ShortStruct { x: 1, y: 2 }; 5]).unwrap();
+	assert_eq!(from_bytes::<Count>(&buf).unwrap().0, 5);
+	let buf = to_bytes(&(1u8, "foo", vec![1.5f64])).unwrap();
+	assert_eq!(from_bytes::<Count>(&buf).unwrap().0, 3);
+
+	// byte strings show as str when valid UTF-8, and as bytes otherwise
+	assert_eq!(
+		from_bytes::<serde_json::Value>(&to_bytes("foo").unwrap()).unwrap(),
+		"foo"
+	);
+	let buf = to_bytes(&serde_bytes::Bytes::new(&[0xff, 0])).unwrap();
+	let err = from_bytes::<serde_json::Value>(&buf).unwrap_err();
+	assert!(err.to_string().contains("byte array"), "{}", err);
+
+	// scalars remain ambiguous, also inside a sequence
+	assert!(matches!(
+		from_bytes::<serde_json::Value>(&[0x08]),
+		Err(Error::NotSelfDescribing)
+	));
+	let buf = to_bytes(&vec![1u8, 2]).unwrap();
+	assert!(matches!(
+		from_bytes::<serde_json::Value>(&buf),
+		Err(Error::NotSelfDescribing)
+	));
+}
+
+#[test]
+fn test_reserved_wire_type() {
+	use serde::de::IgnoredAny;
+	use std::collections::HashMap;
+
+	// entry points that aren't reached through a standard type
+	struct Any;
+	impl<'de> Deserialize<'de> for Any {
+		fn deserialize<D: serde::Deserializer<'de>>(d: D) -> std::result::Result<Self, D::Error> {
+			d.deserialize_any(IgnoredAny).map(|_| Any)
+		}
+	}
+	struct Identifier;
+	impl<'de> Deserialize<'de> for Identifier {
+		fn deserialize<D: serde::Deserializer<'de>>(d: D) -> std::result::Result<Self, D::Error> {
+			d.deserialize_identifier(IgnoredAny).map(|_| Identifier)
+		}
+	}
+	#[derive(Deserialize)]
+	struct Unit;
+	#[derive(Deserialize)]
+	struct Newtype(#[allow(dead_code)] u32);
+	#[derive(Deserialize)]
+	struct Compact(
+		#[serde(with = "crate::duration_nanos")]
+		#[allow(dead_code)]
+		std::time::Duration,
+	);
+
+	fn check_reserved<T: DeserializeOwned>() {
+		let name = std::any::type_name::<T>();
+		// reserved, also with varint bits set; an annotation followed by reserved; and annotations with reserved type
+		// codes (12 and 2044)
+		for data in &[&[7u8][..], &[0xff, 0x01], &[6, 7], &[0x66, 0x00], &[0xe6, 0x7f, 0x00]] {
+			assert!(
+				matches!(from_bytes::<T>(data), Err(Error::UnexpectedWireType)),
+				"{} {:?}",
+				name,
+				data
+			);
+		}
+	}
+	fn check<T: DeserializeOwned>() {
+		check_reserved::<T>();
+		// wire type 6 is an annotation, which typed reads only accept inside a map of unknown length
+		assert!(
+			matches!(from_bytes::<T>(&[6]), Err(Error::UnexpectedWireType)),
+			"{}",
+			std::any::type_name::<T>()
+		);
+	}
+
+	check::<bool>();
+	check::<i8>();
+	check::<i16>();
+	check::<i32>();
+	check::<i64>();
+	check::<i128>();
+	check::<u8>();
+	check::<u16>();
+	check::<u32>();
+	check::<u64>();
+	check::<u128>();
+	check::<f32>();
+	check::<f64>();
+	check::<char>();
+	check::<String>();
+	check::<serde_bytes::ByteBuf>();
+	check::<Option<u8>>();
+	check::<()>();
+	check::<Unit>();
+	check::<Newtype>();
+	check::<Compact>();
+	check::<Vec<u8>>();
+	check::<(u8, u8)>();
+	check::<ShortTuple>();
+	check::<ShortStruct>();
+	check::<HashMap<u8, u8>>();
+	check::<Expr>();
+	check::<IgnoredAny>();
+	check::<Identifier>();
+	// a self-describing read takes the annotation anywhere, which then needs a value to follow
+	check_reserved::<Any>();
+	assert!(matches!(from_bytes::<Any>(&[6]), Err(Error::UnexpectedEndOfInput)));
+}
+
+#[test]
+fn test_peek_wire_type() {
+	use wire::WireType;
+
+	let mut buf = to_bytes(&42u32).unwrap();
+	to_writer(&mut buf, &Fixed(42u32)).unwrap();
+	to_writer(&mut buf, &Fixed(42u64)).unwrap();
+	to_writer(&mut buf, &(1, 2)).unwrap();
+	to_writer(&mut buf, "foo").unwrap();
+	to_writer(&mut buf, &Some(1)).unwrap();
+	buf.extend(&[WireType::Typed as u8, WireType::Int as u8]);
+	buf.push(WireType::Reserved as u8);
+
+	let mut de = Deserializer::from_bytes(&buf);
+	for &expect in &[
+		WireType::Int,
+		WireType::Fixed32,
+		WireType::Fixed64,
+		WireType::Sequence,
+		WireType::Bytes,
+		WireType::Variant,
+		WireType::Typed,
+		WireType::Reserved,
+	] {
+		let len = de.remaining_len();
+		assert_eq!(de.peek_wire_type().unwrap(), expect);
+		assert_eq!(de.peek_wire_type().unwrap(), expect);
+		assert_eq!(de.remaining_len(), len);
+		match expect {
+			// a bare annotation is only valid inside a map of unknown length
+			WireType::Typed => {
+				assert!(matches!(de.skip_value(), Err(Error::UnexpectedWireType)));
+				de = Deserializer::from_bytes(&buf[buf.len() - 1..]);
+			}
+			WireType::Reserved => {}
+			_ => de.skip_value().unwrap(),
+		}
+	}
+	let mut de = Deserializer::from_bytes(&[]);
+	assert!(matches!(de.peek_wire_type(), Err(Error::UnexpectedEndOfInput)));
+	assert!(de.skip_value().is_err());
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_seq_reader() {
+	use std::io::Cursor;
+
+	let v: Vec<Expr> = (0..100_000)
+		.map(|i| match i % 3 {
+			0 => Expr::Lit(i),
+			1 => Expr::Neg(Box::new(Expr::Lit(-i))),
+			_ => Expr::Add(Box::new(Expr::Lit(i)), Box::new(Expr::Lit(1))),
+		})
+		.collect();
+	let mut buf = to_bytes(&v).unwrap();
+	to_writer(&mut buf, &"tail").unwrap();
+	let mut reader = SeqReader::<_, Expr>::new(Cursor::new(&buf)).unwrap();
+	assert_eq!(reader.size_hint(), (0, Some(v.len())));
+	let decoded: Vec<Expr> = reader.by_ref().collect::<Result<_>>().unwrap();
+	let (expected, rest) = from_prefix::<Vec<Expr>>(&buf).unwrap();
+	assert_eq!(decoded, expected);
+	let cursor = reader.into_inner();
+	assert_eq!(&buf[cursor.position() as usize..], rest);
+
+	// all wire types, including annotations and wide integers
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct Item {
+		id: u128,
+		ratio: f32,
+		scale: f64,
+		#[serde(flatten)]
+		inner: ShortStruct,
+		name: String,
+		#[serde(with = "serde_bytes")]
+		data: Vec<u8>,
+	}
+	let items: Vec<Item> = (0..100)
+		.map(|i| Item {
+			id: u128::MAX - i,
+			ratio: i as f32,
+			scale: -(i as f64),
+			inner: ShortStruct { x: i as i32, y: -1 },
+			name: "x".repeat(i as usize),
+			data: vec![i as u8; 300],
+		})
+		.collect();
+	let buf = to_bytes(&items).unwrap();
+	let decoded: Vec<Item> = SeqReader::new(Cursor::new(&buf))
+		.unwrap()
+		.collect::<Result<_>>()
+		.unwrap();
+	assert_eq!(decoded, items);
+
+	// a truncated stream fails at the last element
+	let mut reader = SeqReader::<_, Item>::new(Cursor::new(&buf[..buf.len() - 1])).unwrap();
+	assert!(reader.by_ref().take(99).all(|r| r.is_ok()));
+	assert!(matches!(reader.next(), Some(Err(Error::UnexpectedEndOfInput))));
+	assert!(reader.next().is_none());
+
+	assert!(matches!(
+		SeqReader::<_, u32>::new(Cursor::new(&to_bytes(&"foo").unwrap())),
+		Err(Error::UnexpectedWireType)
+	));
+	// sequences of one element, nested
+	let deep = vec![0x0b; 200];
+	let mut reader = SeqReader::<_, u32>::new(Cursor::new(&deep)).unwrap();
+	assert!(matches!(reader.next(), Some(Err(Error::DepthLimitExceeded))));
+	// and a long run of annotations
+	let mut deep = vec![0x0b];
+	deep.resize(1 << 21, 0x06);
+	let mut reader = SeqReader::<_, u32>::new(Cursor::new(&deep)).unwrap();
+	assert!(matches!(reader.next(), Some(Err(Error::DepthLimitExceeded))));
+
+	// flattened structs have annotated enums, identified by name
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct Flat {
+		expr: Expr,
+		#[serde(flatten)]
+		inner: ShortStruct,
+	}
+	let items: Vec<Flat> = (0..3)
+		.map(|i| Flat {
+			expr: Expr::Add(Box::new(Expr::Lit(i)), Box::new(Expr::Neg(Box::new(Expr::Lit(-i))))),
+			inner: ShortStruct { x: i, y: -i },
+		})
+		.collect();
+	let buf = to_bytes(&items).unwrap();
+	let decoded: Vec<Flat> = SeqReader::new(Cursor::new(&buf))
+		.unwrap()
+		.collect::<Result<_>>()
+		.unwrap();
+	assert_eq!(decoded, items);
+}
+
+#[test]
+fn test_serializer_reuse() {
+	let values = [ShortStruct { x: 1, y: 2 }, ShortStruct { x: -5, y: 1000 }];
+	let mut buf = Vec::new();
+	let mut ser = Serializer::new(&mut buf).big_endian(true);
+	for v in &values {
+		ser.serialize_value(v).unwrap();
+	}
+	ser.serialize_value(&1.5f64).unwrap();
+	let mut expected = to_bytes(&values[0]).unwrap();
+	expected.extend(to_bytes(&values[1]).unwrap());
+	expected.push(2);
+	expected.extend(1.5f64.to_be_bytes());
+	assert_eq!(buf, expected);
+
+	let mut ser = Serializer::new(&mut buf);
+	ser.get_mut().clear();
+	ser.serialize_value(&values[1]).unwrap();
+	assert_eq!(buf, to_bytes(&values[1]).unwrap());
 }
 
 #[test]
-fn test_struct() {
-	#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone)]
-	struct Inner {
-		x: i64,
+fn test_fixed_bytes() {
+	let mut hash = [0u8; 32];
+	for (i, b) in hash.iter_mut().enumerate() {
+		*b = (i * 37) as u8;
 	}
-	#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone)]
-	struct Foo {
-		x: i32,
-		y: String,
-		z: Vec<i32>,
-		i: Inner,
-	};
+	let buf = to_bytes(&FixedBytes(hash)).unwrap();
+	assert_eq!(buf.len(), 34);
+	assert_eq!(&buf[2..], &hash[..]);
+	assert_eq!(ser_de!(FixedBytes(hash)), FixedBytes(hash));
+	assert!(to_bytes(&hash).unwrap().len() > 50);
 
-	let value = Foo {
-		x: 42,
-		y: "foobar".into(),
-		z: vec![1, 2, 3],
-		i: Inner { x: 43 },
-	};
-	assert_eq!(ser_de!(value.clone()), value);
+	// same encoding as a byte string
+	assert_eq!(buf, to_bytes(&serde_bytes::Bytes::new(&hash)).unwrap());
+	assert_eq!(ser_de!(FixedBytes([1u8, 2, 3])), FixedBytes([1, 2, 3]));
+	assert_eq!(ser_de!(FixedBytes([])), FixedBytes([]));
 
-	#[derive(PartialEq, Eq, Serialize, Deserialize, Debug)]
-	struct Bar(i32);
-	assert_eq!(ser_de!(Bar(42)), Bar(42));
+	let buf = to_bytes(&serde_bytes::Bytes::new(&hash[..31])).unwrap();
+	assert!(matches!(
+		from_bytes::<FixedBytes<32>>(&buf),
+		Err(Error::WrongLength {
+			expected: 32,
+			found: 31
+		})
+	));
+	let buf = to_bytes(&serde_bytes::Bytes::new(&[0; 33])).unwrap();
+	assert!(matches!(
+		from_bytes::<FixedBytes<32>>(&buf),
+		Err(Error::WrongLength {
+			expected: 32,
+			found: 33
+		})
+	));
 
-	#[derive(PartialEq, Eq, Serialize, Deserialize, Debug)]
-	struct Bar2(i32, String);
-	assert_eq!(ser_de!(Bar2(42, "foobar".into())), Bar2(42, "foobar".into()));
+	// the default array encoding can be read
+	assert_eq!(
+		from_bytes::<FixedBytes<32>>(&to_bytes(&hash).unwrap()).unwrap(),
+		FixedBytes(hash)
+	);
+	assert!(matches!(
+		from_bytes::<FixedBytes<32>>(&to_bytes(&[0u8; 31]).unwrap()),
+		Err(Error::WrongLength {
+			expected: 32,
+			found: 31
+		})
+	));
+	assert!(matches!(
+		from_bytes::<FixedBytes<32>>(&to_bytes(&1u8).unwrap()),
+		Err(Error::UnexpectedWireType)
+	));
 
-	#[derive(PartialEq, Eq, Serialize, Deserialize, Debug)]
-	struct FooBar;
-	assert_eq!(ser_de!(FooBar), FooBar);
+	// other formats
+	let buf = bincode::serialize(&FixedBytes(hash)).unwrap();
+	assert_eq!(bincode::deserialize::<FixedBytes<32>>(&buf).unwrap(), FixedBytes(hash));
+	let json = serde_json::to_string(&FixedBytes([1u8, 2, 3])).unwrap();
+	assert_eq!(json, "[1,2,3]");
+	assert_eq!(
+		serde_json::from_str::<FixedBytes<3>>(&json).unwrap(),
+		FixedBytes([1, 2, 3])
+	);
+	assert!(serde_json::from_str::<FixedBytes<3>>("[1,2]").is_err());
 }
 
 #[test]
-fn test_struct_vec() {
-	#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone)]
-	struct Inner {
-		x: i64,
-		y: i64,
+#[cfg(feature = "half")]
+fn test_f16() {
+	use half::f16;
+
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct Half {
+		#[serde(with = "crate::f16_bytes")]
+		value: f16,
 	}
-	#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone)]
-	struct Outer {
-		items: Vec<Inner>,
+	for &v in &[0.0f32, -0.0, 1.0, -2.5, 0.1, 65504.0, f32::INFINITY] {
+		let h = f16::from_f32(v);
+		let mut buf = Vec::new();
+		crate::f16_bytes::serialize(&h, Serializer::new(&mut buf)).unwrap();
+		assert_eq!(buf, [0x14, h.to_le_bytes()[0], h.to_le_bytes()[1]]);
+		let back = ser_de!(Half { value: h }).value;
+		assert_eq!(back.to_bits(), h.to_bits());
 	}
+	assert!(ser_de!(Half { value: f16::NAN }).value.is_nan());
 
-	let value = Outer {
-		items: vec![Inner { x: 1, y: 2 }, Inner { x: 3, y: 4 }],
+	// evolution from wider floats
+	let buf = to_bytes(&(0.1f32,)).unwrap();
+	assert_eq!(from_bytes::<Half>(&buf).unwrap().value, f16::from_f32(0.1));
+	let buf = to_bytes(&(-2.5f64,)).unwrap();
+	assert_eq!(from_bytes::<Half>(&buf).unwrap().value, f16::from_f32(-2.5));
+	let mut de = Deserializer::from_bytes(&[1, 0x3f, 0, 0, 0]).big_endian(true);
+	assert_eq!(crate::f16_bytes::deserialize(&mut de).unwrap(), f16::from_f32(0.5));
+
+	let buf = to_bytes(&(serde_bytes::Bytes::new(&[1, 2, 3]),)).unwrap();
+	assert!(matches!(from_bytes::<Half>(&buf), Err(Error::InvalidLength { .. })));
+	let buf = to_bytes(&(1u32,)).unwrap();
+	assert!(matches!(from_bytes::<Half>(&buf), Err(Error::UnexpectedWireType)));
+
+	// only f16_bytes accepts floats; other bytes consumers don't
+	let buf = to_bytes(&1.5f32).unwrap();
+	assert!(matches!(
+		from_bytes::<serde_bytes::ByteBuf>(&buf),
+		Err(Error::UnexpectedWireType)
+	));
+	assert!(matches!(from_bytes::<ByteBuf>(&buf), Err(Error::UnexpectedWireType)));
+}
+
+#[test]
+fn test_probe() {
+	use crate::wire::WireType;
+
+	#[derive(Serialize)]
+	struct Message {
+		id: u32,
+		ratio: f64,
+		name: &'static str,
+		tags: Vec<u32>,
+		expr: Expr,
+	}
+	let msg = Message {
+		id: 1,
+		ratio: 0.5,
+		name: "foo",
+		tags: vec![1, 2],
+		expr: Expr::Lit(3),
 	};
-	assert_eq!(ser_de!(value.clone()), value);
+	let mut buf = to_bytes(&msg).unwrap();
+	let len = buf.len();
+	buf.extend([0x08, 0x08]);
+	assert_eq!(
+		probe::<Message>(&buf).unwrap(),
+		ProbeReport {
+			wire_type: WireType::Sequence,
+			fields: vec![
+				WireType::Int,
+				WireType::Fixed64,
+				WireType::Bytes,
+				WireType::Sequence,
+				WireType::Variant
+			],
+			len,
+		}
+	);
+
+	// a newer version shows more fields
+	let report = probe::<ShortStruct>(&to_bytes(&LongStruct::default()).unwrap()).unwrap();
+	assert_eq!(report.fields.len(), 3);
+	let report = probe::<ShortStruct>(&to_bytes(&ShortStruct { x: 0, y: 0 }).unwrap()).unwrap();
+	assert_eq!(report.fields.len(), 2);
+
+	// annotations of flattened structs are looked through
+	#[derive(Serialize)]
+	struct Outer {
+		#[serde(flatten)]
+		inner: ShortStruct,
+	}
+	let report = probe::<Outer>(
+		&to_bytes(&Outer {
+			inner: ShortStruct { x: 1, y: 2 },
+		})
+		.unwrap(),
+	)
+	.unwrap();
+	assert_eq!(
+		report.fields,
+		vec![WireType::Bytes, WireType::Int, WireType::Bytes, WireType::Int]
+	);
+	// including the variant name of an enum
+	#[derive(Serialize)]
+	struct Inner {
+		expr: Expr,
+	}
+	#[derive(Serialize)]
+	struct WithEnum {
+		#[serde(flatten)]
+		inner: Inner,
+	}
+	let value = WithEnum {
+		inner: Inner {
+			expr: Expr::Neg(Box::new(Expr::Lit(1))),
+		},
+	};
+	let report = probe::<WithEnum>(&to_bytes(&value).unwrap()).unwrap();
+	assert_eq!(report.fields, vec![WireType::Bytes, WireType::Variant]);
+
+	let report = probe::<f32>(&to_bytes(&42.0f32).unwrap()).unwrap();
+	assert_eq!(
+		(report.wire_type, report.fields.len(), report.len),
+		(WireType::Fixed32, 0, 5)
+	);
+
+	assert!(matches!(
+		probe::<Message>(&buf[..len - 1]),
+		Err(Error::UnexpectedEndOfInput)
+	));
+	assert!(matches!(probe::<Message>(&[7]), Err(Error::UnexpectedWireType)));
+
+	// integers beyond 64 bits
+	assert_eq!(
+		probe::<u128>(&to_bytes(&u128::MAX).unwrap()).unwrap().wire_type,
+		WireType::Int
+	);
 }
 
 #[test]
-fn test_map() {
-	use std::collections::HashMap;
+#[cfg(feature = "compression")]
+fn test_compressed() {
+	let v = vec![
+		LongStruct {
+			x: 1000,
+			y: -1000,
+			z: 12345
+		};
+		1000
+	];
+	let raw = to_bytes(&v).unwrap();
+	let buf = to_bytes_compressed(&v).unwrap();
+	assert_eq!(buf[0], 1);
+	assert!(buf.len() * 10 < raw.len());
+	assert_eq!(from_bytes_compressed::<Vec<LongStruct>>(&buf, raw.len()).unwrap(), v);
 
-	let value: HashMap<String, String> = vec![
-		("foo".to_string(), "bar".to_string()),
-		("aap".to_string(), "noot".to_string()),
-	]
-	.into_iter()
-	.collect();
-	assert_eq!(ser_de!(value.clone()), value);
+	// stored as is when compression doesn't help
+	let buf = to_bytes_compressed(&42u32).unwrap();
+	assert_eq!(buf, [&[0u8][..], &to_bytes(&42u32).unwrap()].concat());
+	assert_eq!(from_bytes_compressed::<u32>(&buf, 16).unwrap(), 42);
+
+	assert!(matches!(
+		from_bytes_compressed::<u32>(&[], 16),
+		Err(Error::UnexpectedEndOfInput)
+	));
+	assert!(matches!(
+		from_bytes_compressed::<u32>(&[2, 0], 16),
+		Err(Error::UnknownCompression(2))
+	));
+	assert!(matches!(
+		from_bytes_compressed::<u32>(&[1, 0xff, 0xff], 16),
+		Err(Error::IO(_))
+	));
+
+	// the inflated size is limited
+	let bomb = to_bytes_compressed(&serde_bytes::ByteBuf::from(vec![0u8; 1 << 20])).unwrap();
+	assert!(bomb.len() < 2000);
+	assert!(matches!(
+		from_bytes_compressed::<serde_bytes::ByteBuf>(&bomb, 1 << 16),
+		Err(Error::InputTooLarge)
+	));
+	let exact = (1 << 20) + 4;
+	assert_eq!(
+		from_bytes_compressed::<serde_bytes::ByteBuf>(&bomb, exact)
+			.unwrap()
+			.len(),
+		1 << 20
+	);
+	assert!(matches!(
+		from_bytes_compressed::<serde_bytes::ByteBuf>(&bomb, exact - 1),
+		Err(Error::InputTooLarge)
+	));
 }
 
 #[test]
-fn test_enum() {
-	#[derive(PartialEq, Eq, Serialize, Deserialize, Debug)]
+fn test_to_slice() {
+	let value = ShortStruct { x: 1000, y: -1000 };
+	let expect = to_bytes(&value).unwrap();
+
+	let mut buf = [0u8; 64];
+	let len = to_slice(&mut buf, &value).unwrap();
+	assert_eq!(len, expect.len());
+	assert_eq!(&buf[..len], &expect[..]);
+
+	let mut buf = [0u8; 64];
+	let len = to_slice(&mut buf[..expect.len()], &value).unwrap();
+	assert_eq!(len, expect.len());
+	assert!(matches!(
+		to_slice(&mut buf[..expect.len() - 1], &value),
+		Err(Error::BufferFull)
+	));
+	assert!(matches!(to_slice(&mut [], &value), Err(Error::BufferFull)));
+
+	// appending values
+	let mut buf = [0u8; 64];
+	let mut w = SliceWriter::new(&mut buf);
+	to_writer(&mut w, &value).unwrap();
+	to_writer(&mut w, "foo").unwrap();
+	let (first, rest) = from_prefix::<ShortStruct>(w.written()).unwrap();
+	assert_eq!(first, value);
+	assert_eq!(from_bytes::<&str>(rest).unwrap(), "foo");
+}
+
+#[test]
+fn test_max_bytes() {
+	// many small values, e.g. a map with one-byte keys and values
+	let v: Vec<(u8, u8)> = (0..1000).map(|i| (i as u8, 1)).collect();
+	let buf = to_bytes(&v).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).max_bytes(1000);
+	assert!(matches!(
+		Vec::<(u8, u8)>::deserialize(&mut de),
+		Err(Error::InputTooLarge)
+	));
+
+	// skipping counts too
+	let mut de = Deserializer::from_bytes(&buf).max_bytes(1000);
+	assert!(matches!(de.skip_value(), Err(Error::InputTooLarge)));
+
+	// the limit is inclusive
+	let mut de = Deserializer::from_bytes(&buf).max_bytes(buf.len());
+	assert_eq!(Vec::<(u8, u8)>::deserialize(&mut de).unwrap(), v);
+	let mut de = Deserializer::from_bytes(&buf).max_bytes(buf.len() - 1);
+	assert!(matches!(
+		Vec::<(u8, u8)>::deserialize(&mut de),
+		Err(Error::InputTooLarge)
+	));
+}
+
+#[test]
+fn test_skip_large() {
+	#[derive(Serialize)]
+	struct Long<'a> {
+		x: i32,
+		#[serde(with = "serde_bytes")]
+		blob: &'a [u8],
+		floats: Vec<f64>,
+		ints: Vec<u64>,
+		nested: Vec<Vec<f32>>,
+	}
+	#[derive(Deserialize, PartialEq, Debug)]
+	struct Short {
+		x: i32,
+	}
+	let blob = vec![0xffu8; 10_000_000];
+	let long = Long {
+		x: 42,
+		blob: &blob,
+		floats: vec![1.0; 100_000],
+		ints: (0..100_000).map(|i| i << 40).collect(),
+		nested: vec![vec![1.0; 10]; 10_000],
+	};
+	let buf = to_bytes(&long).unwrap();
+	assert_eq!(from_bytes::<Short>(&buf).unwrap(), Short { x: 42 });
+	assert!(matches!(
+		from_bytes::<Short>(&buf[..buf.len() - 1]),
+		Err(Error::UnexpectedEndOfInput)
+	));
+
+	// ignored fields are subject to the depth limit too
+	let mut buf = to_bytes(&(42i32,)).unwrap();
+	buf[0] = (2 << 3) | wire::WireType::Sequence as u8;
+	buf.extend(vec![(1 << 3) | wire::WireType::Sequence as u8; 200]);
+	buf.push(0);
+	let mut de = Deserializer::from_bytes(&buf).max_depth(100);
+	assert!(matches!(Short::deserialize(&mut de), Err(Error::DepthLimitExceeded)));
+	let mut de = Deserializer::from_bytes(&buf).max_depth(300);
+	assert_eq!(Short::deserialize(&mut de).unwrap(), Short { x: 42 });
+}
+
+#[test]
+fn test_discriminator() {
+	#[derive(Serialize)]
 	enum E {
-		Unit,
-		Newtype(i32),
-		Tuple(i32, i32),
-		Struct { x: i32, y: i32 },
+		A,
+		B(i32),
 	}
 
-	assert_eq!(ser_de!(E::Unit), E::Unit);
-	assert_eq!(ser_de!(E::Newtype(42)), E::Newtype(42));
-	assert_eq!(ser_de!(E::Tuple(42, 43)), E::Tuple(42, 43));
-	assert_eq!(ser_de!(E::Struct { x: 42, y: 43 }), E::Struct { x: 42, y: 43 });
+	for &discr in &[0u32, 1, 15, 16, 1000, u32::MAX] {
+		let mut buf = Vec::new();
+		write_discriminator(&mut buf, discr).unwrap();
+		assert_eq!(read_discriminator(&buf).unwrap(), (discr, buf.len()));
+
+		// followed by a payload
+		let len = buf.len();
+		to_writer(&mut buf, "foobar").unwrap();
+		assert_eq!(read_discriminator(&buf).unwrap(), (discr, len));
+		assert_eq!(from_bytes::<String>(&buf[len..]).unwrap(), "foobar");
+	}
+
+	// same layout as serde enums
+	let mut buf = Vec::new();
+	write_discriminator(&mut buf, 0).unwrap();
+	to_writer(&mut buf, &()).unwrap();
+	assert_eq!(buf, to_bytes(&E::A).unwrap());
+	buf.clear();
+	write_discriminator(&mut buf, 1).unwrap();
+	to_writer(&mut buf, &42).unwrap();
+	assert_eq!(buf, to_bytes(&E::B(42)).unwrap());
+
+	assert!(matches!(read_discriminator(&[]), Err(Error::UnexpectedEndOfInput)));
+	assert!(matches!(
+		read_discriminator(&to_bytes(&42).unwrap()),
+		Err(Error::UnexpectedWireType)
+	));
+	let mut buf = Vec::new();
+	wire::write_varint(&mut buf, wire::WireType::Variant, u32::MAX as u64 + 1).unwrap();
+	assert!(matches!(read_discriminator(&buf), Err(Error::ValueOverflow { .. })));
+}
+
+#[test]
+fn test_skip_value() {
+	let mut buf = Vec::new();
+	to_writer(&mut buf, &LongStruct { x: 1, y: 2, z: 3 }).unwrap();
+	to_writer(&mut buf, &Expr::Neg(Box::new(Expr::Lit(42)))).unwrap();
+	to_writer(&mut buf, "foobar").unwrap();
+	to_writer(&mut buf, &42.0f64).unwrap();
+	to_writer(&mut buf, &-42i64).unwrap();
+
+	let mut de = Deserializer::from_bytes(&buf);
+	de.skip_value().unwrap();
+	de.skip_value().unwrap();
+	assert_eq!(String::deserialize(&mut de).unwrap(), "foobar");
+	de.skip_value().unwrap();
+	assert_eq!(i64::deserialize(&mut de).unwrap(), -42);
+	assert_eq!(de.remaining_len(), 0);
+	assert!(matches!(de.skip_value(), Err(Error::UnexpectedEndOfInput)));
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
@@ -221,6 +3273,151 @@ fn test_long_struct_to_short() {
 	assert_eq!(dest, expected);
 }
 
+#[test]
+fn test_long_struct_position() {
+	let src = LongStruct { x: 1, y: 2, z: 3000 };
+	let expected = ShortStruct { x: 1, y: 2 };
+	let buf = to_bytes(&(src.clone(), -42i32)).unwrap();
+	assert_eq!(from_bytes::<(ShortStruct, i32)>(&buf).unwrap(), (expected.clone(), -42));
+
+	// concatenated messages
+	let mut buf = to_bytes(&src).unwrap();
+	to_writer(&mut buf, &-42i32).unwrap();
+	let mut de = Deserializer::from_bytes(&buf);
+	assert_eq!(ShortStruct::deserialize(&mut de).unwrap(), expected);
+	assert_eq!(i32::deserialize(&mut de).unwrap(), -42);
+	assert_eq!(de.remaining_len(), 0);
+
+	// evolved fields of variable length, after a variable-length field
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct V1 {
+		name: String,
+		tags: Vec<String>,
+	}
+	#[derive(Serialize)]
+	struct V2 {
+		name: String,
+		tags: Vec<String>,
+		history: Vec<LongStruct>,
+		note: Option<String>,
+	}
+	let v2 = V2 {
+		name: "foo".into(),
+		tags: vec!["a".into(), "b".into()],
+		history: vec![src.clone(), src],
+		note: Some("x".repeat(100)),
+	};
+	let buf = to_bytes(&(&v2, -42i32, &v2)).unwrap();
+	let v1 = V1 {
+		name: "foo".into(),
+		tags: vec!["a".into(), "b".into()],
+	};
+	let (a, n, b) = from_bytes::<(V1, i32, V1)>(&buf).unwrap();
+	assert_eq!((&a, n, &b), (&v1, -42, &v1));
+}
+
+#[test]
+fn test_trim_defaults() {
+	fn trimmed<T: Serialize>(v: &T) -> Vec<u8> {
+		let mut buf = Vec::new();
+		v.serialize(Serializer::new(&mut buf).trim_defaults(true)).unwrap();
+		buf
+	}
+
+	let long = LongStruct { x: 1, y: 2, z: 0 };
+	let buf = trimmed(&long);
+	assert_eq!(buf, to_bytes(&ShortStruct { x: 1, y: 2 }).unwrap());
+	assert_eq!(from_bytes::<LongStruct>(&buf).unwrap(), long);
+	// only trailing fields
+	let long = LongStruct { x: 0, y: 0, z: 3 };
+	assert_eq!(trimmed(&long), to_bytes(&long).unwrap());
+
+	#[derive(Serialize, Deserialize, Default, PartialEq, Debug)]
+	struct Inner {
+		#[serde(default)]
+		flag: bool,
+		#[serde(default)]
+		unit: (),
+	}
+	#[derive(Serialize, Deserialize, Default, PartialEq, Debug)]
+	struct Options {
+		#[serde(default)]
+		count: u32,
+		#[serde(default)]
+		delta: i64,
+		#[serde(default)]
+		name: String,
+		#[serde(default)]
+		parent: Option<u32>,
+		#[serde(default)]
+		tags: Vec<String>,
+		#[serde(default)]
+		ratio: f64,
+		#[serde(default)]
+		scale: Fixed<u32>,
+		#[serde(default)]
+		inner: Inner,
+	}
+	assert_eq!(trimmed(&Options::default()), [0x03]);
+	assert_eq!(from_bytes::<Options>(&[0x03]).unwrap(), Options::default());
+	let v = Options {
+		delta: -1,
+		..Default::default()
+	};
+	assert_eq!(trimmed(&v), [0x13, 0x00, 0x08]);
+	assert_eq!(from_bytes::<Options>(&trimmed(&v)).unwrap(), v);
+	let v = Options {
+		inner: Inner { flag: true, unit: () },
+		..Default::default()
+	};
+	assert!(trimmed(&v).len() < to_bytes(&v).unwrap().len());
+	assert_eq!(from_bytes::<Options>(&trimmed(&v)).unwrap(), v);
+
+	// also struct variants, but not sequences or tuples
+	#[derive(Serialize)]
+	enum E {
+		S { x: u32, y: u32 },
+	}
+	assert_eq!(trimmed(&E::S { x: 1, y: 0 }), [0x05, 0x0b, 0x08]);
+	assert_eq!(trimmed(&vec![0u32, 0]), to_bytes(&vec![0u32, 0]).unwrap());
+	assert_eq!(trimmed(&(1u32, 0u32)), to_bytes(&(1u32, 0u32)).unwrap());
+
+	// fields are trimmed by their encoding, so an enum field is only restored if its default is the first variant
+	#[derive(Serialize, Deserialize, Default, PartialEq, Debug)]
+	enum First {
+		#[default]
+		A,
+		B,
+	}
+	#[derive(Serialize, Deserialize, Default, PartialEq, Debug)]
+	enum Second {
+		A,
+		#[default]
+		B,
+	}
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct Modes {
+		x: u32,
+		#[serde(default)]
+		first: First,
+		#[serde(default)]
+		second: Second,
+	}
+	let v = Modes {
+		x: 1,
+		first: First::A,
+		second: Second::B,
+	};
+	assert_eq!(from_bytes::<Modes>(&trimmed(&v)).unwrap(), v);
+	let v = Modes {
+		x: 1,
+		first: First::B,
+		second: Second::A,
+	};
+	assert_eq!(trimmed(&v), [0x13, 0x08, 0x0d, 0x00]);
+	assert_eq!(from_bytes::<Modes>(&trimmed(&v)).unwrap().second, Second::B);
+}
+
 #[test]
 fn test_short_struct_to_long() {
 	let expected = vec![
@@ -245,6 +3442,103 @@ fn test_short_struct_to_long() {
 	assert_eq!(dest, expected);
 }
 
+#[test]
+fn test_long_struct_truncated_tail() {
+	let src = LongStruct { x: 1, y: 2, z: 3000 };
+	let buf = to_bytes(&src).unwrap();
+	assert!(matches!(
+		from_bytes::<ShortStruct>(&buf[..buf.len() - 1]),
+		Err(Error::UnexpectedEndOfInput)
+	));
+
+	// also when nested
+	let buf = to_bytes(&vec![src.clone(), src]).unwrap();
+	assert!(matches!(
+		from_bytes::<Vec<ShortStruct>>(&buf[..buf.len() - 1]),
+		Err(Error::UnexpectedEndOfInput)
+	));
+}
+
+#[test]
+fn test_damaged_input() {
+	// every truncation and every single-byte change must give an error or a value, never a panic
+	let src = (
+		vec![LongStruct { x: 1, y: -2, z: 3000 }; 3],
+		"foo".to_string(),
+		Some(Expr::Add(
+			Box::new(Expr::Lit(1)),
+			Box::new(Expr::Neg(Box::new(Expr::Lit(-7)))),
+		)),
+		u128::MAX,
+		1.5f64,
+	);
+	type T = (Vec<ShortStruct>, String, Option<Expr>, u128, f64);
+	let buf = to_bytes(&src).unwrap();
+	for n in 0..buf.len() {
+		assert!(from_bytes::<T>(&buf[..n]).is_err());
+		assert!(probe::<T>(&buf[..n]).is_err());
+	}
+	let mut damaged = buf.clone();
+	for i in 0..buf.len() {
+		for b in [0x00, 0x7f, 0x80, 0xff, buf[i] ^ 0x07] {
+			damaged[i] = b;
+			let _ = from_bytes::<T>(&damaged);
+			let _ = probe::<T>(&damaged);
+			let _ = Deserializer::from_bytes(&damaged).canonical(true).skip_value();
+		}
+		damaged[i] = buf[i];
+	}
+}
+
+#[test]
+fn test_huge_length() {
+	// lengths that don't fit in a 32-bit usize must not be truncated there; 2^32 + 3, followed by 3 bytes
+	let mut buf = Vec::new();
+	wire::write_varint(&mut buf, wire::WireType::Bytes, (1 << 32) + 3).unwrap();
+	buf.extend(b"foo");
+	assert!(matches!(from_bytes::<&str>(&buf), Err(Error::UnexpectedEndOfInput)));
+	assert!(matches!(
+		from_bytes::<serde_bytes::ByteBuf>(&buf),
+		Err(Error::UnexpectedEndOfInput)
+	));
+	assert!(matches!(
+		Deserializer::from_bytes(&buf).skip_value(),
+		Err(Error::UnexpectedEndOfInput)
+	));
+	assert!(matches!(read_length_delimited(&buf), Err(Error::UnexpectedEndOfInput)));
+
+	let mut buf = Vec::new();
+	wire::write_varint(&mut buf, wire::WireType::Sequence, (1 << 32) + 1).unwrap();
+	buf.push(0x08);
+	assert!(matches!(from_bytes::<Vec<u32>>(&buf), Err(Error::UnexpectedEndOfInput)));
+	assert!(matches!(
+		Deserializer::from_bytes(&buf).skip_value(),
+		Err(Error::UnexpectedEndOfInput)
+	));
+
+	for &wt in &[wire::WireType::Bytes, wire::WireType::Sequence] {
+		let mut buf = Vec::new();
+		wire::write_varint(&mut buf, wt, u64::MAX).unwrap();
+		assert!(matches!(
+			Deserializer::from_bytes(&buf).skip_value(),
+			Err(Error::UnexpectedEndOfInput)
+		));
+	}
+}
+
+#[test]
+#[cfg(target_pointer_width = "32")]
+fn test_huge_length_32bit() {
+	// one past the address space; would be truncated to length 0 by a plain cast
+	let mut buf = Vec::new();
+	wire::write_varint(&mut buf, wire::WireType::Bytes, usize::MAX as u64 + 1).unwrap();
+	assert!(matches!(from_bytes::<&[u8]>(&buf), Err(Error::UnexpectedEndOfInput)));
+	assert!(matches!(
+		Deserializer::from_bytes(&buf).skip_value(),
+		Err(Error::UnexpectedEndOfInput)
+	));
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
 struct LongTuple(i32, i32, #[serde(default)] i32);
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -271,6 +3565,37 @@ fn test_short_tuple_to_long() {
 	assert_eq!(dest, expected);
 }
 
+#[test]
+fn test_short_sequence_to_long() {
+	// the wire length determines which fields get their default
+	let buf = to_bytes(&(1, 2)).unwrap();
+	assert_eq!(from_bytes::<LongTuple>(&buf).unwrap(), LongTuple(1, 2, 0));
+	assert_eq!(
+		from_bytes::<LongStruct>(&buf).unwrap(),
+		LongStruct {
+			x: 1,
+			y: 2,
+			..Default::default()
+		}
+	);
+
+	// a missing field without default is a clear error
+	let buf = to_bytes(&(1,)).unwrap();
+	match from_bytes::<LongTuple>(&buf) {
+		Err(Error::InvalidLength { len: 1, expected, .. }) => assert!(expected.contains("LongTuple")),
+		other => panic!("unexpected result: {:?}", other),
+	}
+	assert!(matches!(
+		from_bytes::<LongStruct>(&buf),
+		Err(Error::InvalidLength { len: 1, .. })
+	));
+	let buf = to_bytes(&(1, 2)).unwrap();
+	assert!(matches!(
+		from_bytes::<(i32, i32, i32)>(&buf),
+		Err(Error::InvalidLength { len: 2, .. })
+	));
+}
+
 #[test]
 fn anonymous_tuple_to_named() {
 	let expected = vec![LongTuple(1, 2, 0), LongTuple(4, 5, 0), LongTuple(7, 8, 0)];
@@ -426,7 +3751,23 @@ fn extend_enum() {
 	// but vice versa should throw
 	let src = E2::Z("foobar".into());
 	let maybe_dest: std::result::Result<E1, _> = from_bytes(&to_bytes(&src).unwrap());
-	assert!(maybe_dest.is_err());
+	assert!(matches!(maybe_dest, Err(Error::UnknownVariant(2))));
+
+	// also for unit variants, and nested
+	#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+	enum U1 {
+		A,
+	}
+	#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+	enum U2 {
+		A,
+		B,
+	}
+	let buf = to_bytes(&(1u32, vec![U2::A, U2::B])).unwrap();
+	assert!(matches!(
+		from_bytes::<(u32, Vec<U1>)>(&buf),
+		Err(Error::UnknownVariant(1))
+	));
 }
 
 #[test]
@@ -450,6 +3791,66 @@ fn extend_enum_with_other() {
 	assert_eq!(dest, vec![E1::X(42), E1::Y(43), E1::Other,]);
 }
 
+#[test]
+fn extend_enum_lenient() {
+	#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+	enum E1 {
+		X(i32),
+		Y(i64),
+		Z,
+	}
+
+	// an unknown variant with content, followed by more data
+	let mut buf = Vec::new();
+	write_discriminator(&mut buf, 99).unwrap();
+	to_writer(&mut buf, &LongStruct { x: 1, y: 2, z: 3 }).unwrap();
+	let buf = to_bytes(&(serde_bytes::Bytes::new(&buf), 42u32)).unwrap();
+	let (raw, n): (&serde_bytes::Bytes, u32) = from_bytes(&buf).unwrap();
+	assert_eq!(n, 42);
+	assert_eq!(from_bytes::<Lenient<E1>>(raw).unwrap(), Lenient::Unknown(99));
+
+	let v = vec![Lenient::Known(E1::X(1)), Lenient::Unknown(99), Lenient::Known(E1::Z)];
+	assert_eq!(ser_de!(v.clone()), v);
+	let mut buf = Vec::new();
+	wire::write_varint(&mut buf, wire::WireType::Sequence, 3).unwrap();
+	to_writer(&mut buf, &E1::Y(5)).unwrap();
+	buf.extend(raw.iter());
+	to_writer(&mut buf, &E1::Z).unwrap();
+	assert_eq!(
+		from_bytes::<Vec<Lenient<E1>>>(&buf).unwrap(),
+		vec![Lenient::Known(E1::Y(5)), Lenient::Unknown(99), Lenient::Known(E1::Z)]
+	);
+
+	// unknown variants of enums nested in a known variant are still errors
+	#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+	enum Outer {
+		A(Vec<E1>),
+	}
+	let mut buf = Vec::new();
+	write_discriminator(&mut buf, 0).unwrap();
+	to_writer(&mut buf, &(E1::Z,)).unwrap();
+	assert_eq!(
+		from_bytes::<Lenient<Outer>>(&buf).unwrap(),
+		Lenient::Known(Outer::A(vec![E1::Z]))
+	);
+	let mut buf = Vec::new();
+	write_discriminator(&mut buf, 0).unwrap();
+	// a sequence of one element
+	buf.push(0x0b);
+	buf.extend(raw.iter());
+	assert!(matches!(
+		from_bytes::<Lenient<Outer>>(&buf),
+		Err(Error::UnknownVariant(99))
+	));
+
+	// same value in other formats
+	let json = serde_json::to_string(&Lenient::Known(E1::X(1))).unwrap();
+	assert_eq!(
+		serde_json::from_str::<Lenient<E1>>(&json).unwrap(),
+		Lenient::Known(E1::X(1))
+	);
+}
+
 #[test]
 fn skip_field() {
 	#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
@@ -463,8 +3864,254 @@ fn skip_field() {
 	assert_eq!(ser_de!(Foo { x: 42, y: 43, z: 44 }), Foo { x: 42, y: 0, z: 44 });
 }
 
+#[test]
+fn skip_field_conditionally() {
+	#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+	struct Foo {
+		x: i32,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		y: Option<i32>,
+	}
+	#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+	enum E {
+		Foo {
+			x: i32,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			y: Option<i32>,
+		},
+	}
+
+	assert_eq!(ser_de!(Foo { x: 42, y: Some(43) }), Foo { x: 42, y: Some(43) });
+	assert!(matches!(to_bytes(&Foo { x: 42, y: None }), Err(Error::CannotSkipField)));
+	assert!(matches!(
+		to_bytes(&E::Foo { x: 42, y: None }),
+		Err(Error::CannotSkipField)
+	));
+}
+
 #[test]
 fn test_readme_varint_example() {
     let v = to_bytes(&10042u32).unwrap();
     assert_eq!(v, vec![0xd0, 0xf3, 0x04]);
 }
+
+#[test]
+fn test_serialized_size() {
+	let value = LongStruct { x: 1, y: 2000, z: -3 };
+	assert_eq!(serialized_size(&value).unwrap(), to_bytes(&value).unwrap().len());
+	assert_eq!(serialized_size("foobar").unwrap(), 7);
+}
+
+#[test]
+fn test_to_writer_len() {
+	let mut buf = Vec::new();
+	let first = LongStruct { x: 1, y: 2000, z: -3 };
+	let second = "foobar".repeat(10);
+	let n1 = to_writer_len(&mut buf, &first).unwrap();
+	let n2 = to_writer_len(&mut buf, &second).unwrap();
+	assert_eq!(n1, serialized_size(&first).unwrap());
+	assert_eq!(n2, serialized_size(&second).unwrap());
+	assert_eq!(buf.len(), n1 + n2);
+	assert_eq!(from_bytes::<LongStruct>(&buf[..n1]).unwrap(), first);
+	assert_eq!(from_bytes::<String>(&buf[n1..]).unwrap(), second);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_limited_writer() {
+	let value = vec![LongStruct { x: 1, y: 2, z: 3 }; 100];
+	let size = serialized_size(&value).unwrap();
+
+	let mut w = LimitedWriter::new(Vec::new(), size);
+	to_writer(&mut w, &value).unwrap();
+	assert_eq!(w.remaining(), 0);
+	assert_eq!(w.into_inner(), to_bytes(&value).unwrap());
+
+	let mut w = LimitedWriter::new(Vec::new(), 64);
+	match to_writer(&mut w, &value) {
+		Err(Error::IO(e)) => assert_eq!(e.kind(), std::io::ErrorKind::WriteZero),
+		other => panic!("unexpected result: {:?}", other),
+	}
+	// stopped early
+	assert!(w.into_inner().len() <= 64);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_length_delimited() {
+	let values = vec![
+		LongStruct { x: 1, y: 2, z: 3 },
+		LongStruct {
+			x: 1000,
+			y: -2000,
+			z: 300000,
+		},
+		LongStruct::default(),
+	];
+	let mut buf = Vec::new();
+	for v in &values {
+		to_writer_length_delimited(&mut buf, v).unwrap();
+	}
+	let dest: Vec<LongStruct> = FrameReader::new(&buf[..]).collect::<Result<_>>().unwrap();
+	assert_eq!(dest, values);
+
+	// frames may be decoded into an evolved type
+	let dest: Vec<ShortStruct> = FrameReader::new(&buf[..]).collect::<Result<_>>().unwrap();
+	assert_eq!(dest, values.into_iter().map(Into::into).collect::<Vec<_>>());
+
+	// a large frame needs a multi-byte length
+	let long = vec!["x".repeat(1000)];
+	buf.clear();
+	to_writer_length_delimited(&mut buf, &long).unwrap();
+	let dest: Vec<Vec<String>> = FrameReader::new(&buf[..]).collect::<Result<_>>().unwrap();
+	assert_eq!(dest, vec![long]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_length_delimited_truncated() {
+	let mut buf = Vec::new();
+	to_writer_length_delimited(&mut buf, &"x".repeat(100)).unwrap();
+	to_writer_length_delimited(&mut buf, &"y".repeat(100)).unwrap();
+
+	// cut in the second frame's body
+	let mut reader = FrameReader::<_, String>::new(&buf[..buf.len() - 1]);
+	assert_eq!(reader.next().unwrap().unwrap(), "x".repeat(100));
+	assert!(matches!(reader.next(), Some(Err(Error::UnexpectedEndOfInput))));
+	assert!(reader.next().is_none());
+
+	// cut in the second frame's length prefix
+	let first_len = serialized_size(&"x".repeat(100)).unwrap() + 2;
+	let mut reader = FrameReader::<_, String>::new(&buf[..first_len + 1]);
+	assert!(reader.next().unwrap().is_ok());
+	assert!(matches!(reader.next(), Some(Err(Error::UnexpectedEndOfInput))));
+	assert!(reader.next().is_none());
+
+	// a huge claimed length isn't allocated up front
+	let mut huge = Vec::new();
+	wire::write_varint(&mut huge, wire::WireType::Bytes, 1 << 50).unwrap();
+	huge.extend_from_slice(b"foo");
+	let mut reader = FrameReader::<_, String>::new(&huge[..]);
+	assert!(matches!(reader.next(), Some(Err(Error::UnexpectedEndOfInput))));
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn test_compression_estimate() {
+	let repetitive = vec![LongStruct { x: 1, y: 2, z: 3 }; 1000];
+	let (raw, compressed) = compression_estimate(&repetitive).unwrap();
+	assert_eq!(raw, serialized_size(&repetitive).unwrap());
+	assert!(compressed * 10 < raw);
+
+	// xorshift, so we don't need a dependency for some noise
+	let mut state = 0x2545_f491_4f6c_dd1du64;
+	let noise: Vec<u64> = (0..1000)
+		.map(|_| {
+			state ^= state << 13;
+			state ^= state >> 7;
+			state ^= state << 17;
+			state
+		})
+		.collect();
+	let (raw, compressed) = compression_estimate(&noise).unwrap();
+	assert!(compressed * 10 > raw * 9);
+}
+
+#[test]
+fn test_canonical_varint() {
+	// 5, padded with an empty continuation byte
+	let buf = [(5 << 3) | 0x80, 0x80, 0x00];
+	assert_eq!(from_bytes::<u32>(&buf).unwrap(), 5);
+
+	let mut de = Deserializer::from_bytes(&buf).canonical(true);
+	assert!(matches!(u32::deserialize(&mut de), Err(Error::NonCanonicalVarint)));
+
+	// also when the padded value is skipped rather than read
+	let buf = [wire::WireType::Sequence as u8 | (1 << 3), (5 << 3) | 0x80, 0x80, 0x00];
+	assert_eq!(from_bytes::<()>(&buf).unwrap(), ());
+	let mut de = Deserializer::from_bytes(&buf).canonical(true);
+	assert!(matches!(<()>::deserialize(&mut de), Err(Error::NonCanonicalVarint)));
+
+	let value = LongStruct {
+		x: 10042,
+		y: -1,
+		z: i32::MIN,
+	};
+	let buf = to_bytes(&value).unwrap();
+	let mut de = Deserializer::from_bytes(&buf).canonical(true);
+	assert_eq!(LongStruct::deserialize(&mut de).unwrap(), value);
+}
+
+#[test]
+fn test_bitvec() {
+	#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+	struct Packed {
+		#[serde(with = "crate::bitvec")]
+		flags: Vec<bool>,
+	}
+	#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+	struct Unpacked {
+		flags: Vec<bool>,
+	}
+
+	for &n in &[0usize, 1, 7, 8, 9, 15, 16, 17, 100, 1000] {
+		let flags: Vec<bool> = (0..n).map(|i| i % 3 == 0 || i % 7 == 0).collect();
+		let packed = Packed { flags: flags.clone() };
+		assert_eq!(ser_de!(packed.clone()), packed);
+		if n > 8 {
+			assert!(serialized_size(&packed).unwrap() < serialized_size(&Unpacked { flags }).unwrap());
+		}
+	}
+
+	let packed = Packed {
+		flags: vec![true; 1000],
+	};
+	let buf = to_bytes(&packed).unwrap();
+	// struct + tuple tags, bit count, bytes header, 125 bytes of bits
+	assert_eq!(buf.len(), 1 + 1 + 2 + 2 + 125);
+
+	// hand-built, and a bit count that doesn't match the bytes
+	let buf = to_bytes(&[(8u64, serde_bytes::Bytes::new(&[0xff]))]).unwrap();
+	assert_eq!(from_bytes::<Packed>(&buf).unwrap().flags, vec![true; 8]);
+	let buf = to_bytes(&[(9u64, serde_bytes::Bytes::new(&[0xff]))]).unwrap();
+	assert!(from_bytes::<Packed>(&buf).is_err());
+	// bits beyond the count must be zero
+	let buf = to_bytes(&[(7u64, serde_bytes::Bytes::new(&[0x7f]))]).unwrap();
+	assert_eq!(from_bytes::<Packed>(&buf).unwrap().flags, vec![true; 7]);
+	let buf = to_bytes(&[(7u64, serde_bytes::Bytes::new(&[0xff]))]).unwrap();
+	assert!(matches!(from_bytes::<Packed>(&buf), Err(Error::InvalidValue { .. })));
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn test_bytes_crate() {
+	let value = bytes::Bytes::from_static(b"foobar");
+	let buf = to_bytes(&value).unwrap();
+	// compact bytes encoding: a single tag byte with the length
+	assert_eq!(buf.len(), 1 + 6);
+	assert_eq!(buf, to_bytes("foobar").unwrap());
+	assert_eq!(from_bytes::<bytes::Bytes>(&buf).unwrap(), value);
+
+	// zero-copy from a shared buffer, here after another value
+	let mut concat = to_bytes(&42).unwrap();
+	concat.extend_from_slice(&buf);
+	let buffer = bytes::Bytes::from(concat);
+	let mut de = Deserializer::from_bytes(&buffer);
+	assert_eq!(i32::deserialize(&mut de).unwrap(), 42);
+	let shared = de.read_shared_bytes(&buffer).unwrap();
+	assert_eq!(shared, value);
+	assert_eq!(shared.as_ptr(), buffer[buffer.len() - 6..].as_ptr());
+
+	// from another buffer, it's copied
+	let copy = bytes::Bytes::from(buf.clone());
+	let mut de = Deserializer::from_bytes(&buf);
+	assert_eq!(de.read_shared_bytes(&copy).unwrap(), value);
+
+	// as it is when the content runs past the end of the buffer
+	static DATA: [u8; 7] = *b"\x34foobar";
+	let buffer = bytes::Bytes::from_static(&DATA[..4]);
+	let mut de = Deserializer::from_bytes(&DATA);
+	let shared = de.read_shared_bytes(&buffer).unwrap();
+	assert_eq!(shared, value);
+	assert_ne!(shared.as_ptr(), DATA[1..].as_ptr());
+}
@@ -0,0 +1,101 @@
+//! Compact encoding of a time [`OffsetDateTime`] as a single varint of nanoseconds since the Unix epoch.
+//!
+//! The time crate's own serde encoding is a tuple of nine integers. Use this module on a field to encode it as a
+//! zigzag varint instead, which takes 10 bytes for current dates:
+//!
+//! ```
+//! # use serde::{Serialize, Deserialize};
+//! # use time::OffsetDateTime;
+//! #[derive(Serialize, Deserialize)]
+//! struct Event {
+//!     #[serde(with = "fcode::time_timestamp")]
+//!     at: OffsetDateTime,
+//!     #[serde(with = "fcode::time_timestamp::secs")]
+//!     created: OffsetDateTime,
+//! }
+//! ```
+//!
+//! Only the instant is encoded: the offset is lost, and decoded values are in UTC. The nanoseconds are encoded as an
+//! `i128`, so the full range of `OffsetDateTime` fits, and times before the epoch are negative. Where nanosecond
+//! precision isn't needed, [`secs`] takes 5 bytes for current dates and rounds down to whole seconds. Either encoding
+//! is incompatible with the time crate's default, so adding the attribute to an existing field is not a compatible
+//! change. The encoding is the same as that of [`chrono_timestamp`](crate::chrono_timestamp).
+//!
+//! Other serde formats see an integer.
+
+use core::fmt;
+use serde::de::{self, Deserializer, Visitor};
+use serde::ser::Serializer;
+use time::OffsetDateTime;
+
+pub fn serialize<S: Serializer>(value: &OffsetDateTime, serializer: S) -> Result<S::Ok, S::Error> {
+	serializer.serialize_i128(value.unix_timestamp_nanos())
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<OffsetDateTime, D::Error> {
+	deserializer.deserialize_i128(NanosVisitor)
+}
+
+struct NanosVisitor;
+
+impl<'de> Visitor<'de> for NanosVisitor {
+	type Value = OffsetDateTime;
+
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("a timestamp in nanoseconds")
+	}
+
+	fn visit_i128<E: de::Error>(self, v: i128) -> Result<OffsetDateTime, E> {
+		OffsetDateTime::from_unix_timestamp_nanos(v)
+			.map_err(|_| de::Error::invalid_value(de::Unexpected::Other("out of range timestamp"), &self))
+	}
+
+	// for other formats
+	fn visit_i64<E: de::Error>(self, v: i64) -> Result<OffsetDateTime, E> {
+		self.visit_i128(v as i128)
+	}
+
+	fn visit_u64<E: de::Error>(self, v: u64) -> Result<OffsetDateTime, E> {
+		self.visit_i128(v as i128)
+	}
+}
+
+/// Encoding of an [`OffsetDateTime`] as a single varint of seconds since the Unix epoch.
+///
+/// Sub-second precision is lost: times are rounded down to the second.
+pub mod secs {
+	use core::convert::TryFrom;
+	use core::fmt;
+	use serde::de::{self, Deserializer, Visitor};
+	use serde::ser::Serializer;
+	use time::OffsetDateTime;
+
+	pub fn serialize<S: Serializer>(value: &OffsetDateTime, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_i64(value.unix_timestamp())
+	}
+
+	pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<OffsetDateTime, D::Error> {
+		deserializer.deserialize_i64(SecsVisitor)
+	}
+
+	struct SecsVisitor;
+
+	impl<'de> Visitor<'de> for SecsVisitor {
+		type Value = OffsetDateTime;
+
+		fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+			f.write_str("a timestamp in seconds")
+		}
+
+		fn visit_i64<E: de::Error>(self, v: i64) -> Result<OffsetDateTime, E> {
+			OffsetDateTime::from_unix_timestamp(v)
+				.map_err(|_| de::Error::invalid_value(de::Unexpected::Signed(v), &self))
+		}
+
+		// for other formats
+		fn visit_u64<E: de::Error>(self, v: u64) -> Result<OffsetDateTime, E> {
+			let v = i64::try_from(v).map_err(|_| de::Error::invalid_value(de::Unexpected::Unsigned(v), &self))?;
+			self.visit_i64(v)
+		}
+	}
+}
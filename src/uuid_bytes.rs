@@ -0,0 +1,35 @@
+//! Encoding of a [`Uuid`] as exactly 16 bytes, regardless of the `uuid` crate's serde configuration.
+//!
+//! Use as `#[serde(with = "fcode::uuid_bytes")]`. The field takes 18 bytes on the wire: two bytes for the tag and
+//! length (16 doesn't fit in the tag byte), and the content. A plain `Uuid` field is encoded the same way, as fcode
+//! isn't a human-readable format, but this module also fixes the layout for other formats, and for fields where a
+//! string form would otherwise be used.
+
+use core::convert::TryInto;
+use core::fmt;
+use serde::de::{self, Deserializer, Visitor};
+use serde::ser::Serializer;
+use uuid::Uuid;
+
+pub fn serialize<S: Serializer>(value: &Uuid, serializer: S) -> Result<S::Ok, S::Error> {
+	serializer.serialize_bytes(value.as_bytes())
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Uuid, D::Error> {
+	deserializer.deserialize_bytes(UuidVisitor)
+}
+
+struct UuidVisitor;
+
+impl<'de> Visitor<'de> for UuidVisitor {
+	type Value = Uuid;
+
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("16 bytes")
+	}
+
+	fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Uuid, E> {
+		let bytes: [u8; 16] = v.try_into().map_err(|_| E::invalid_length(v.len(), &self))?;
+		Ok(Uuid::from_bytes(bytes))
+	}
+}
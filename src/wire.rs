@@ -1,38 +1,109 @@
-use crate::error::{Error, Result};
-use std::io::Write;
+//! Low-level access to the wire format, for tools that inspect or transform encoded data without knowing its types.
+//!
+//! The wire format is stable: data written by one version of this crate can be read by any later version, so the
+//! items in this module won't change meaning either.
+//!
+//! Every value starts with a tag byte, which has the wire type in the low 3 bits. If the wire type carries a varint
+//! (all but the fixed types), the tag byte also holds the low 4 bits of the varint and a continuation bit. Read the
+//! varint with [`read_varint`], passing the tag byte and the data after it:
+//!
+//! ```
+//! use fcode::wire::{self, WireType};
+//!
+//! let buf = fcode::to_bytes(&(1u32, "foo")).unwrap();
+//! assert_eq!(wire::read_wiretype(buf[0]), WireType::Sequence);
+//! let (len, varint_len) = wire::read_varint(buf[0], &buf[1..]).unwrap();
+//! assert_eq!((len, varint_len), (2, 0));
+//!
+//! // the first element is an integer in a single byte, the second a string
+//! assert_eq!(wire::read_wiretype(buf[1]), WireType::Int);
+//! assert_eq!(wire::skip_varint(buf[1], &buf[2..]).unwrap(), 0);
+//! assert_eq!(wire::read_wiretype(buf[2]), WireType::Bytes);
+//! assert_eq!(wire::read_varint(buf[2], &buf[3..]).unwrap(), (3, 0));
+//! assert_eq!(&buf[3..], b"foo");
+//! ```
 
-// A tag byte has the wire type in the low 3 bits. If the wire type is a varint
-// (Int, Sequence, Bytes, Variant), then it additionally has 4 bits of value,
-// and a stop bit as bit 7.
+use crate::{
+	error::{Error, Result},
+	Write,
+};
 
+/// The type of an encoded value, as stored in the low 3 bits of its tag byte.
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WireType {
-	Int = 0, // varint, up to 128 bits of data
+	/// Varint, up to 128 bits of data. Signed integers are zigzag-encoded.
+	Int = 0,
+	/// 4 bytes, little-endian by default.
 	Fixed32 = 1,
+	/// 8 bytes, little-endian by default.
 	Fixed64 = 2,
-	Sequence = 3, // varint length followed by this many encoded items
-	Bytes = 4,    // varint length, followed by u8 data
-	Variant = 5,  // varint discriminator, followed by single item; for Option it's 0 (None) or 1 (Some)
-	_Reserved1 = 6,
-	_Reserved2 = 7,
+	/// Varint length, followed by this many encoded items.
+	Sequence = 3,
+	/// Varint length, followed by this many bytes.
+	Bytes = 4,
+	/// Varint discriminator, followed by a single item. For `Option`, it's 0 (`None`) or 1 (`Some`).
+	Variant = 5,
+	/// Varint type code (one of the `TYPE_` constants), followed by a single item; for [`TYPE_ENUM`], the variant
+	/// name as bytes and the item.
+	Typed = 6,
+	/// Not used; decoders reject it.
+	Reserved = 7,
 }
 
+// Type codes of type-annotated values. These are written inside maps of unknown length (i.e. `#[serde(flatten)]`),
+// where serde needs to buffer values without knowing their type. All items nested in an annotated value are
+// annotated as well. The annotated item itself is encoded as usual, except for enums.
+/// `bool`, as an [`Int`](WireType::Int) of 0 or 1.
+pub const TYPE_BOOL: u64 = 0;
+/// Signed integer, as a zigzag-encoded [`Int`](WireType::Int).
+pub const TYPE_INT: u64 = 1;
+/// Unsigned integer, as an [`Int`](WireType::Int).
+pub const TYPE_UINT: u64 = 2;
+/// `f32` or `f64`, as [`Fixed32`](WireType::Fixed32) or [`Fixed64`](WireType::Fixed64).
+pub const TYPE_FLOAT: u64 = 3;
+/// `char`, as an [`Int`](WireType::Int) of its code point.
+pub const TYPE_CHAR: u64 = 4;
+/// String, as UTF-8 [`Bytes`](WireType::Bytes).
+pub const TYPE_STR: u64 = 5;
+/// Byte string, as [`Bytes`](WireType::Bytes).
+pub const TYPE_BYTES: u64 = 6;
+/// Unit, as an [`Int`](WireType::Int) of 0.
+pub const TYPE_UNIT: u64 = 7;
+/// `Option`, as a [`Variant`](WireType::Variant) of 0 or 1.
+pub const TYPE_OPTION: u64 = 8;
+/// Sequence, tuple or struct, as a [`Sequence`](WireType::Sequence).
+pub const TYPE_SEQ: u64 = 9;
+/// Map, as a [`Sequence`](WireType::Sequence) of keys and values.
+pub const TYPE_MAP: u64 = 10;
+/// Enum, as the variant name as [`Bytes`](WireType::Bytes), followed by the content without a variant tag.
+pub const TYPE_ENUM: u64 = 11;
+
+// type codes beyond the known ones are reserved, and rejected like wire type 7
+#[inline]
+pub(crate) fn check_type_code(code: u64) -> Result<u64> {
+	if code > TYPE_ENUM {
+		return Err(Error::UnexpectedWireType);
+	}
+	Ok(code)
+}
+
+/// Get the wire type from a tag byte.
 #[inline]
 pub fn read_wiretype(tagbyte: u8) -> WireType {
 	let tag = tagbyte & 7;
 	// safety: this is safe because every value from 0 to 7 is a valid wire type
-	unsafe { std::mem::transmute(tag) }
+	unsafe { core::mem::transmute(tag) }
 }
 
-// write a varint together with the wiretype tag
+/// Write a varint together with the wire type, i.e. a tag byte and as many bytes as needed for the rest of the value.
 #[inline]
-pub fn write_varint(writer: &mut impl Write, tag: WireType, mut value: u64) -> Result<()> {
+pub fn write_varint(writer: &mut (impl Write + ?Sized), tag: WireType, mut value: u64) -> Result<()> {
 	let tag = tag as u8;
 	let partial = ((value & 15) << 3) as u8;
 	value >>= 4;
 	if value == 0 {
-		writer.write(&[tag | partial])?;
+		writer.write_all(&[tag | partial])?;
 		return Ok(());
 	}
 	// 10 bytes supports 4 + 9 * 7 = 67 bits of data
@@ -60,8 +131,10 @@ pub fn write_varint(writer: &mut impl Write, tag: WireType, mut value: u64) -> R
 	Ok(())
 }
 
-// read a varint, given a tag byte and remaining data; returns the value and
-// the size consumed from data
+/// Read a varint, given a tag byte and the data following it. Returns the value and the size consumed from `data`.
+///
+/// The wire type isn't checked; it should be one that carries a varint. Values over 64 bits fail with
+/// [`Error::ValueOverflow`].
 #[inline]
 pub fn read_varint(tagbyte: u8, data: &[u8]) -> Result<(u64, usize)> {
 	if tagbyte & 0x80 == 0 {
@@ -73,8 +146,12 @@ pub fn read_varint(tagbyte: u8, data: &[u8]) -> Result<(u64, usize)> {
 	// note I've tested with a fast/slow variant, where the fast variant doesn't need
 	// to check for end of input, but it doesn't make it faster; the test is negligible
 	for (i, b) in data.iter().copied().enumerate() {
-		if shift >= 64 {
-			return Err(Error::ValueOverflow);
+		// the last byte that fits may only have the remaining bits, and no continuation
+		if shift > 64 - 7 && b >> (64 - shift) != 0 {
+			return Err(Error::ValueOverflow {
+				value: None,
+				target: "u64",
+			});
 		}
 		if b & 0x80 == 0 {
 			value |= (b as u64) << shift;
@@ -86,6 +163,17 @@ pub fn read_varint(tagbyte: u8, data: &[u8]) -> Result<(u64, usize)> {
 	Err(Error::UnexpectedEndOfInput)
 }
 
+// check that a varint, given the data consumed after the tag byte, is in its shortest form: the stop byte may not be
+// zero, as then the previous byte could have been the stop byte
+#[inline]
+pub(crate) fn check_canonical_varint(data: &[u8]) -> Result<()> {
+	match data.last() {
+		Some(0) => Err(Error::NonCanonicalVarint),
+		_ => Ok(()),
+	}
+}
+
+/// Skip a varint of up to 128 bits, given a tag byte and the data following it. Returns the size consumed from `data`.
 #[inline]
 pub fn skip_varint(tagbyte: u8, data: &[u8]) -> Result<usize> {
 	if tagbyte & 0x80 == 0 {
@@ -95,7 +183,10 @@ pub fn skip_varint(tagbyte: u8, data: &[u8]) -> Result<usize> {
 		// if we reach byte 18, we've consumed 19 bytes including tag byte, exceeding
 		// max encoding of a 128-bit varint
 		if i == 18 {
-			return Err(Error::ValueOverflow);
+			return Err(Error::ValueOverflow {
+				value: None,
+				target: "u128",
+			});
 		}
 		if b & 0x80 == 0 {
 			return Ok(i + 1);
@@ -116,79 +207,150 @@ fn test_varint() {
 	write_varint(&mut buf, WireType::Int, u64::MAX).unwrap();
 	assert_eq!(buf.len(), 10);
 	assert_eq!(read_varint(buf[0], &buf[1..]).unwrap(), (u64::MAX, 9));
+
+	// 65 bits in the same length
+	buf.clear();
+	write_varint_128(&mut buf, WireType::Int, 1 << 64).unwrap();
+	assert_eq!(buf.len(), 10);
+	assert!(read_varint(buf[0], &buf[1..]).is_err());
+}
+
+#[test]
+fn test_varint_near_max() {
+	let mut buf = vec![];
+	for &v in &[u64::MAX, u64::MAX - 1, 1 << 63, (1 << 63) - 1, 1 << 60, (1 << 60) - 1] {
+		buf.clear();
+		write_varint(&mut buf, WireType::Int, v).unwrap();
+		// 4 bits in the tag byte, and 7 per byte after that
+		let expected_len = if v >> 60 == 0 { 9 } else { 10 };
+		assert_eq!(buf.len(), expected_len, "{:#x}", v);
+		assert_eq!(read_varint(buf[0], &buf[1..]).unwrap(), (v, expected_len - 1));
+	}
+
+	// the 10th byte holds bits 60 to 63; anything above that overflows rather than being dropped
+	let mut max = vec![0xf8];
+	max.extend_from_slice(&[0xff; 8]);
+	for &last in &[0x10, 0x1f, 0x7f, 0x40] {
+		let mut data = max.clone();
+		data.push(last);
+		assert!(
+			matches!(
+				read_varint(data[0], &data[1..]),
+				Err(Error::ValueOverflow { target: "u64", .. })
+			),
+			"{:#x}",
+			last
+		);
+	}
+	// as does continuing past it, even with zero bits
+	for &last in &[0x8f, 0x80] {
+		let mut data = max.clone();
+		data.extend_from_slice(&[last, 0x00]);
+		assert!(matches!(
+			read_varint(data[0], &data[1..]),
+			Err(Error::ValueOverflow { .. })
+		));
+	}
+	max.push(0x0f);
+	assert_eq!(read_varint(max[0], &max[1..]).unwrap(), (u64::MAX, 9));
 }
 
-serde::serde_if_integer128! {
-	#[inline]
-	pub fn write_varint_128(writer: &mut impl Write, tag: WireType, mut value: u128) -> Result<()> {
-		let tag = tag as u8;
-		let partial = ((value & 15) << 3) as u8;
-		value >>= 4;
+#[inline]
+pub(crate) fn write_varint_128(writer: &mut (impl Write + ?Sized), tag: WireType, mut value: u128) -> Result<()> {
+	let tag = tag as u8;
+	let partial = ((value & 15) << 3) as u8;
+	value >>= 4;
+	if value == 0 {
+		writer.write_all(&[tag | partial])?;
+		return Ok(());
+	}
+	// 19 bytes supports 4 + 18 x 7 = 130 bits of data
+	let mut b = [0u8; 19];
+	b[0] = tag | partial | 0x80;
+	let mut len = 1;
+	loop {
+		let partial = (value & 0x7f) as u8;
+		value >>= 7;
 		if value == 0 {
-			writer.write(&[tag | partial])?;
-			return Ok(());
-		}
-		// 19 bytes supports 4 + 18 x 7 = 130 bits of data
-		let mut b = [0u8; 19];
-		b[0] = tag | partial | 0x80;
-		let mut len = 1;
-		loop {
-			let partial = (value & 0x7f) as u8;
-			value >>= 7;
-			if value == 0 {
-				// safety: we've calculcated that the local array can support the value
-				unsafe { *b.get_unchecked_mut(len) = partial; }
-				len += 1;
-				break;
-			}
 			// safety: we've calculcated that the local array can support the value
-			unsafe { *b.get_unchecked_mut(len) = partial | 0x80; }
+			unsafe {
+				*b.get_unchecked_mut(len) = partial;
+			}
 			len += 1;
+			break;
 		}
-		writer.write_all(&b[..len])?;
-		Ok(())
+		// safety: we've calculcated that the local array can support the value
+		unsafe {
+			*b.get_unchecked_mut(len) = partial | 0x80;
+		}
+		len += 1;
 	}
+	writer.write_all(&b[..len])?;
+	Ok(())
+}
+
+#[test]
+fn test_canonical_varint() {
+	let mut buf = vec![];
+	write_varint(&mut buf, WireType::Int, 10042).unwrap();
+	let (_, len) = read_varint(buf[0], &buf[1..]).unwrap();
+	assert!(check_canonical_varint(&buf[1..1 + len]).is_ok());
 
-	#[inline]
-	pub fn read_varint_128(tagbyte: u8, data: &[u8]) -> Result<(u128, usize)> {
-		if tagbyte & 0x80 == 0 {
-			let value = tagbyte >> 3;
-			return Ok((value as u128, 0));
+	// 5, padded with an empty continuation byte
+	let buf = [(5 << 3) | 0x80, 0x80, 0x00];
+	let (value, len) = read_varint(buf[0], &buf[1..]).unwrap();
+	assert_eq!((value, len), (5, 2));
+	assert!(check_canonical_varint(&buf[1..1 + len]).is_err());
+}
+
+#[inline]
+pub(crate) fn read_varint_128(tagbyte: u8, data: &[u8]) -> Result<(u128, usize)> {
+	if tagbyte & 0x80 == 0 {
+		let value = tagbyte >> 3;
+		return Ok((value as u128, 0));
+	}
+	let mut value = ((tagbyte & 0x7f) >> 3) as u128;
+	let mut shift = 4;
+	for (i, b) in data.iter().copied().enumerate() {
+		if shift > 128 - 7 && b >> (128 - shift) != 0 {
+			return Err(Error::ValueOverflow {
+				value: None,
+				target: "u128",
+			});
 		}
-		let mut value = ((tagbyte & 0x7f) >> 3) as u128;
-		let mut shift = 4;
-		for (i,b) in data.iter().copied().enumerate() {
-			if shift >= 128 {
-				return Err(Error::ValueOverflow);
-			}
-			if b & 0x80 == 0 {
-				value |= (b as u128) << shift;
-				return Ok((value, i + 1));
-			}
-			value |= ((b & 0x7f) as u128) << shift;
-			shift += 7;
+		if b & 0x80 == 0 {
+			value |= (b as u128) << shift;
+			return Ok((value, i + 1));
 		}
-		Err(Error::UnexpectedEndOfInput)
+		value |= ((b & 0x7f) as u128) << shift;
+		shift += 7;
 	}
+	Err(Error::UnexpectedEndOfInput)
+}
 
-	#[test]
-	fn test_varint_128() {
-		let mut buf = vec![];
+#[test]
+fn test_varint_128() {
+	let mut buf = vec![];
 
-		write_varint_128(&mut buf, WireType::Int, u128::MAX).unwrap();
-		assert_eq!(buf.len(), 19);
-		assert_eq!(read_varint_128(buf[0], &buf[1..]).unwrap(), (u128::MAX, 18));
-	}
+	write_varint_128(&mut buf, WireType::Int, u128::MAX).unwrap();
+	assert_eq!(buf.len(), 19);
+	assert_eq!(read_varint_128(buf[0], &buf[1..]).unwrap(), (u128::MAX, 18));
+
+	// 129 bits
+	*buf.last_mut().unwrap() |= 0x20;
+	assert!(read_varint_128(buf[0], &buf[1..]).is_err());
 }
 
 // signed varints use google's zig-zag method
 
+/// Map a signed integer to an unsigned one, such that small absolute values give small results.
 #[inline]
 pub fn zigzag_encode(value: i64) -> u64 {
 	let encoded = (value << 1) ^ (value >> 63);
 	encoded as u64
 }
 
+/// Inverse of [`zigzag_encode`].
 #[inline]
 pub fn zigzag_decode(encoded: u64) -> i64 {
 	(encoded >> 1) as i64 ^ -(encoded as i64 & 1)
@@ -206,30 +368,27 @@ fn test_zigzag() {
 	assert!(zigzag_encode(10) < zigzag_encode(-100));
 }
 
-serde::serde_if_integer128! {
-	#[inline]
-	pub fn zigzag_encode_128(value: i128) -> u128 {
-		let encoded = (value << 1) ^ (value >> 127);
-		encoded as u128
-	}
-
+#[inline]
+pub(crate) fn zigzag_encode_128(value: i128) -> u128 {
+	let encoded = (value << 1) ^ (value >> 127);
+	encoded as u128
+}
 
-	#[inline]
-	pub fn zigzag_decode_128(encoded: u128) -> i128 {
-		(encoded >> 1) as i128 ^ -(encoded as i128 & 1)
-	}
+#[inline]
+pub(crate) fn zigzag_decode_128(encoded: u128) -> i128 {
+	(encoded >> 1) as i128 ^ -(encoded as i128 & 1)
+}
 
-	#[test]
-	fn test_zigzag_128() {
-		assert_eq!(zigzag_decode_128(zigzag_encode_128(42)), 42);
-		assert_eq!(zigzag_decode_128(zigzag_encode_128(-42)), -42);
-		assert_eq!(zigzag_decode_128(zigzag_encode_128(i128::MAX)), i128::MAX);
-		assert_eq!(zigzag_decode_128(zigzag_encode_128(i128::MIN)), i128::MIN);
+#[test]
+fn test_zigzag_128() {
+	assert_eq!(zigzag_decode_128(zigzag_encode_128(42)), 42);
+	assert_eq!(zigzag_decode_128(zigzag_encode_128(-42)), -42);
+	assert_eq!(zigzag_decode_128(zigzag_encode_128(i128::MAX)), i128::MAX);
+	assert_eq!(zigzag_decode_128(zigzag_encode_128(i128::MIN)), i128::MIN);
 
-		assert_eq!(zigzag_decode_128(zigzag_encode(42) as u128), 42);
-		assert_eq!(zigzag_decode_128(zigzag_encode(-42) as u128), -42);
+	assert_eq!(zigzag_decode_128(zigzag_encode(42) as u128), 42);
+	assert_eq!(zigzag_decode_128(zigzag_encode(-42) as u128), -42);
 
-		assert_eq!(zigzag_decode(zigzag_encode_128(42) as u64), 42);
-		assert_eq!(zigzag_decode(zigzag_encode_128(-42) as u64), -42);
-	}
+	assert_eq!(zigzag_decode(zigzag_encode_128(42) as u64), 42);
+	assert_eq!(zigzag_decode(zigzag_encode_128(-42) as u64), -42);
 }
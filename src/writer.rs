@@ -0,0 +1,303 @@
+use crate::{
+	wire::{self, WireType},
+	Error, Result,
+};
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io;
+
+/// Output of the serializer.
+///
+/// With the `std` feature (enabled by default), this is implemented for every `std::io::Write`. Without it, it's
+/// implemented for `Vec<u8>`, and can be implemented for other byte sinks.
+pub trait Write {
+	/// Write all of `buf`.
+	fn write_all(&mut self, buf: &[u8]) -> Result<()>;
+
+	// called by the serializer around each field or element of a struct, tuple or sequence, and with the encoding of
+	// each field that was buffered rather than written (with trim_defaults); only ObservedWriter needs these, and the
+	// token argument keeps other crates from calling or implementing them
+	#[doc(hidden)]
+	#[inline]
+	fn enter_element(&mut self, _: Internal) {}
+
+	#[doc(hidden)]
+	#[inline]
+	fn leave_element(&mut self, _: Internal) {}
+
+	#[doc(hidden)]
+	#[inline]
+	fn buffered_element(&mut self, _encoded: &[u8], _: Internal) {}
+}
+
+mod sealed {
+	// can't be named outside this crate, as the module is private
+	#[derive(Clone, Copy)]
+	pub struct Internal;
+}
+
+pub(crate) use sealed::Internal;
+
+#[cfg(feature = "std")]
+impl<W: io::Write + ?Sized> Write for W {
+	#[inline]
+	fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+		io::Write::write_all(self, buf)?;
+		Ok(())
+	}
+}
+
+#[cfg(not(feature = "std"))]
+impl Write for Vec<u8> {
+	#[inline]
+	fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+		self.extend_from_slice(buf);
+		Ok(())
+	}
+}
+
+#[cfg(not(feature = "std"))]
+impl<W: Write + ?Sized> Write for &mut W {
+	#[inline]
+	fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+		(**self).write_all(buf)
+	}
+
+	#[inline]
+	fn enter_element(&mut self, internal: Internal) {
+		(**self).enter_element(internal)
+	}
+
+	#[inline]
+	fn leave_element(&mut self, internal: Internal) {
+		(**self).leave_element(internal)
+	}
+
+	#[inline]
+	fn buffered_element(&mut self, encoded: &[u8], internal: Internal) {
+		(**self).buffered_element(encoded, internal)
+	}
+}
+
+// writer that counts the bytes written through it
+pub(crate) struct CountingWriter<'a, W: ?Sized> {
+	pub inner: &'a mut W,
+	pub count: usize,
+}
+
+impl<'a, W: Write + ?Sized> Write for CountingWriter<'a, W> {
+	#[inline]
+	fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+		self.inner.write_all(buf)?;
+		self.count += buf.len();
+		Ok(())
+	}
+
+	#[inline]
+	fn enter_element(&mut self, internal: Internal) {
+		self.inner.enter_element(internal)
+	}
+
+	#[inline]
+	fn leave_element(&mut self, internal: Internal) {
+		self.inner.leave_element(internal)
+	}
+
+	#[inline]
+	fn buffered_element(&mut self, encoded: &[u8], internal: Internal) {
+		self.inner.buffered_element(encoded, internal)
+	}
+}
+
+// writer that discards its output
+pub(crate) struct Sink;
+
+impl Write for Sink {
+	#[inline]
+	fn write_all(&mut self, _buf: &[u8]) -> Result<()> {
+		Ok(())
+	}
+}
+
+/// A writer into a fixed slice, for serializing without heap allocation.
+///
+/// Unlike `&mut [u8]` as `std::io::Write`, which fails with a generic I/O error when full, this fails with
+/// [`Error::BufferFull`]. Nothing of the write that doesn't fit is written. See also [`to_slice`](crate::to_slice).
+pub struct SliceWriter<'a> {
+	buf: &'a mut [u8],
+	len: usize,
+}
+
+impl<'a> SliceWriter<'a> {
+	pub fn new(buf: &'a mut [u8]) -> Self {
+		SliceWriter { buf, len: 0 }
+	}
+
+	/// The number of bytes written so far.
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// The bytes written so far.
+	pub fn written(&self) -> &[u8] {
+		&self.buf[..self.len]
+	}
+}
+
+impl Write for SliceWriter<'_> {
+	#[inline]
+	fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+		let end = self.len + buf.len();
+		self.buf
+			.get_mut(self.len..end)
+			.ok_or(Error::BufferFull)?
+			.copy_from_slice(buf);
+		self.len = end;
+		Ok(())
+	}
+}
+
+/// A writer that calls an observer for every field or element of the top-level struct, tuple or sequence written
+/// through it, with its wire type and encoded length in bytes, e.g. to collect size metrics.
+///
+/// Nested values are not reported separately; they're part of the field that contains them. Newtypes and `Some` are
+/// looked through, so the fields of a `Some(struct)` are reported. Map entries (including flattened fields) are not
+/// reported, and with [`trim_defaults`](crate::Serializer::trim_defaults), trailing fields that are left out still
+/// are. Serializing to any other writer measures nothing.
+///
+/// ```
+/// # fn main() -> fcode::Result<()> {
+/// let mut sizes = Vec::new();
+/// let mut observer = |_, len| sizes.push(len);
+/// let mut w = fcode::ObservedWriter::new(Vec::new(), &mut observer);
+/// fcode::to_writer(&mut w, &(1u8, "foo"))?;
+/// let buf = w.into_inner();
+/// assert_eq!(sizes, [1, 4]);
+/// assert_eq!(buf.len(), 6);
+/// # Ok(())
+/// # }
+/// ```
+pub struct ObservedWriter<'a, W> {
+	inner: W,
+	observer: &'a mut dyn FnMut(WireType, usize),
+	// number of elements entered, so 1 inside a top-level field
+	depth: usize,
+	// tag byte and start of the current top-level field
+	tag: u8,
+	start: usize,
+	written: usize,
+}
+
+impl<'a, W: Write> ObservedWriter<'a, W> {
+	pub fn new(inner: W, observer: &'a mut dyn FnMut(WireType, usize)) -> Self {
+		ObservedWriter {
+			inner,
+			observer,
+			depth: 0,
+			tag: 0,
+			start: 0,
+			written: 0,
+		}
+	}
+
+	/// Unwrap the underlying writer.
+	pub fn into_inner(self) -> W {
+		self.inner
+	}
+}
+
+impl<W: Write> Write for ObservedWriter<'_, W> {
+	#[inline]
+	fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+		if self.depth == 1 && self.written == self.start {
+			if let Some(&tag) = buf.first() {
+				self.tag = tag;
+			}
+		}
+		self.inner.write_all(buf)?;
+		self.written += buf.len();
+		Ok(())
+	}
+
+	fn enter_element(&mut self, _: Internal) {
+		self.depth += 1;
+		if self.depth == 1 {
+			self.start = self.written;
+		}
+	}
+
+	fn leave_element(&mut self, _: Internal) {
+		if self.depth == 1 && self.written > self.start {
+			(self.observer)(wire::read_wiretype(self.tag), self.written - self.start);
+		}
+		self.depth -= 1;
+	}
+
+	fn buffered_element(&mut self, encoded: &[u8], _: Internal) {
+		if let (0, Some(&tag)) = (self.depth, encoded.first()) {
+			(self.observer)(wire::read_wiretype(tag), encoded.len());
+		}
+	}
+}
+
+/// A writer that fails once more than a given number of bytes is written to it.
+///
+/// Use this to bail out early when serializing untrusted data, rather than serializing into an unbounded buffer and
+/// checking the size afterwards:
+///
+/// ```
+/// let mut w = fcode::LimitedWriter::new(Vec::new(), 64 * 1024);
+/// fcode::to_writer(&mut w, &vec![42u64; 100]).unwrap();
+/// let buf = w.into_inner();
+/// ```
+///
+/// Exceeding the limit results in [`Error::IO`](crate::Error::IO), with error kind
+/// [`WriteZero`](io::ErrorKind::WriteZero). Nothing of the write that exceeds the limit is written.
+#[cfg(feature = "std")]
+pub struct LimitedWriter<W> {
+	inner: W,
+	remaining: usize,
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write> LimitedWriter<W> {
+	pub fn new(inner: W, limit: usize) -> Self {
+		LimitedWriter {
+			inner,
+			remaining: limit,
+		}
+	}
+
+	/// The number of bytes that can still be written.
+	pub fn remaining(&self) -> usize {
+		self.remaining
+	}
+
+	/// Unwrap the underlying writer.
+	pub fn into_inner(self) -> W {
+		self.inner
+	}
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write> io::Write for LimitedWriter<W> {
+	#[inline]
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		if buf.len() > self.remaining {
+			return Err(io::Error::new(io::ErrorKind::WriteZero, "output size limit exceeded"));
+		}
+		let n = self.inner.write(buf)?;
+		self.remaining -= n;
+		Ok(n)
+	}
+
+	#[inline]
+	fn flush(&mut self) -> io::Result<()> {
+		self.inner.flush()
+	}
+}
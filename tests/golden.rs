@@ -0,0 +1,198 @@
+//! The exact encoding of representative values. These pin the wire format: a failure here means that data written by
+//! an earlier version can no longer be read, so any change must be deliberate.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Point {
+	x: i32,
+	y: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Expr {
+	Lit(i32),
+	Add(Box<Expr>, Box<Expr>),
+	Neg(Box<Expr>),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Flat {
+	a: i32,
+	#[serde(flatten)]
+	point: Point,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct FlatMixed {
+	#[serde(flatten)]
+	point: Point,
+	shape: Shape,
+	label: Option<String>,
+	flag: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Shape {
+	Empty,
+	Circle { radius: u32 },
+}
+
+// encodes to exactly the expected bytes, and decodes back
+fn golden<T>(value: T, expected: &[u8])
+where
+	T: Serialize + for<'de> Deserialize<'de> + Debug + PartialEq,
+{
+	let buf = fcode::to_bytes(&value).unwrap();
+	assert_eq!(buf, expected, "encoding of {:?}", value);
+	assert_eq!(fcode::from_bytes::<T>(expected).unwrap(), value);
+}
+
+#[test]
+fn integers() {
+	// tag byte: wire type in bits 0-2, value bits 0-3 in bits 3-6, continuation in bit 7; then 7 bits per byte
+	golden(0u32, &[0x00]);
+	golden(7u8, &[0x38]);
+	golden(15u64, &[0x78]);
+	golden(16u64, &[0x80, 0x01]);
+	golden(42u32, &[0xd0, 0x02]);
+	golden(10042u32, &[0xd0, 0xf3, 0x04]);
+	golden(u64::MAX, &[0xf8, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x0f]);
+	// signed integers are zigzag encoded: 0, -1, 1, -2, ... map to 0, 1, 2, 3, ...
+	golden(-1i32, &[0x08]);
+	golden(1i32, &[0x10]);
+	golden(42i32, &[0xa0, 0x05]);
+	golden(i64::MIN, &[0xf8, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x0f]);
+	golden(true, &[0x08]);
+	golden(false, &[0x00]);
+	golden('a', &[0x88, 0x06]);
+	golden((), &[0x00]);
+	golden(fcode::Fixed(1u32), &[0x01, 0x01, 0x00, 0x00, 0x00]);
+	golden(fcode::Fixed(-2i64), &[0x02, 0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+}
+
+#[test]
+fn unit_bool_int() {
+	// unit and booleans are integers on the wire, which allows a field to evolve between these types
+	golden((), &[0x00]);
+	golden(false, &[0x00]);
+	golden(0u8, &[0x00]);
+	golden(true, &[0x08]);
+	golden(1u8, &[0x08]);
+	assert_eq!(fcode::to_bytes(&()).unwrap(), fcode::to_bytes(&false).unwrap());
+	assert_eq!(fcode::to_bytes(&false).unwrap(), fcode::to_bytes(&0u8).unwrap());
+	assert_eq!(fcode::to_bytes(&true).unwrap(), fcode::to_bytes(&1u8).unwrap());
+	assert!(!fcode::from_bytes::<bool>(&fcode::to_bytes(&()).unwrap()).unwrap());
+	assert_eq!(fcode::from_bytes::<u8>(&fcode::to_bytes(&true).unwrap()).unwrap(), 1);
+	assert!(fcode::from_bytes::<bool>(&[0x38]).unwrap());
+	// a unit accepts anything
+	fcode::from_bytes::<()>(&[0x08]).unwrap();
+	fcode::from_bytes::<()>(b"\x1cfoo").unwrap();
+}
+
+#[test]
+fn floats() {
+	// wire type 1 (fixed32) and 2 (fixed64), little-endian
+	golden(1.5f32, &[0x01, 0x00, 0x00, 0xc0, 0x3f]);
+	golden(1.5f64, &[0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf8, 0x3f]);
+}
+
+#[test]
+fn bytes() {
+	// wire type 4, with the length as varint
+	golden("foo".to_string(), b"\x1cfoo");
+	golden(String::new(), &[0x04]);
+	golden("x".repeat(16), &[b"\x84\x01"[..].to_vec(), b"x".repeat(16)].concat());
+	golden(serde_bytes::ByteBuf::from(vec![1, 2]), &[0x14, 0x01, 0x02]);
+}
+
+#[test]
+fn sequences() {
+	// wire type 3, with the number of items as varint; structs, tuples and sequences are the same
+	golden(Point { x: 1, y: -2 }, &[0x13, 0x10, 0x18]);
+	golden((1i32, -2i32), &[0x13, 0x10, 0x18]);
+	golden(vec![1u8, 2], &[0x13, 0x08, 0x10]);
+	golden(Vec::<u8>::new(), &[0x03]);
+	// maps have alternating keys and values, so the count is twice the number of entries
+	golden(BTreeMap::from([(1u32, 2u32), (3, 4)]), &[0x23, 0x08, 0x10, 0x18, 0x20]);
+}
+
+#[test]
+fn enums() {
+	// wire type 5, with the discriminator as varint, followed by exactly one item; units are integer 0
+	golden(Some(1u32), &[0x0d, 0x08]);
+	golden(None::<u32>, &[0x05, 0x00]);
+	golden(Expr::Lit(3), &[0x05, 0x30]);
+	golden(Expr::Neg(Box::new(Expr::Lit(3))), &[0x15, 0x05, 0x30]);
+	golden(
+		Expr::Add(Box::new(Expr::Lit(1)), Box::new(Expr::Lit(-1))),
+		&[0x0d, 0x13, 0x05, 0x10, 0x05, 0x08],
+	);
+	golden(Shape::Empty, &[0x05, 0x00]);
+	golden(Shape::Circle { radius: 2 }, &[0x0d, 0x0b, 0x10]);
+}
+
+#[test]
+fn typed() {
+	// structs with flattened fields are maps of unknown length, buffered and written as a sequence of alternating keys
+	// and values; every item has a type annotation: wire type 6, with the type code as varint
+	golden(
+		Flat {
+			a: 1,
+			point: Point { x: 2, y: -3 },
+		},
+		&[
+			0x33, // 6 items
+			0x2e, 0x0c, b'a', // TYPE_STR (5), "a"
+			0x0e, 0x10, // TYPE_INT (1), 1
+			0x2e, 0x0c, b'x', 0x0e, 0x20, // "x", 2
+			0x2e, 0x0c, b'y', 0x0e, 0x28, // "y", -3
+		],
+	);
+	// enums are identified by name, and nested values are annotated too
+	golden(
+		FlatMixed {
+			point: Point { x: 0, y: 1 },
+			shape: Shape::Circle { radius: 2 },
+			label: Some("x".into()),
+			flag: true,
+		},
+		&[
+			&[0x53][..], // 10 items
+			&[0x2e, 0x0c, b'x', 0x0e, 0x00],
+			&[0x2e, 0x0c, b'y', 0x0e, 0x10],
+			b"\x2e\x2cshape",
+			// TYPE_ENUM (11), the variant name, and the content: TYPE_SEQ (9) of one TYPE_UINT (2)
+			b"\x5e\x34Circle\x4e\x0b\x16\x10",
+			b"\x2e\x2clabel",
+			// TYPE_OPTION (8), Some, and the annotated content
+			&[0x46, 0x0d, 0x2e, 0x0c, b'x'],
+			b"\x2e\x24flag",
+			&[0x06, 0x08], // TYPE_BOOL (0), true
+		]
+		.concat(),
+	);
+	golden(
+		FlatMixed {
+			point: Point { x: 0, y: 1 },
+			shape: Shape::Empty,
+			label: None,
+			flag: false,
+		},
+		&[
+			&[0x53][..],
+			&[0x2e, 0x0c, b'x', 0x0e, 0x00],
+			&[0x2e, 0x0c, b'y', 0x0e, 0x10],
+			b"\x2e\x2cshape",
+			// a unit variant has TYPE_UNIT (7) content
+			b"\x5e\x2cEmpty\x3e\x00",
+			b"\x2e\x2clabel",
+			&[0x46, 0x05, 0x3e, 0x00],
+			b"\x2e\x24flag",
+			&[0x06, 0x00],
+		]
+		.concat(),
+	);
+}